@@ -0,0 +1,59 @@
+//! Compares [`lexer::lex`] with the chumsky lexer it replaced, on generated sources of a few
+//! sizes. Run with `cargo bench --bench lexer`.
+
+use chumsky::Parser;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use dberd::lexer;
+
+#[path = "../tests/lexer/chumsky.rs"]
+mod chumsky_lexer;
+
+/// A made up module that uses every kind of token, repeated until it is about `size` bytes long.
+fn generate(size: usize) -> String {
+    let mut source = String::with_capacity(size + 1024);
+    let mut i = 0usize;
+    while source.len() < size {
+        source.push_str(&format!(
+            "# Entity number {i}, with a comment that has some ünïcödé in it
+entity Table{i} {{
+    id: int pk,
+    name_{i}: string default \"row \\\"{i}\\\"\\n\\u{{1F600}}\" check :gt{{:len{{name_{i}}}, 0}},
+    score: float default {i}.5e-3 check :ge{{score, 0x1F}}
+}}
+relation Table{i} 0..* Table{}
+
+let f{i} = {{a, b}} -> :add{{a, (b << 2) ^ 0b1010 | {i} & 7 >> 1}} in
+let g{i} = {{x}} -> :f{i}{{x / 3, true}} in
+",
+            i.saturating_sub(1)
+        ));
+        i += 1;
+    }
+    source.push_str("{} -> false");
+    source
+}
+
+fn lexers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    // The chumsky lexer takes seconds per megabyte.
+    group.sample_size(10);
+    let chumsky = chumsky_lexer::lexer();
+    for size in [16 * 1024, 256 * 1024, 1024 * 1024] {
+        let source = generate(size);
+        let tokens = lexer::lex(&source).expect("the generated source should lex");
+        assert_eq!(chumsky.parse(source.as_str()).ok(), Some(tokens), "the lexers disagree");
+
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::new("hand-written", size), &source, |b, source| {
+            b.iter(|| lexer::lex(source))
+        });
+        group.bench_with_input(BenchmarkId::new("chumsky", size), &source, |b, source| {
+            b.iter(|| chumsky.parse(source.as_str()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lexers);
+criterion_main!(benches);
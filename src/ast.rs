@@ -0,0 +1,378 @@
+//! The syntax tree of a module as JSON, for tools that want to analyse dberd code without parsing
+//! it themselves. `dberd parse --emit ast-json` writes it, and `dberd run --input ast-json` reads
+//! it back.
+//!
+//! # Format, version 1
+//!
+//! The document is an object `{"version": 1, "tests": [Test], "entities": [Entity],
+//! "relations": [Relation], "expr": Expr | null}`. Readers should reject versions they don't
+//! know; fields are only ever added within a version.
+//!
+//! - A span is `{"start": n, "end": n}`, counting chars (not bytes) from the start of the file,
+//!   with an exclusive end. A spanned name is `{"value": "name", "span": Span}`.
+//! - An `Expr` is an object with a `"kind"` and a `"span"`:
+//!   - `literal`: `"value": Literal`
+//!   - `ident`: `"name": "x"`
+//!   - `call`: `"fun": Expr, "args": [Expr]`
+//!   - `let`: `"ident": Expr, "value": Expr, "body": Expr`
+//!   - `grouping`: `"expr": Expr`
+//!   - `binary`: `"op": "+" | "/" | "<<" | ">>" | "&" | "^" | "|", "lhs": Expr, "rhs": Expr`
+//! - A `Literal` is an object with a `"type"`:
+//!   - `integer`: `"value": "123"`, a string so that integers of any size stay exact
+//!   - `rational`: `"numerator": "1", "denominator": "3"`
+//!   - `float`: `"value": 2.5`
+//!   - `string`: `"value": "text"`
+//!   - `boolean`: `"value": true`
+//!   - `array`: `"items": [Expr]`
+//!   - `record`: `"fields": [{"name": "x", "value": Expr}]`
+//!   - `function`: `"params": [Expr], "body": Expr`
+//! - A `Test` is `{"name": Spanned, "body": Expr}`.
+//! - An `Entity` is `{"name": Spanned, "renamed_from": Spanned | null, "columns": [Column],
+//!   "span": Span}`, and a `Column` is `{"name": Spanned, "type": Spanned, "constraints":
+//!   [Spanned], "default": Expr | null, "checks": [Expr], "renamed_from": Spanned | null}`.
+//! - A `Relation` is `{"from": Spanned, "cardinality": {"min": n, "max": n | null, "span":
+//!   Span}, "to": Spanned, "span": Span}`, where a `max` of `null` has no upper bound.
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use serde_json::{json, Value};
+
+use crate::{
+    lexer::{Span, Spanned},
+    number::Number,
+    parser::{BinaryOp, Expr, LiteralValue, Module, Test},
+    schema::{Cardinality, Column, ColumnType, Constraint, Entity, Relation},
+};
+
+/// The version of the format that [`to_json`] writes and [`from_json`] reads.
+pub const VERSION: u64 = 1;
+
+fn span_json(span: &Span) -> Value {
+    json!({ "start": span.start, "end": span.end })
+}
+
+fn spanned_json<T: ToString>((value, span): &Spanned<T>) -> Value {
+    json!({ "value": value.to_string(), "span": span_json(span) })
+}
+
+fn literal_json(literal: &LiteralValue) -> Value {
+    match literal {
+        LiteralValue::Number(number @ (Number::Integer(_) | Number::Big(_))) => {
+            json!({ "type": "integer", "value": number.to_string() })
+        }
+        LiteralValue::Number(Number::Rational(rational)) => json!({
+            "type": "rational",
+            "numerator": rational.numer().to_string(),
+            "denominator": rational.denom().to_string(),
+        }),
+        LiteralValue::Number(Number::Float(float)) => json!({ "type": "float", "value": float }),
+        LiteralValue::String(string) => json!({ "type": "string", "value": string }),
+        LiteralValue::Boolean(boolean) => json!({ "type": "boolean", "value": boolean }),
+        LiteralValue::Array(items) => json!({ "type": "array", "items": items.iter().map(expr_json).collect::<Vec<_>>() }),
+        LiteralValue::Record(fields) => json!({
+            "type": "record",
+            "fields": fields
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": expr_json(value) }))
+                .collect::<Vec<_>>(),
+        }),
+        LiteralValue::Function { params, body } => json!({
+            "type": "function",
+            "params": params.iter().map(expr_json).collect::<Vec<_>>(),
+            "body": expr_json(body),
+        }),
+    }
+}
+
+fn expr_json((expr, span): &Spanned<Expr>) -> Value {
+    let mut value = match expr {
+        Expr::Literal(literal) => json!({ "kind": "literal", "value": literal_json(literal) }),
+        Expr::Ident(name) => json!({ "kind": "ident", "name": name }),
+        Expr::Call { fun, args } => json!({
+            "kind": "call",
+            "fun": expr_json(fun),
+            "args": args.iter().map(expr_json).collect::<Vec<_>>(),
+        }),
+        Expr::Let { ident, value, body } => json!({
+            "kind": "let",
+            "ident": expr_json(ident),
+            "value": expr_json(value),
+            "body": expr_json(body),
+        }),
+        Expr::Grouping(inner) => json!({ "kind": "grouping", "expr": expr_json(inner) }),
+        Expr::Binary { op, lhs, rhs } => json!({
+            "kind": "binary",
+            "op": op.to_string(),
+            "lhs": expr_json(lhs),
+            "rhs": expr_json(rhs),
+        }),
+    };
+    value["span"] = span_json(span);
+    value
+}
+
+fn column_json(column: &Column) -> Value {
+    json!({
+        "name": spanned_json(&column.name),
+        "type": spanned_json(&column.ty),
+        "constraints": column.constraints.iter().map(spanned_json).collect::<Vec<_>>(),
+        "default": column.default.as_ref().map(expr_json),
+        "checks": column.checks.iter().map(expr_json).collect::<Vec<_>>(),
+        "renamed_from": column.renamed_from.as_ref().map(spanned_json),
+    })
+}
+
+/// Writes a module in the format described at the top of this module.
+pub fn to_json(module: &Module) -> Value {
+    json!({
+        "version": VERSION,
+        "tests": module
+            .tests
+            .iter()
+            .map(|test| json!({ "name": spanned_json(&test.name), "body": expr_json(&test.body) }))
+            .collect::<Vec<_>>(),
+        "entities": module
+            .entities
+            .iter()
+            .map(|entity| json!({
+                "name": spanned_json(&entity.name),
+                "renamed_from": entity.renamed_from.as_ref().map(spanned_json),
+                "columns": entity.columns.iter().map(column_json).collect::<Vec<_>>(),
+                "span": span_json(&entity.span),
+            }))
+            .collect::<Vec<_>>(),
+        "relations": module
+            .relations
+            .iter()
+            .map(|relation| json!({
+                "from": spanned_json(&relation.from),
+                "cardinality": {
+                    "min": relation.cardinality.0.min,
+                    "max": relation.cardinality.0.max,
+                    "span": span_json(&relation.cardinality.1),
+                },
+                "to": spanned_json(&relation.to),
+                "span": span_json(&relation.span),
+            }))
+            .collect::<Vec<_>>(),
+        "expr": module.expr.as_ref().map(expr_json),
+    })
+}
+
+/// A JSON value being read, with the path it was found at for error messages, e.g.
+/// `expr.body.args[1]`.
+#[derive(Clone, Copy)]
+struct Node<'a, 'p> {
+    value: &'a Value,
+    path: &'p str,
+}
+
+impl<'a> Node<'a, '_> {
+    fn error<T>(&self, message: impl std::fmt::Display) -> Result<T, String> {
+        Err(format!("{}: {message}", self.path))
+    }
+
+    /// Reads a field, calling `read` with it and its path.
+    fn field<T>(&self, name: &str, read: impl FnOnce(Node<'a, '_>) -> Result<T, String>) -> Result<T, String> {
+        let Some(object) = self.value.as_object() else {
+            return self.error("expected an object");
+        };
+        let path = format!("{}.{name}", self.path);
+        match object.get(name) {
+            Some(value) => read(Node { value, path: &path }),
+            None => self.error(format!("missing '{name}'")),
+        }
+    }
+
+    /// Reads a field that may be missing or `null`.
+    fn optional<T>(&self, name: &str, read: impl FnOnce(Node<'a, '_>) -> Result<T, String>) -> Result<Option<T>, String> {
+        match self.value.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(_) => self.field(name, read).map(Some),
+        }
+    }
+
+    fn items<T>(&self, mut read: impl FnMut(Node<'a, '_>) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let Some(items) = self.value.as_array() else {
+            return self.error("expected an array");
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, value)| read(Node { value, path: &format!("{}[{i}]", self.path) }))
+            .collect()
+    }
+
+    fn str(&self) -> Result<&'a str, String> {
+        self.value.as_str().map_or_else(|| self.error("expected a string"), Ok)
+    }
+
+    fn u64(&self) -> Result<u64, String> {
+        self.value.as_u64().map_or_else(|| self.error("expected a non-negative integer"), Ok)
+    }
+
+    fn big(&self) -> Result<BigInt, String> {
+        let text = self.str()?;
+        text.parse().map_or_else(|_| self.error(format!("'{text}' is not an integer")), Ok)
+    }
+}
+
+fn span(node: Node) -> Result<Span, String> {
+    let start = node.field("start", |node| node.u64())? as usize;
+    let end = node.field("end", |node| node.u64())? as usize;
+    if end < start {
+        return node.error("the span ends before it starts");
+    }
+    Ok(start..end)
+}
+
+fn spanned<T>(node: Node, read: impl FnOnce(&str) -> Option<T>) -> Result<Spanned<T>, String> {
+    let value = node.field("value", |node| {
+        let text = node.str()?;
+        read(text).map_or_else(|| node.error(format!("'{text}' is not valid here")), Ok)
+    })?;
+    Ok((value, node.field("span", span)?))
+}
+
+fn name(node: Node) -> Result<Spanned<String>, String> {
+    spanned(node, |name| Some(name.to_string()))
+}
+
+fn literal(node: Node) -> Result<LiteralValue, String> {
+    let ty = node.field("type", |node| node.str())?;
+    Ok(match ty {
+        "integer" => LiteralValue::Number(Number::from(node.field("value", |node| node.big())?)),
+        "rational" => {
+            let numerator = node.field("numerator", |node| node.big())?;
+            let denominator = node.field("denominator", |node| node.big())?;
+            if denominator == BigInt::from(0) {
+                return node.error("the denominator is zero");
+            }
+            LiteralValue::Number(Number::from(BigRational::new(numerator, denominator)))
+        }
+        "float" => LiteralValue::Number(Number::Float(
+            node.field("value", |node| node.value.as_f64().map_or_else(|| node.error("expected a number"), Ok))?,
+        )),
+        "string" => LiteralValue::String(node.field("value", |node| node.str())?.into()),
+        "boolean" => LiteralValue::Boolean(
+            node.field("value", |node| node.value.as_bool().map_or_else(|| node.error("expected a boolean"), Ok))?,
+        ),
+        "array" => LiteralValue::Array(node.field("items", |node| node.items(expr))?),
+        "record" => LiteralValue::Record(node.field("fields", |node| {
+            node.items(|field| Ok((field.field("name", |node| node.str())?.to_string(), field.field("value", expr)?)))
+        })?),
+        "function" => LiteralValue::Function {
+            params: node.field("params", |node| node.items(ident))?,
+            body: Box::new(node.field("body", expr)?),
+        },
+        ty => return node.error(format!("unknown literal type '{ty}'")),
+    })
+}
+
+/// Reads an expression that has to be an identifier, like a parameter or what `let` binds.
+fn ident(node: Node) -> Result<Spanned<Expr>, String> {
+    match expr(node)? {
+        ident @ (Expr::Ident(_), _) => Ok(ident),
+        _ => node.error("expected an identifier"),
+    }
+}
+
+fn expr(node: Node) -> Result<Spanned<Expr>, String> {
+    let kind = node.field("kind", |node| node.str())?;
+    let boxed = |name: &str| node.field(name, expr).map(Box::new);
+    let expr = match kind {
+        "literal" => Expr::Literal(node.field("value", literal)?),
+        "ident" => Expr::Ident(node.field("name", |node| node.str())?.into()),
+        "call" => Expr::Call {
+            fun: boxed("fun")?,
+            args: node.field("args", |node| node.items(expr))?,
+        },
+        "let" => Expr::Let {
+            ident: Box::new(node.field("ident", ident)?),
+            value: boxed("value")?,
+            body: boxed("body")?,
+        },
+        "grouping" => Expr::Grouping(boxed("expr")?),
+        "binary" => Expr::Binary {
+            op: node.field("op", |node| {
+                let op = node.str()?;
+                let ops = [
+                    BinaryOp::Divide,
+                    BinaryOp::Add,
+                    BinaryOp::ShiftLeft,
+                    BinaryOp::ShiftRight,
+                    BinaryOp::BitAnd,
+                    BinaryOp::BitXor,
+                    BinaryOp::BitOr,
+                ];
+                ops.into_iter()
+                    .find(|candidate| candidate.to_string() == op)
+                    .map_or_else(|| node.error(format!("unknown operator '{op}'")), Ok)
+            })?,
+            lhs: boxed("lhs")?,
+            rhs: boxed("rhs")?,
+        },
+        kind => return node.error(format!("unknown expression kind '{kind}'")),
+    };
+    Ok((expr, node.field("span", span)?))
+}
+
+fn column(node: Node) -> Result<Column, String> {
+    Ok(Column {
+        name: node.field("name", name)?,
+        ty: node.field("type", |node| spanned(node, ColumnType::from_name))?,
+        constraints: node.field("constraints", |node| {
+            node.items(|node| spanned(node, Constraint::from_name))
+        })?,
+        default: node.optional("default", expr)?,
+        checks: node.field("checks", |node| node.items(expr))?,
+        renamed_from: node.optional("renamed_from", name)?,
+    })
+}
+
+fn relation(node: Node) -> Result<Relation, String> {
+    let cardinality = node.field("cardinality", |node| {
+        let min = node.field("min", |node| node.u64())?;
+        let max = node.optional("max", |node| node.u64())?;
+        Ok((Cardinality { min, max }, node.field("span", span)?))
+    })?;
+    Ok(Relation {
+        from: node.field("from", name)?,
+        cardinality,
+        to: node.field("to", name)?,
+        span: node.field("span", span)?,
+    })
+}
+
+/// Reads a module written by [`to_json`], saying where the JSON doesn't fit the format if it
+/// doesn't.
+pub fn from_json(value: &Value) -> Result<Module, String> {
+    let node = Node { value, path: "$" };
+    let version = node.field("version", |node| node.u64())?;
+    if version != VERSION {
+        return node.error(format!("version {version} is not supported, only version {VERSION} is"));
+    }
+
+    Ok(Module {
+        tests: node.field("tests", |node| {
+            node.items(|node| {
+                Ok(Test {
+                    name: node.field("name", name)?,
+                    body: node.field("body", expr)?,
+                })
+            })
+        })?,
+        entities: node.field("entities", |node| {
+            node.items(|node| {
+                Ok(Entity {
+                    name: node.field("name", name)?,
+                    renamed_from: node.optional("renamed_from", name)?,
+                    columns: node.field("columns", |node| node.items(column))?,
+                    span: node.field("span", span)?,
+                })
+            })
+        })?,
+        relations: node.field("relations", |node| node.items(relation))?,
+        expr: node.optional("expr", expr)?,
+    })
+}
@@ -0,0 +1,271 @@
+//! Long-form explanations of every diagnostic code, as printed by `dberd explain`.
+
+/// What a diagnostic code means, with an example that reports it and one that doesn't.
+#[derive(Debug, Clone, Copy)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// Retired codes keep their explanation, but are no longer reported.
+    pub emitted: bool,
+    /// How to check the examples, e.g. `dberd lint`.
+    pub command: &'static str,
+    /// Source that is reported with this code.
+    pub failing: &'static str,
+    /// The same source, fixed.
+    pub passing: &'static str,
+}
+
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: "E0001",
+        title: "Invalid character",
+        description: "\
+The source contains a character that can't start any token, or a string with an invalid escape.
+Tokens are symbols like `+` and `->`, numbers, strings and words. Anything after a `#` on a line
+is a comment, so other characters can still be used there.",
+        emitted: true,
+        command: "dberd tokenize",
+        failing: "{} -> 5 $ 2",
+        passing: "{} -> 5 + 2 # $",
+    },
+    Explanation {
+        code: "E0002",
+        title: "Unexpected token",
+        description: "\
+The tokens are valid on their own, but not in this order. The label lists what could have come
+instead. A common cause is a `let` binding without the `in` before its body.",
+        emitted: true,
+        command: "dberd tokenize",
+        failing: "{} -> let x = 1 x",
+        passing: "{} -> let x = 1 in x",
+    },
+    Explanation {
+        code: "E0003",
+        title: "Unclosed delimiter",
+        description: "\
+A `(` or `{` was never closed, or was closed by the other kind of delimiter. Delimiters are matched
+up before anything else is parsed, so the error points at the one that was left open.",
+        emitted: true,
+        command: "dberd tokenize",
+        failing: "{} -> :add{(1 + 2, 3}",
+        passing: "{} -> :add{(1 + 2), 3}",
+    },
+    Explanation {
+        code: "E0004",
+        title: "Invalid expression",
+        description: "\
+The code is well-formed, but not allowed. Builtins like `add` can't be bound with `let` or used
+as parameter names, number literals may only contain digits of their base, e.g. `0b` literals
+only `0` and `1`, and columns can only have the types and constraints dberd knows.",
+        emitted: true,
+        command: "dberd tokenize",
+        failing: "{} -> let add = 1 in add",
+        passing: "{} -> let total = 1 in total",
+    },
+    Explanation {
+        code: "E0005",
+        title: "Unknown identifier",
+        description: "\
+An identifier is used where no binding of that name is in scope. Bindings are only visible in the
+body of their `let`, and parameters only in the body of their function, so a function can't call
+itself. Names are looked up where they are written, so a function can't use a binding that is only
+in scope where it is called.",
+        emitted: true,
+        command: "dberd tokenize",
+        failing: "{} -> let x = 1 in y",
+        passing: "{} -> let x = 1 in x",
+    },
+    Explanation {
+        code: "E0006",
+        title: "Limit exceeded",
+        description: "\
+Evaluation took more steps, nested deeper or used more memory than allowed by `--max-steps`,
+`--max-depth` or `--max-memory`. Either raise the limit, or simplify the expression it stopped
+at.",
+        emitted: true,
+        command: "dberd tokenize --max-steps 5",
+        failing: "{} -> 1 + 2 + 3 + 4",
+        passing: "{} -> 10",
+    },
+    Explanation {
+        code: "E0007",
+        title: "Assertion failed",
+        description: "\
+An `assert` was given `false`, an `assert_eq` was given two different values, or a test evaluated
+to `false`. The labels show the values that were compared.",
+        emitted: true,
+        command: "dberd test",
+        failing: "test \"sum\" = :assert_eq{1 + 1, 3}",
+        passing: "test \"sum\" = :assert_eq{1 + 1, 2}",
+    },
+    Explanation {
+        code: "E0008",
+        title: "Duplicate declaration",
+        description: "\
+Two entities, or two columns of the same entity, have the same name. Rename or remove one of
+them.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "entity User { id: int pk, name: string, name: string }",
+        passing: "entity User { id: int pk, name: string }",
+    },
+    Explanation {
+        code: "E0009",
+        title: "Unknown entity",
+        description: "\
+A relation refers to an entity that isn't declared in the file. Check its spelling, or declare
+the entity.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "entity User { id: int pk }\nrelation User 1..* Order",
+        passing: "entity User { id: int pk }\nentity Order { id: int pk }\nrelation User 1..* Order",
+    },
+    Explanation {
+        code: "E0010",
+        title: "Missing primary key",
+        description: "\
+Every entity needs a primary key, so that its rows can be told apart and referred to by
+relations. Mark the columns that identify a row with `pk`.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "entity User { email: string }",
+        passing: "entity User { email: string pk }",
+    },
+    Explanation {
+        code: "E0011",
+        title: "Invalid SQL",
+        description: "\
+`dberd import sql` couldn't read the SQL it was given. Only `CREATE TABLE` statements and
+`ALTER TABLE` statements adding keys are read in detail, so the error is in one of those, or in a
+quoted name or comment that is never closed.",
+        emitted: true,
+        command: "dberd import sql",
+        failing: "CREATE TABLE users (id INTEGER PRIMARY KEY,);",
+        passing: "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+    },
+    Explanation {
+        code: "E0012",
+        title: "Type mismatch",
+        description: "\
+A column's default or check doesn't have the type it needs. Defaults have to be values of the
+column's type, where float columns take any number, and checks have to be booleans. Builtins
+like `len` and `gt` only take arguments of certain types too.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "entity User { id: int pk, age: int default \"18\" }",
+        passing: "entity User { id: int pk, age: int default 18 }",
+    },
+    Explanation {
+        code: "E0013",
+        title: "Invalid default",
+        description: "\
+A column's default can't be evaluated, e.g. because it divides by zero, or a row that only has
+defaults would fail one of the entity's checks. Checks that refer to columns without a default
+aren't considered.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "entity User { id: int pk, age: int default 0 check :ge{age, 18} }",
+        passing: "entity User { id: int pk, age: int default 18 check :ge{age, 18} }",
+    },
+    Explanation {
+        code: "E0014",
+        title: "Runtime error",
+        description: "\
+Evaluation failed, e.g. because a number was divided by zero, a builtin was given arguments of
+the wrong type, or a value has no JSON form, like a fraction given to `to_json` or printed with
+`--output json`. Unlike a type mismatch, this is only found when the code runs.",
+        emitted: true,
+        command: "dberd run",
+        failing: "{} -> let total = 10 in total / 0",
+        passing: "{} -> let total = 10 in total / 4",
+    },
+    Explanation {
+        code: "W0001",
+        title: "Unused binding",
+        description: "\
+A `let` binding is never used in its body. Remove it, or use it if it was forgotten. Reported by
+the `unused-binding` lint.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "let unused = 1 in 2",
+        passing: "let used = 1 in used + 1",
+    },
+    Explanation {
+        code: "W0002",
+        title: "Shadowed binding",
+        description: "\
+A binding has the same name as one that is already in scope, which hides the earlier one for the
+rest of its body. Rename one of them. Reported by the `shadowing` lint.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "let x = 1 in x + (let x = 2 in x)",
+        passing: "let x = 1 in x + (let y = 2 in y)",
+    },
+    Explanation {
+        code: "W0003",
+        title: "Uncalled function",
+        description: "\
+A function bound with `let` is used, but never called or passed to anything that could call it,
+so its value is the function itself rather than its result. Reported by the `uncalled-function`
+lint.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "let f = {} -> 1 in f",
+        passing: "let f = {} -> 1 in :f{}",
+    },
+    Explanation {
+        code: "W0004",
+        title: "Unsupported SQL",
+        description: "\
+`dberd import sql` skipped a part of the SQL that dberd can't express, like an index, a `CHECK` or
+`DEFAULT`, or a type other than integers, floats, strings and booleans, which is imported as a
+string. The import still succeeds, but the entities may need to be adjusted by hand.",
+        emitted: true,
+        command: "dberd import sql",
+        failing: "CREATE TABLE users (id INTEGER PRIMARY KEY, created DATE);",
+        passing: "CREATE TABLE users (id INTEGER PRIMARY KEY, created TEXT);",
+    },
+    Explanation {
+        code: "W0005",
+        title: "Constant condition",
+        description: "\
+A `check` doesn't refer to any column of its entity, so it gives the same answer for every row. If
+it is always true it checks nothing, and if it is always false no row can ever be stored. Reported
+by the `constant-condition` lint.",
+        emitted: true,
+        command: "dberd lint",
+        failing: "entity User { id: int pk, age: int check :ge{18, 0} }",
+        passing: "entity User { id: int pk, age: int check :ge{age, 0} }",
+    },
+];
+
+/// Finds the explanation of a code, ignoring case.
+pub fn explanation(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS
+        .iter()
+        .find(|explanation| explanation.code.eq_ignore_ascii_case(code))
+}
+
+impl Explanation {
+    /// The explanation as printed by `dberd explain`.
+    pub fn render(&self) -> String {
+        let indent = |source: &str| {
+            source
+                .lines()
+                .map(|line| format!("    {line}\n"))
+                .collect::<String>()
+        };
+
+        format!(
+            "{}: {}\n\n{}\n\nThis {} reported by `{}`:\n\n{}\nThis is not:\n\n{}",
+            self.code,
+            self.title,
+            self.description,
+            if self.emitted { "is" } else { "used to be" },
+            self.command,
+            indent(self.failing),
+            indent(self.passing),
+        )
+    }
+}
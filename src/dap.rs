@@ -0,0 +1,415 @@
+use std::{
+    error::Error,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    debugger::{Lines, Resume, Stepper, Stop},
+    diagnostics,
+    enterpreter::{self, Context, EvalError, Limits, Observer, MAX_DEPTH},
+    formatter,
+    lexer::{Span, Spanned},
+    lsp::{read_message, write_message},
+    parser::{Expr, Module},
+    resolver,
+};
+
+// https://microsoft.github.io/debug-adapter-protocol/specification
+/// dberd programs only ever have one thread.
+const THREAD_ID: i64 = 1;
+
+/// A program that has been launched, but might not be running yet.
+struct Program {
+    path: String,
+    text: String,
+    lines: Lines,
+    module: Module,
+}
+
+impl Program {
+    /// The name and text of the program, for printing where its functions are defined.
+    fn file(&self) -> (&str, &str) {
+        (&self.path, &self.text)
+    }
+}
+
+/// A function call that is being evaluated.
+struct Frame {
+    name: String,
+    /// The call expression.
+    span: Span,
+    /// The context of the caller.
+    ctx: Context,
+}
+
+/// Where evaluation is stopped.
+struct Paused<'a> {
+    span: &'a Span,
+    depth: usize,
+    ctx: &'a Context,
+}
+
+/// What should happen after a request is answered.
+enum Action {
+    Wait,
+    Start,
+    Resume,
+    Disconnect,
+}
+
+struct Session<I, O> {
+    input: I,
+    output: O,
+    seq: i64,
+    program: Option<Program>,
+    stop_on_entry: bool,
+    /// Applied to the program and to every watch expression.
+    limits: Limits,
+    stepper: Stepper,
+    /// The function calls being evaluated, outermost first.
+    frames: Vec<Frame>,
+    /// Whether the next stop is the first one, which is reported as stopping on entry.
+    entry: bool,
+    disconnected: bool,
+}
+
+/// Runs the debug adapter on stdin and stdout until the client disconnects.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    serve(io::BufReader::new(io::stdin()), io::stdout())
+}
+
+/// Answers requests read from `input`, writing responses and events to `output`, until the client
+/// disconnects or the input ends.
+pub fn serve(input: impl BufRead + Send, output: impl Write + Send) -> Result<(), Box<dyn Error>> {
+    Session {
+        input,
+        output,
+        seq: 0,
+        program: None,
+        stop_on_entry: false,
+        limits: Limits::default(),
+        stepper: Stepper::new([]),
+        frames: Vec::new(),
+        entry: true,
+        disconnected: false,
+    }
+    .serve()
+}
+
+impl<I: BufRead + Send, O: Write + Send> Session<I, O> {
+    fn serve(&mut self) -> Result<(), Box<dyn Error>> {
+        while let Some(request) = read_message(&mut self.input)? {
+            match self.handle(&request, None)? {
+                Action::Wait | Action::Resume => {}
+                Action::Start => self.start()?,
+                Action::Disconnect => break,
+            }
+            if self.disconnected {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    /// Answers a request. `paused` is where evaluation is stopped, if it has started.
+    fn handle(&mut self, request: &Value, paused: Option<&Paused>) -> io::Result<Action> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+
+        let (result, action) = match self.answer(command, arguments, paused) {
+            Ok((body, action)) => (Ok(body), action),
+            Err(message) => (Err(message), Action::Wait),
+        };
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)?;
+
+        if command == "initialize" {
+            self.event("initialized", json!({}))?;
+        }
+        Ok(action)
+    }
+
+    fn answer(
+        &mut self,
+        command: &str,
+        arguments: &Value,
+        paused: Option<&Paused>,
+    ) -> Result<(Value, Action), String> {
+        let body = match command {
+            "initialize" => json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsEvaluateForHovers": true,
+            }),
+            "launch" => {
+                let path = arguments["program"].as_str().ok_or("Missing 'program' to launch")?;
+                self.program = Some(load(path)?);
+                self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                let max_depth = arguments["maxDepth"].as_u64();
+                if max_depth.is_some_and(|max| max > MAX_DEPTH as u64) {
+                    return Err(format!("'maxDepth' can't go over {MAX_DEPTH}"));
+                }
+                self.limits = Limits {
+                    max_steps: arguments["maxSteps"].as_u64(),
+                    max_depth: max_depth.map(|max| max as usize),
+                    max_memory: arguments["maxMemory"].as_u64().map(|max| max as usize),
+                };
+                json!({})
+            }
+            "setBreakpoints" => {
+                let lines: Vec<usize> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                self.stepper.breakpoints = lines.iter().copied().collect();
+                json!({
+                    "breakpoints": lines
+                        .iter()
+                        .map(|line| json!({ "verified": true, "line": line }))
+                        .collect::<Vec<_>>(),
+                })
+            }
+            "configurationDone" => {
+                if self.program.is_none() {
+                    return Err("No program has been launched".into());
+                }
+                return Ok((json!({}), Action::Start));
+            }
+            "threads" => json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            "stackTrace" => json!({ "stackFrames": self.stack_frames(paused.ok_or("Not stopped")?) }),
+            "scopes" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or(0);
+                json!({
+                    "scopes": [{ "name": "Locals", "variablesReference": frame + 1, "expensive": false }],
+                })
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or(0);
+                let ctx = self
+                    .frame_ctx(paused.ok_or("Not stopped")?, reference.saturating_sub(1) as usize)
+                    .ok_or("Unknown variables reference")?;
+
+                let mut idents: Vec<_> = ctx.idents.iter().collect();
+                idents.sort_by_key(|(name, _)| name.as_str());
+                let program = self.program.as_ref();
+                json!({
+                    "variables": idents
+                        .into_iter()
+                        .map(|(name, value)| json!({
+                            "name": name,
+                            "value": formatter::format_binding(value, 60, program.map(Program::file)),
+                            "variablesReference": 0,
+                        }))
+                        .collect::<Vec<_>>(),
+                })
+            }
+            "evaluate" => {
+                let paused = paused.ok_or("Not stopped")?;
+                let frame = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+                let ctx = self.frame_ctx(paused, frame).ok_or("Unknown frame")?;
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                json!({ "result": evaluate(expression, ctx, &self.limits)?, "variablesReference": 0 })
+            }
+            "continue" | "next" | "stepIn" | "stepOut" => {
+                let paused = paused.ok_or("Not stopped")?;
+                self.stepper.resume = match command {
+                    "continue" => Resume::Continue,
+                    "next" => Resume::Next { depth: paused.depth },
+                    "stepIn" => Resume::Step,
+                    _ => Resume::Out { frames: self.frames.len() },
+                };
+                return Ok((json!({ "allThreadsContinued": true }), Action::Resume));
+            }
+            "disconnect" => {
+                self.disconnected = true;
+                return Ok((json!({}), Action::Disconnect));
+            }
+            _ => return Err(format!("Unknown command '{command}'")),
+        };
+        Ok((body, Action::Wait))
+    }
+
+    /// The context of a frame, counting from the innermost one.
+    fn frame_ctx<'a>(&'a self, paused: &'a Paused, frame: usize) -> Option<&'a Context> {
+        if frame == 0 {
+            Some(paused.ctx)
+        } else {
+            let i = self.frames.len().checked_sub(frame)?;
+            Some(&self.frames[i].ctx)
+        }
+    }
+
+    /// Lists the frames from the innermost one out, each at the place it is evaluating.
+    fn stack_frames(&self, paused: &Paused) -> Vec<Value> {
+        let Some(program) = &self.program else {
+            return Vec::new();
+        };
+
+        let names = std::iter::once("main").chain(self.frames.iter().map(|frame| frame.name.as_str()));
+        let spans = self.frames.iter().map(|frame| &frame.span).chain([paused.span]);
+        let frames: Vec<_> = names.zip(spans).collect();
+        frames
+            .into_iter()
+            .rev()
+            .enumerate()
+            .map(|(id, (name, span))| {
+                let (line, column) = program.lines.line_col(span.start);
+                json!({
+                    "id": id,
+                    "name": name,
+                    "source": { "name": program.path, "path": program.path },
+                    "line": line,
+                    "column": column,
+                })
+            })
+            .collect()
+    }
+
+    /// Runs the launched program to the end, or until the client disconnects.
+    fn start(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(program) = &self.program else {
+            return Ok(());
+        };
+        let Some(expr) = program.module.expr.clone() else {
+            self.event("output", json!({ "category": "stderr", "output": "There is nothing to evaluate\n" }))?;
+            self.event("exited", json!({ "exitCode": 1 }))?;
+            self.event("terminated", json!({}))?;
+            return Ok(());
+        };
+        if self.stop_on_entry {
+            self.stepper.resume = Resume::Step;
+        } else {
+            self.stepper.resume = Resume::Continue;
+            self.entry = false;
+        }
+
+        let limits = self.limits.clone();
+        let result = enterpreter::interpret_with(expr, self, &limits);
+        if self.disconnected {
+            return Ok(());
+        }
+
+        let (category, output, exit_code) = match result {
+            Ok(value) => ("stdout", formatter::format_value(&value, 80, self.program.as_ref().map(Program::file)), 0),
+            Err(error) => ("stderr", error.to_string(), 1),
+        };
+        self.event("output", json!({ "category": category, "output": format!("{output}\n") }))?;
+        self.event("exited", json!({ "exitCode": exit_code }))?;
+        self.event("terminated", json!({}))?;
+        Ok(())
+    }
+}
+
+impl<I: BufRead + Send, O: Write + Send> Observer for Session<I, O> {
+    fn enter(&mut self, (_, span): &Spanned<Expr>, depth: usize, ctx: &Context) -> Result<(), EvalError> {
+        let Some(program) = &self.program else {
+            return Ok(());
+        };
+        let (line, _) = program.lines.line_col(span.start);
+        let Some(stop) = self.stepper.stop(line, depth, self.frames.len()) else {
+            return Ok(());
+        };
+
+        let reason = match stop {
+            _ if self.entry => "entry",
+            Stop::Breakpoint => "breakpoint",
+            Stop::Step => "step",
+        };
+        self.entry = false;
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )
+        .map_err(|err| err.to_string())?;
+
+        let paused = Paused { span, depth, ctx };
+        loop {
+            let request = read_message(&mut self.input).map_err(|err| err.to_string())?;
+            let Some(request) = request else {
+                self.disconnected = true;
+                return Err(EvalError::Stopped);
+            };
+            match self.handle(&request, Some(&paused)).map_err(|err| err.to_string())? {
+                Action::Wait | Action::Start => {}
+                Action::Resume => return Ok(()),
+                Action::Disconnect => return Err(EvalError::Stopped),
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, span: &Span, ctx: &Context) {
+        self.frames.push(Frame {
+            name: name.to_string(),
+            span: span.clone(),
+            ctx: ctx.clone(),
+        });
+    }
+
+    fn returned(&mut self) {
+        self.frames.pop();
+    }
+}
+
+/// Reads, parses and resolves a program, failing with the first problem found.
+fn load(path: &str) -> Result<Program, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("Can't read {path}: {err}"))?;
+    let module = diagnostics::lex(&text)
+        .and_then(|tokens| diagnostics::parse(&text, tokens))
+        .and_then(|module| {
+            let diagnostics = resolver::resolve(&module).diagnostics;
+            if diagnostics.is_empty() {
+                Ok(module)
+            } else {
+                Err(diagnostics)
+            }
+        })
+        .map_err(|diagnostics| first_error(&text, &diagnostics))?;
+
+    Ok(Program {
+        path: path.to_string(),
+        lines: Lines::new(&text),
+        text,
+        module,
+    })
+}
+
+/// Evaluates a watch expression in the context of a frame.
+fn evaluate(expression: &str, ctx: &Context, limits: &Limits) -> Result<String, String> {
+    let expr = diagnostics::lex(expression)
+        .and_then(|tokens| diagnostics::parse_expr(expression, tokens))
+        .map_err(|diagnostics| first_error(expression, &diagnostics))?;
+    enterpreter::evaluate(expr, ctx, limits)
+        .map(|value| value.to_string())
+        .map_err(|error| error.to_string())
+}
+
+fn first_error(text: &str, diagnostics: &[diagnostics::Diagnostic]) -> String {
+    let Some(diagnostic) = diagnostics.first() else {
+        return "Unknown error".into();
+    };
+    let (line, column) = Lines::new(text).line_col(diagnostic.span.start);
+    format!("{line}:{column}: [{}] {}", diagnostic.code, diagnostic.message)
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    enterpreter::{Context, EvalError, Observer},
+    formatter,
+    lexer::{Span, Spanned},
+    parser::Expr,
+};
+
+/// Where each line of a source starts, so that finding the line of an offset doesn't have to scan
+/// the source on every step.
+#[derive(Debug, Clone)]
+pub struct Lines {
+    /// The char offset of the start of every line.
+    starts: Vec<usize>,
+}
+
+impl Lines {
+    pub fn new(source_text: &str) -> Self {
+        let newlines = source_text.chars().enumerate().filter(|(_, c)| *c == '\n');
+        Self {
+            starts: std::iter::once(0).chain(newlines.map(|(i, _)| i + 1)).collect(),
+        }
+    }
+
+    /// The 1-based line and column of a char offset.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= offset);
+        (line, offset - self.starts[line - 1] + 1)
+    }
+}
+
+/// The source of `span` on a single line, cut short if it is long.
+fn snippet(source_text: &str, span: &Span) -> String {
+    const MAX_WIDTH: usize = 40;
+
+    let text: String = source_text.chars().skip(span.start).take(span.len()).collect();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > MAX_WIDTH {
+        format!("{}...", text.chars().take(MAX_WIDTH).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// Prints every step of an evaluation to stderr, indented by how deep it is.
+pub struct Tracer<'a> {
+    source_text: &'a str,
+    lines: Lines,
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(source_text: &'a str) -> Self {
+        Self {
+            source_text,
+            lines: Lines::new(source_text),
+        }
+    }
+}
+
+impl Observer for Tracer<'_> {
+    fn enter(&mut self, (_, span): &Spanned<Expr>, depth: usize, _: &Context) -> Result<(), EvalError> {
+        let (line, col) = self.lines.line_col(span.start);
+        eprintln!(
+            "{}{line}:{col} {}",
+            "  ".repeat(depth),
+            snippet(self.source_text, span)
+        );
+        Ok(())
+    }
+}
+
+/// When the debugger should next stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// At the next step.
+    Step,
+    /// At the next step that isn't deeper than `depth`.
+    Next { depth: usize },
+    /// Once fewer than `frames` function calls are being evaluated.
+    Out { frames: usize },
+    /// At the next breakpoint.
+    Continue,
+}
+
+/// Why evaluation stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Breakpoint,
+}
+
+/// Decides when evaluation should stop, from the breakpoints and how it was last resumed.
+#[derive(Debug, Clone)]
+pub struct Stepper {
+    /// The lines to stop at, starting from 1.
+    pub breakpoints: BTreeSet<usize>,
+    pub resume: Resume,
+    /// The line of the previous step, so that a breakpoint stops once when evaluation reaches its
+    /// line rather than on every expression on it.
+    line: usize,
+}
+
+impl Stepper {
+    /// Stops at the first step, or at the first breakpoint if there are any.
+    pub fn new(breakpoints: impl IntoIterator<Item = usize>) -> Self {
+        let breakpoints: BTreeSet<usize> = breakpoints.into_iter().collect();
+        Self {
+            resume: if breakpoints.is_empty() {
+                Resume::Step
+            } else {
+                Resume::Continue
+            },
+            breakpoints,
+            line: 0,
+        }
+    }
+
+    /// Checks whether to stop at a step on `line`, `depth` deep and inside `frames` function calls.
+    pub fn stop(&mut self, line: usize, depth: usize, frames: usize) -> Option<Stop> {
+        let reached_breakpoint = line != self.line && self.breakpoints.contains(&line);
+        self.line = line;
+
+        if reached_breakpoint {
+            return Some(Stop::Breakpoint);
+        }
+        let stop = match self.resume {
+            Resume::Step => true,
+            Resume::Next { depth: next_depth } => depth <= next_depth,
+            Resume::Out { frames: out_frames } => frames < out_frames,
+            Resume::Continue => false,
+        };
+        stop.then_some(Stop::Step)
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  step, s           Evaluate until the next expression.
+  next, n           Evaluate until the next expression that isn't part of this one.
+  continue, c       Evaluate until the next breakpoint.
+  break, b LINE     Stop whenever evaluation reaches LINE.
+  delete, d LINE    Remove the breakpoint on LINE.
+  locals, l         List the bindings in scope.
+  print, p NAME     Show the value of a binding.
+  where, w          Show where evaluation has stopped.
+  quit, q           Stop evaluating.";
+
+/// An interactive debugger reading commands from stdin whenever evaluation stops.
+pub struct Debugger<'a> {
+    source_name: &'a str,
+    source_text: &'a str,
+    lines: Lines,
+    stepper: Stepper,
+}
+
+impl<'a> Debugger<'a> {
+    /// Creates a debugger that stops at the first step, or at the first breakpoint if there are
+    /// any.
+    pub fn new(source_name: &'a str, source_text: &'a str, breakpoints: Vec<usize>) -> Self {
+        Self {
+            source_name,
+            source_text,
+            lines: Lines::new(source_text),
+            stepper: Stepper::new(breakpoints),
+        }
+    }
+
+    fn format(&self, value: &Spanned<Expr>) -> String {
+        formatter::format_binding(value, 60, Some((self.source_name, self.source_text)))
+    }
+
+    fn show_location(&self, span: &Span) {
+        let (line, col) = self.lines.line_col(span.start);
+        let text = self.source_text.lines().nth(line - 1).unwrap_or_default();
+        let width = span.len().min(text.chars().count() + 1 - col).max(1);
+
+        println!("{}:{line}:{col}", self.source_name);
+        println!("{line:>4} | {text}");
+        println!("     | {}{}", " ".repeat(col - 1), "^".repeat(width));
+    }
+
+    /// Reads and runs commands until one resumes evaluation.
+    fn prompt(&mut self, span: &Span, depth: usize, ctx: &Context) -> Result<(), EvalError> {
+        self.show_location(span);
+
+        let stdin = io::stdin();
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().map_err(|err| err.to_string())?;
+
+            let mut command = String::new();
+            if stdin.lock().read_line(&mut command).map_err(|err| err.to_string())? == 0 {
+                return Err(EvalError::Stopped);
+            }
+            let mut words = command.split_whitespace();
+            let (command, argument) = (words.next().unwrap_or_default(), words.next());
+
+            match (command, argument) {
+                ("step" | "s", None) => {
+                    self.stepper.resume = Resume::Step;
+                    return Ok(());
+                }
+                ("next" | "n", None) => {
+                    self.stepper.resume = Resume::Next { depth };
+                    return Ok(());
+                }
+                ("continue" | "c", None) => {
+                    self.stepper.resume = Resume::Continue;
+                    return Ok(());
+                }
+                ("break" | "b", Some(line)) => match line.parse() {
+                    Ok(line) => {
+                        self.stepper.breakpoints.insert(line);
+                        println!("Breakpoint set on line {line}");
+                    }
+                    Err(_) => println!("'{line}' is not a line number"),
+                },
+                ("delete" | "d", Some(line)) => match line.parse() {
+                    Ok(line) if self.stepper.breakpoints.remove(&line) => {
+                        println!("Breakpoint removed from line {line}")
+                    }
+                    _ => println!("There is no breakpoint on line {line}"),
+                },
+                ("locals" | "l", None) => {
+                    let mut idents: Vec<_> = ctx.idents.iter().collect();
+                    idents.sort_by_key(|(name, _)| name.as_str());
+                    if idents.is_empty() {
+                        println!("Nothing is bound here");
+                    }
+                    for (name, value) in idents {
+                        println!("{name} = {}", self.format(value));
+                    }
+                }
+                ("print" | "p", Some(name)) => match ctx.idents.get(name) {
+                    Some(value) => println!("{name} = {}", self.format(value)),
+                    None => println!("'{name}' is not bound here"),
+                },
+                ("where" | "w", None) => self.show_location(span),
+                ("quit" | "q", None) => return Err(EvalError::Stopped),
+                ("", None) => {}
+                _ => println!("{HELP}"),
+            }
+        }
+    }
+}
+
+impl Observer for Debugger<'_> {
+    fn enter(&mut self, (_, span): &Spanned<Expr>, depth: usize, ctx: &Context) -> Result<(), EvalError> {
+        let (line, _) = self.lines.line_col(span.start);
+        if self.stepper.stop(line, depth, 0).is_some() {
+            self.prompt(span, depth, ctx)?;
+        }
+        Ok(())
+    }
+}
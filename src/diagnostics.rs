@@ -0,0 +1,471 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    fmt::Display,
+    hash::Hash,
+};
+
+use ariadne::{sources, ColorGenerator, Report};
+use chumsky::{error::SimpleReason, prelude::*, Stream};
+use serde_json::{json, Value};
+
+use crate::{
+    lexer::{self, Span, Spanned, Token},
+    parser::{self, Expr, Module},
+};
+
+/// A message attached to a part of the source.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// How the CLI prints diagnostics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Colored ariadne reports.
+    #[default]
+    Human,
+    /// One JSON object per line, for tools to read.
+    Json,
+}
+
+/// A failure whose diagnostics have already been printed, e.g. `Failed to parse`. With
+/// `--message-format json` nothing more is printed about it, since the diagnostics said it all.
+#[derive(Debug, Clone)]
+pub struct Reported(pub String);
+
+impl Display for Reported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for Reported {}
+
+/// A problem found in a source file, independent of how it ends up being shown.
+///
+/// The CLI renders these with ariadne, the language server sends them to the editor.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Prints the diagnostic to stderr as an ariadne report.
+    pub fn eprint(&self, source: &'static str, source_text: &str) -> std::io::Result<()> {
+        let mut colors = ColorGenerator::new();
+
+        let kind = match self.severity {
+            Severity::Error => ariadne::ReportKind::Error,
+            Severity::Warning => ariadne::ReportKind::Warning,
+        };
+        let mut report = Report::build(kind, source, self.span.start)
+            .with_code(self.code)
+            .with_message(&self.message);
+        if let Some(help) = &self.help {
+            report = report.with_help(help);
+        }
+        for (i, label) in self.labels.iter().enumerate() {
+            report = report.with_label(
+                ariadne::Label::new((source, label.span.clone()))
+                    .with_message(&label.message)
+                    .with_color(colors.next())
+                    .with_order(i as i32),
+            );
+        }
+
+        report
+            .finish()
+            .eprint(sources(vec![(source, source_text)]))
+    }
+
+    /// Prints the diagnostic to stderr in the given format.
+    pub fn emit(&self, format: MessageFormat, source: &'static str, source_text: &str) -> std::io::Result<()> {
+        match format {
+            MessageFormat::Human => self.eprint(source, source_text),
+            MessageFormat::Json => {
+                eprintln!("{}", self.to_json(source, source_text));
+                Ok(())
+            }
+        }
+    }
+
+    /// Prints the diagnostic to stderr without the source it points into, like for a syntax tree
+    /// loaded from JSON. Spans are shown as char offsets.
+    pub fn emit_detached(&self, format: MessageFormat, source: &str) {
+        match format {
+            MessageFormat::Human => {
+                let kind = match self.severity {
+                    Severity::Error => "Error",
+                    Severity::Warning => "Warning",
+                };
+                eprintln!("[{}] {kind}: {}", self.code, self.message);
+                for label in &self.labels {
+                    eprintln!("  at chars {}..{}: {}", label.span.start, label.span.end, label.message);
+                }
+                if let Some(help) = &self.help {
+                    eprintln!("  help: {help}");
+                }
+            }
+            MessageFormat::Json => eprintln!(
+                "{}",
+                json!({
+                    "file": source,
+                    "code": self.code,
+                    "severity": self.severity.name(),
+                    "message": self.message,
+                    "span": null,
+                    "labels": self.labels.iter().map(|label| json!({
+                        "span": null,
+                        "message": label.message,
+                    })).collect::<Vec<_>>(),
+                    "help": self.help,
+                })
+            ),
+        }
+    }
+
+    pub fn to_json(&self, source: &str, source_text: &str) -> Value {
+        json!({
+            "file": source,
+            "code": self.code,
+            "severity": self.severity.name(),
+            "message": self.message,
+            "span": span_json(source_text, &self.span),
+            "labels": self.labels.iter().map(|label| json!({
+                "span": span_json(source_text, &label.span),
+                "message": label.message,
+            })).collect::<Vec<_>>(),
+            "help": self.help,
+        })
+    }
+}
+
+/// Prints an error that doesn't point at any part of the source, like a diagnostic would be. Errors
+/// that aren't about the source, like a file that can't be loaded, have no code, and errors that
+/// aren't about any one file, like an unknown command line option, have no `source`.
+pub fn emit_error(format: MessageFormat, source: Option<&str>, code: Option<&str>, message: &str) {
+    match format {
+        MessageFormat::Human => match code {
+            Some(code) => eprintln!("[{code}] Error: {message}"),
+            None => eprintln!("error: {message}"),
+        },
+        MessageFormat::Json => eprintln!(
+            "{}",
+            json!({
+                "file": source,
+                "code": code,
+                "severity": Severity::Error.name(),
+                "message": message,
+                "span": null,
+                "labels": [],
+                "help": null,
+            })
+        ),
+    }
+}
+
+/// Describes a span by its byte offsets and its 1-based lines and columns. Columns count chars,
+/// and the end is exclusive.
+fn span_json(source_text: &str, span: &Span) -> Value {
+    let (mut byte, mut line, mut column) = (0, 1, 1);
+    let mut start = (0, 1, 1);
+    for (i, c) in source_text.chars().take(span.end).enumerate() {
+        if i == span.start {
+            start = (byte, line, column);
+        }
+        byte += c.len_utf8();
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    if span.start >= span.end {
+        start = (byte, line, column);
+    }
+
+    json!({
+        "byte_start": start.0,
+        "byte_end": byte,
+        "line_start": start.1,
+        "column_start": start.2,
+        "line_end": line,
+        "column_end": column,
+    })
+}
+
+/// Something the lexer or parser can expect, grouped into categories so that syntax errors can
+/// summarize what they expected instead of listing every token.
+trait Expected: Hash + Eq {
+    /// The category this belongs to, e.g. "an operator". A category is named instead of its
+    /// members when more than two of them were expected.
+    fn category(&self) -> Option<&'static str>;
+
+    /// This input as it appears in the source.
+    fn quote(&self) -> String;
+
+    /// How this is described when it is expected on its own.
+    fn describe(&self) -> String {
+        self.quote()
+    }
+}
+
+impl Expected for char {
+    fn category(&self) -> Option<&'static str> {
+        match self {
+            c if c.is_ascii_digit() => Some("a digit"),
+            c if c.is_ascii_alphabetic() => Some("a letter"),
+            c if c.is_whitespace() => Some("whitespace"),
+            c if c.is_ascii_punctuation() => Some("a symbol"),
+            _ => None,
+        }
+    }
+
+    fn quote(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl Expected for Token {
+    fn category(&self) -> Option<&'static str> {
+        match self {
+            // Only keywords like `test` are identifiers with a name.
+            Token::Ident(name) if name.is_empty() => Some("an identifier"),
+            Token::Number(_) | Token::Minus => Some("a number literal"),
+            Token::Str(_) => Some("a string literal"),
+            Token::Plus
+            | Token::Star
+            | Token::Slash
+            | Token::Ampersand
+            | Token::Pipe
+            | Token::Caret
+            | Token::ShiftLeft
+            | Token::ShiftRight => Some("an operator"),
+            Token::Let | Token::In | Token::True | Token::False => Some("a keyword"),
+            _ => None,
+        }
+    }
+
+    fn quote(&self) -> String {
+        format!("'{self}'")
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) if name.is_empty() => "an identifier".into(),
+            Token::Number(_) => "a number literal".into(),
+            Token::Str(_) => "a string literal".into(),
+            _ => self.quote(),
+        }
+    }
+}
+
+/// Lists what was expected, e.g. "an identifier, an operator or ')'".
+fn describe_expected<'a, T: Expected + 'a>(expected: impl Iterator<Item = &'a Option<T>>) -> String {
+    let expected: Vec<_> = expected.collect();
+
+    let mut members = HashMap::new();
+    for category in expected.iter().filter_map(|input| input.as_ref()?.category()) {
+        *members.entry(category).or_insert(0) += 1;
+    }
+
+    // Sorted, since chumsky keeps them in a set.
+    let descriptions: BTreeSet<_> = expected
+        .iter()
+        .filter_map(|input| input.as_ref())
+        .map(|input| match input.category() {
+            Some(category) if members[category] > 2 => category.to_string(),
+            _ => input.describe(),
+        })
+        .collect();
+    let mut descriptions: Vec<_> = descriptions.into_iter().collect();
+    if expected.contains(&&None) {
+        descriptions.push("the end of the file".into());
+    }
+
+    match descriptions.pop() {
+        None => "something else".into(),
+        Some(last) if descriptions.is_empty() => last,
+        Some(last) => format!("{} or {last}", descriptions.join(", ")),
+    }
+}
+
+/// Reports that the lexer or parser found `what` where it expected something else.
+fn unexpected<T: Expected>(error: &Simple<T>, code: &'static str, what: &str) -> Diagnostic {
+    let context = error
+        .label()
+        .map(|label| format!(" while parsing {label}"))
+        .unwrap_or_default();
+    let (message, found) = match error.found() {
+        Some(found) => (format!("Unexpected {what} found{context}."), found.quote()),
+        None => (format!("Unexpected end of file{context}."), "the end of the file".into()),
+    };
+
+    Diagnostic {
+        severity: Severity::Error,
+        code,
+        message,
+        span: error.span(),
+        labels: vec![Label {
+            span: error.span(),
+            message: format!("Expected {}. Found {found}.", describe_expected(error.expected())),
+        }],
+        help: None,
+    }
+}
+
+fn lex_diagnostic(error: Simple<char>) -> Diagnostic {
+    match error.reason() {
+        SimpleReason::Unexpected => unexpected(&error, "E0001", "character"),
+        SimpleReason::Unclosed {
+            span: _,
+            delimiter: _,
+        } => unreachable!("Delimiters are not lexed"),
+        SimpleReason::Custom(message) => Diagnostic {
+            severity: Severity::Error,
+            code: "E0001",
+            message: format!("{message}."),
+            span: error.span(),
+            labels: vec![Label {
+                span: error.span(),
+                message: "Error occured here".into(),
+            }],
+            help: None,
+        },
+    }
+}
+
+fn parse_diagnostic(error: Simple<Token>) -> Diagnostic {
+    match error.reason() {
+        SimpleReason::Unexpected => unexpected(&error, "E0002", "token"),
+        SimpleReason::Unclosed { span, .. } => Diagnostic {
+            severity: Severity::Error,
+            code: "E0003",
+            message: "Unclosed delimiter found.".into(),
+            span: error.span(),
+            labels: vec![
+                Label {
+                    span: span.clone(),
+                    message: "Unclosed delimiter started here!".into(),
+                },
+                Label {
+                    span: error.span(),
+                    message: format!(
+                        "Expected {} to close it. Found {}.",
+                        describe_expected(error.expected()),
+                        error.found().map_or("the end of the file".into(), Expected::quote)
+                    ),
+                },
+            ],
+            help: None,
+        },
+        SimpleReason::Custom(message) => Diagnostic {
+            severity: Severity::Error,
+            code: "E0004",
+            message: message.clone(),
+            span: error.span(),
+            labels: vec![Label {
+                span: error.span(),
+                message: "Error occured here".into(),
+            }],
+            help: None,
+        },
+    }
+}
+
+/// Lexes a source file, keeping comments.
+pub fn lex(source_text: &str) -> Result<Vec<Spanned<Token>>, Vec<Diagnostic>> {
+    lexer::lex(source_text).map_err(|error| vec![lex_diagnostic(error)])
+}
+
+/// Parses the tokens of a source file.
+pub fn parse(source_text: &str, tokens: Vec<Spanned<Token>>) -> Result<Module, Vec<Diagnostic>> {
+    parse_with(parser::module(), source_text, tokens)
+}
+
+/// Parses tokens into a single expression, e.g. a debugger's watch expression.
+pub fn parse_expr(source_text: &str, tokens: Vec<Spanned<Token>>) -> Result<Spanned<Expr>, Vec<Diagnostic>> {
+    parse_with(parser::expr(), source_text, tokens)
+}
+
+/// The closing delimiter of an opening one.
+fn closing(delimiter: &Token) -> Option<Token> {
+    match delimiter {
+        Token::LeftParen => Some(Token::RightParen),
+        Token::LeftBrace => Some(Token::RightBrace),
+        _ => None,
+    }
+}
+
+/// Matches up the delimiters of a source file before it is parsed, so that one that isn't closed
+/// is reported where it starts rather than wherever the parser gives up. Closing delimiters that
+/// don't close anything are left for the parser to report.
+fn unclosed_delimiters(tokens: &[Spanned<Token>], len: usize) -> Vec<Simple<Token>> {
+    let unclosed = |(delimiter, span): &Spanned<Token>, found: Option<&Spanned<Token>>| {
+        let expected = closing(delimiter).expect("Only opening delimiters are kept open");
+        let (found, found_span) = found.map_or((None, len..len), |(token, span)| (Some(token.clone()), span.clone()));
+        <Simple<Token> as chumsky::Error<Token>>::unclosed_delimiter(span.clone(), delimiter.clone(), found_span, expected, found)
+    };
+
+    let mut open: Vec<&Spanned<Token>> = Vec::new();
+    for token in tokens {
+        match &token.0 {
+            Token::LeftParen | Token::LeftBrace => open.push(token),
+            Token::RightParen | Token::RightBrace => match open.last() {
+                Some(delimiter) if closing(&delimiter.0).as_ref() == Some(&token.0) => {
+                    open.pop();
+                }
+                Some(delimiter) => return vec![unclosed(delimiter, Some(token))],
+                None => {}
+            },
+            _ => {}
+        }
+    }
+    open.into_iter().map(|delimiter| unclosed(delimiter, None)).collect()
+}
+
+fn parse_with<T>(
+    parser: impl Parser<Token, T, Error = Simple<Token>>,
+    source_text: &str,
+    tokens: Vec<Spanned<Token>>,
+) -> Result<T, Vec<Diagnostic>> {
+    let len = source_text.chars().count();
+    let tokens: Vec<_> = tokens
+        .into_iter()
+        .filter(|(token, _)| *token != Token::Comment)
+        .collect();
+
+    let unclosed = unclosed_delimiters(&tokens, len);
+    if !unclosed.is_empty() {
+        return Err(unclosed.into_iter().map(parse_diagnostic).collect());
+    }
+
+    parser
+        .then_ignore(end())
+        .parse(Stream::from_iter(len..len, tokens.into_iter()))
+        .map_err(|errors| errors.into_iter().map(parse_diagnostic).collect())
+}
@@ -0,0 +1,356 @@
+//! `dberd diff`: what changed between two versions of a schema.
+
+use std::fmt::Write;
+
+use crate::{
+    formatter,
+    parser::Module,
+    schema::{Column, Entity, Relation},
+};
+
+/// An entity or column that has a different name in the new schema.
+#[derive(Debug, Clone)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+    /// Whether the rename was guessed from the columns rather than annotated with `was`.
+    pub guessed: bool,
+}
+
+/// How the entities and columns of the old schema are called in the new one.
+#[derive(Debug, Clone, Default)]
+pub struct Renames {
+    pub entities: Vec<Rename>,
+    /// The renamed columns, with the old name of their entity.
+    pub columns: Vec<(String, Rename)>,
+}
+
+impl Renames {
+    /// The new name of an entity of the old schema.
+    pub fn entity<'a>(&'a self, old: &'a str) -> &'a str {
+        self.entities
+            .iter()
+            .find(|rename| rename.from == old)
+            .map_or(old, |rename| &rename.to)
+    }
+
+    /// The new name of a column of the old schema, given the old name of its entity.
+    pub fn column<'a>(&'a self, entity: &str, old: &'a str) -> &'a str {
+        self.columns
+            .iter()
+            .find(|(renamed, rename)| renamed == entity && rename.from == old)
+            .map_or(old, |(_, rename)| &rename.to)
+    }
+}
+
+fn find<'a>(entities: &'a [Entity], name: &str) -> Option<&'a Entity> {
+    entities.iter().find(|entity| entity.name.0 == name)
+}
+
+fn column<'a>(entity: &'a Entity, name: &str) -> Option<&'a Column> {
+    entity.columns.iter().find(|column| column.name.0 == name)
+}
+
+/// Wide enough that defaults and checks are written on one line.
+const ONE_LINE: usize = 1000;
+
+/// What a column is like, apart from its name: its type, constraints, default and checks.
+fn parts(column: &Column) -> Vec<String> {
+    let mut parts = vec![column.ty.0.to_string()];
+    let mut constraints: Vec<String> = column.constraints.iter().map(|(c, _)| c.to_string()).collect();
+    constraints.sort();
+    parts.extend(constraints);
+    if let Some(default) = &column.default {
+        parts.push(format!("default {}", formatter::format_expr(default, ONE_LINE)));
+    }
+    for check in &column.checks {
+        parts.push(format!("check {}", formatter::format_expr(check, ONE_LINE)));
+    }
+    parts
+}
+
+/// What a column is like, apart from its name, on one line.
+fn shape(column: &Column) -> String {
+    parts(column).join(" ")
+}
+
+/// The columns of an entity, apart from its name.
+fn entity_shape(entity: &Entity) -> Vec<String> {
+    let mut columns: Vec<String> = entity
+        .columns
+        .iter()
+        .map(|column| format!("{}: {}", column.name.0, shape(column)))
+        .collect();
+    columns.sort();
+    columns
+}
+
+/// The share of the parts of two shapes that they have in common, from 0 to 1.
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    let common = a.iter().filter(|part| b.contains(part)).count();
+    match a.len().max(b.len()) {
+        0 => 1.0,
+        most => common as f64 / most as f64,
+    }
+}
+
+/// How similar a removed and an added shape have to be to be guessed as a rename.
+const SIMILAR: f64 = 0.5;
+
+/// Pairs up the names in `removed` and `added` whose shapes have the most in common, as long as
+/// they have at least half of it in common and no other pairing would be just as good.
+fn guess(removed: &[(String, Vec<String>)], added: &[(String, Vec<String>)]) -> Vec<Rename> {
+    let mut pairs: Vec<(usize, usize, f64)> = Vec::new();
+    for (i, (_, old)) in removed.iter().enumerate() {
+        for (j, (_, new)) in added.iter().enumerate() {
+            let score = similarity(old, new);
+            if score >= SIMILAR {
+                pairs.push((i, j, score));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let (mut removed_taken, mut added_taken) = (vec![false; removed.len()], vec![false; added.len()]);
+    let mut renames = Vec::new();
+    for &(i, j, score) in &pairs {
+        if removed_taken[i] || added_taken[j] {
+            continue;
+        }
+        let ambiguous = pairs.iter().any(|&(other_i, other_j, other_score)| {
+            other_score == score
+                && (other_i == i) != (other_j == j)
+                && !removed_taken[other_i]
+                && !added_taken[other_j]
+        });
+        // Neither is paired up with anything else either, since it would be a worse match.
+        removed_taken[i] = true;
+        added_taken[j] = true;
+        if !ambiguous {
+            renames.push(Rename {
+                from: removed[i].0.clone(),
+                to: added[j].0.clone(),
+                guessed: true,
+            });
+        }
+    }
+    renames
+}
+
+/// Finds what was renamed between `old` and `new`: what is annotated with `was`, and if
+/// `guess_renames` is set, entities and columns that were removed while one much like them was
+/// added.
+pub fn renames(old: &Module, new: &Module, guess_renames: bool) -> Result<Renames, String> {
+    let mut renames = Renames::default();
+
+    for entity in &new.entities {
+        let Some((from, _)) = &entity.renamed_from else {
+            continue;
+        };
+        // Annotations are left in place after the rename was migrated.
+        if find(&old.entities, &entity.name.0).is_some() {
+            continue;
+        }
+        if find(&old.entities, from).is_none() {
+            return Err(format!(
+                "'{}' was renamed from '{from}', but the old schema has no entity of that name",
+                entity.name.0
+            ));
+        }
+        renames.entities.push(Rename {
+            from: from.clone(),
+            to: entity.name.0.clone(),
+            guessed: false,
+        });
+    }
+
+    if guess_renames {
+        let removed: Vec<_> = old
+            .entities
+            .iter()
+            .filter(|entity| {
+                find(&new.entities, &entity.name.0).is_none()
+                    && !renames.entities.iter().any(|rename| rename.from == entity.name.0)
+            })
+            .map(|entity| (entity.name.0.clone(), entity_shape(entity)))
+            .collect();
+        let added: Vec<_> = new
+            .entities
+            .iter()
+            .filter(|entity| {
+                find(&old.entities, &entity.name.0).is_none()
+                    && !renames.entities.iter().any(|rename| rename.to == entity.name.0)
+            })
+            .map(|entity| (entity.name.0.clone(), entity_shape(entity)))
+            .collect();
+        renames.entities.extend(guess(&removed, &added));
+    }
+
+    for old_entity in &old.entities {
+        let Some(new_entity) = find(&new.entities, renames.entity(&old_entity.name.0)) else {
+            continue;
+        };
+
+        let mut columns = Vec::new();
+        for new_column in &new_entity.columns {
+            let Some((from, _)) = &new_column.renamed_from else {
+                continue;
+            };
+            if column(old_entity, &new_column.name.0).is_some() {
+                continue;
+            }
+            if column(old_entity, from).is_none() {
+                return Err(format!(
+                    "'{}.{}' was renamed from '{from}', but '{}' had no column of that name",
+                    new_entity.name.0, new_column.name.0, old_entity.name.0
+                ));
+            }
+            columns.push(Rename {
+                from: from.clone(),
+                to: new_column.name.0.clone(),
+                guessed: false,
+            });
+        }
+
+        if guess_renames {
+            let removed: Vec<_> = old_entity
+                .columns
+                .iter()
+                .filter(|old_column| {
+                    column(new_entity, &old_column.name.0).is_none()
+                        && !columns.iter().any(|rename| rename.from == old_column.name.0)
+                })
+                .map(|old_column| (old_column.name.0.clone(), parts(old_column)))
+                .collect();
+            let added: Vec<_> = new_entity
+                .columns
+                .iter()
+                .filter(|new_column| {
+                    column(old_entity, &new_column.name.0).is_none()
+                        && !columns.iter().any(|rename| rename.to == new_column.name.0)
+                })
+                .map(|new_column| (new_column.name.0.clone(), parts(new_column)))
+                .collect();
+            columns.extend(guess(&removed, &added));
+        }
+
+        renames
+            .columns
+            .extend(columns.into_iter().map(|rename| (old_entity.name.0.clone(), rename)));
+    }
+
+    Ok(renames)
+}
+
+fn guessed(rename: &Rename) -> &'static str {
+    if rename.guessed {
+        " (guessed)"
+    } else {
+        ""
+    }
+}
+
+fn relation_text(relation: &Relation, from: &str, to: &str) -> String {
+    format!("relation {from} {} {to}", relation.cardinality.0)
+}
+
+/// Lists what changed from `old` to `new`, one line per entity, column or relation, with `+` for
+/// what was added, `-` for what was removed and `~` for what was changed.
+pub fn summary(old: &Module, new: &Module, renames: &Renames) -> String {
+    let mut out = String::new();
+
+    for rename in &renames.entities {
+        writeln!(out, "~ entity {} renamed to {}{}", rename.from, rename.to, guessed(rename)).unwrap();
+    }
+    for entity in &old.entities {
+        if find(&new.entities, renames.entity(&entity.name.0)).is_none() {
+            writeln!(out, "- entity {}", entity.name.0).unwrap();
+        }
+    }
+
+    for new_entity in &new.entities {
+        let Some(old_entity) = old
+            .entities
+            .iter()
+            .find(|entity| renames.entity(&entity.name.0) == new_entity.name.0)
+        else {
+            writeln!(out, "+ entity {}", new_entity.name.0).unwrap();
+            continue;
+        };
+
+        let mut changes = Vec::new();
+        for (entity, rename) in &renames.columns {
+            if *entity == old_entity.name.0 {
+                changes.push(format!("~ {} renamed to {}{}", rename.from, rename.to, guessed(rename)));
+            }
+        }
+        for old_column in &old_entity.columns {
+            let name = renames.column(&old_entity.name.0, &old_column.name.0);
+            match column(new_entity, name) {
+                None => changes.push(format!("- {}: {}", old_column.name.0, shape(old_column))),
+                Some(new_column) if shape(new_column) != shape(old_column) => {
+                    changes.push(format!("~ {name}: {} -> {}", shape(old_column), shape(new_column)));
+                }
+                Some(_) => {}
+            }
+        }
+        for new_column in &new_entity.columns {
+            let existed = old_entity
+                .columns
+                .iter()
+                .any(|old_column| renames.column(&old_entity.name.0, &old_column.name.0) == new_column.name.0);
+            if !existed {
+                changes.push(format!("+ {}: {}", new_column.name.0, shape(new_column)));
+            }
+        }
+
+        if !changes.is_empty() {
+            writeln!(out, "~ entity {}", new_entity.name.0).unwrap();
+            for change in changes {
+                writeln!(out, "    {change}").unwrap();
+            }
+        }
+    }
+
+    // Relations are told apart by the entities they relate, and matched up in order if there
+    // are several between the same entities.
+    let mut added: Vec<&Relation> = new.relations.iter().collect();
+    for relation in &old.relations {
+        let (from, to) = (renames.entity(&relation.from.0), renames.entity(&relation.to.0));
+        match added.iter().position(|other| other.from.0 == from && other.to.0 == to) {
+            Some(i) => {
+                let other = added.remove(i);
+                if other.cardinality.0 != relation.cardinality.0 {
+                    writeln!(
+                        out,
+                        "~ {} -> {}",
+                        relation_text(relation, from, to),
+                        other.cardinality.0
+                    )
+                    .unwrap();
+                }
+            }
+            // A removed relation isn't in the new schema, so it keeps the names of the old one.
+            None => writeln!(out, "- {}", relation_text(relation, &relation.from.0, &relation.to.0)).unwrap(),
+        }
+    }
+    for relation in added {
+        writeln!(out, "+ {}", relation_text(relation, &relation.from.0, &relation.to.0)).unwrap();
+    }
+
+    if out.is_empty() {
+        out.push_str("No changes.\n");
+    } else if renames
+        .entities
+        .iter()
+        .chain(renames.columns.iter().map(|(_, rename)| rename))
+        .any(|rename| rename.guessed)
+    {
+        out.push_str(
+            "\nGuessed renames pair up what was removed with what was added that is most like it. \
+             Annotate the new name with `was old_name` to pair it up differently, or pass \
+             --no-guess if nothing was renamed.\n",
+        );
+    }
+    out
+}
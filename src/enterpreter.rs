@@ -1,64 +1,470 @@
-use std::collections::HashMap;
+use std::{cell::Cell, cmp::Ordering, collections::HashMap, fmt::Display};
 
-use crate::parser::{Expr, LiteralValue};
+use crate::{diagnostics::{Diagnostic, Label, Severity}, formatter, json, lexer::{Span, Spanned}, number::Number, parser::{is_ident_reserved, BinaryOp, Expr, LiteralValue}};
 
-#[derive(Clone)]
-struct Context {
-    pub idents: HashMap<String, Expr>
+#[derive(Clone, Default)]
+pub struct Context {
+    pub idents: HashMap<String, Spanned<Expr>>
 }
 
-pub fn interpret(expr: Expr) -> Result<LiteralValue, String> {
-    if let Expr::Literal(LiteralValue::Function { body }) = expr {
+/// How deep any evaluation may go, whatever its [`Limits`] say, so that it can't overflow the
+/// stack it runs on.
+pub const MAX_DEPTH: usize = 10_000;
+
+/// The size of the stack evaluations run on, which has room for [`MAX_DEPTH`] levels even in a
+/// debug build.
+const STACK_SIZE: usize = 512 << 20;
+
+/// Bounds on the resources a single evaluation may use, e.g. when running untrusted code. Every
+/// limit but the depth is off by default.
+///
+/// The depth is always at most [`MAX_DEPTH`]: leaving `max_depth` out means [`MAX_DEPTH`], and
+/// `--max-depth` rejects anything above it. Limits built in code with a bigger `max_depth` are
+/// treated as [`MAX_DEPTH`], and reaching it says so.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct Limits {
+    /// Stop evaluating after this many steps.
+    #[arg(long, value_name = "STEPS")]
+    pub max_steps: Option<u64>,
+    /// Stop evaluating expressions nested deeper than this, e.g. by recursion. Defaults to, and
+    /// can't go over, 10000.
+    #[arg(long, value_name = "DEPTH", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(..=MAX_DEPTH as u64))]
+    pub max_depth: Option<usize>,
+    /// Stop evaluating once bindings and values take up more than about this many bytes.
+    #[arg(long, value_name = "BYTES")]
+    pub max_memory: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps,
+    Depth,
+    Memory,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Steps => write!(f, "step"),
+            Limit::Depth => write!(f, "depth"),
+            Limit::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// The program did something wrong, like dividing by zero, while evaluating the expression at
+    /// `span`. Errors start out without a span, and get the span of the innermost expression they
+    /// are returned from.
+    Runtime { message: String, span: Option<Span> },
+    /// The evaluation went over one of its [`Limits`] while evaluating the expression at `span`.
+    LimitExceeded { limit: Limit, max: u64, span: Span },
+    /// An `assert` or `assert_eq` failed. `expected` is what `actual` should have been equal to,
+    /// for `assert_eq`.
+    AssertionFailed {
+        actual: Box<Spanned<LiteralValue>>,
+        expected: Option<Box<Spanned<LiteralValue>>>,
+    },
+    /// The [`Observer`] stopped the evaluation, e.g. because the debugger was told to quit. Nothing
+    /// went wrong with the program.
+    Stopped,
+}
+
+impl EvalError {
+    /// The code of the error, which every error but [`EvalError::Stopped`] has.
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            EvalError::Runtime { .. } => Some("E0014"),
+            EvalError::LimitExceeded { .. } => Some("E0006"),
+            EvalError::AssertionFailed { .. } => Some("E0007"),
+            EvalError::Stopped => None,
+        }
+    }
+
+    /// Gives a runtime error the span of the expression it was returned from, unless it already
+    /// has the span of one inside it.
+    pub fn at(self, span: &Span) -> Self {
+        match self {
+            EvalError::Runtime { message, span: None } => EvalError::Runtime { message, span: Some(span.clone()) },
+            error => error,
+        }
+    }
+
+    /// Describes the error as a diagnostic, if it can be pointed at a place in the source.
+    pub fn diagnostic(&self) -> Option<Diagnostic> {
+        if let EvalError::Runtime { message, span: Some(span) } = self {
+            return Some(Diagnostic {
+                severity: Severity::Error,
+                code: self.code()?,
+                message: format!("{message}."),
+                span: span.clone(),
+                labels: vec![Label {
+                    span: span.clone(),
+                    message: "Evaluating this failed.".into(),
+                }],
+                help: None,
+            });
+        }
+
+        if let EvalError::AssertionFailed { actual, expected } = self {
+            let (actual, actual_span) = actual.as_ref();
+            let mut labels = vec![Label {
+                span: actual_span.clone(),
+                message: format!("This is {}.", show(actual)),
+            }];
+            if let Some((expected, span)) = expected.as_deref() {
+                labels.push(Label {
+                    span: span.clone(),
+                    message: format!("But it should be {}.", show(expected)),
+                });
+            }
+            return Some(Diagnostic {
+                severity: Severity::Error,
+                code: self.code()?,
+                message: "Assertion failed.".into(),
+                span: actual_span.clone(),
+                labels,
+                help: expected.as_deref().map(|(expected, _)| {
+                    format!("expected: {}\n  actual: {}", show(expected), show(actual))
+                }),
+            });
+        }
+
+        let EvalError::LimitExceeded { limit, max, span } = self else {
+            return None;
+        };
+        Some(Diagnostic {
+            severity: Severity::Error,
+            code: self.code()?,
+            message: "Evaluation limit exceeded.".into(),
+            span: span.clone(),
+            labels: vec![Label {
+                span: span.clone(),
+                message: format!("The {limit} limit of {max} was reached here."),
+            }],
+            help: Some(match limit {
+                Limit::Steps => "Raise the limit with '--max-steps'.".into(),
+                Limit::Depth if *max == MAX_DEPTH as u64 => format!("The depth can't go over {MAX_DEPTH}."),
+                Limit::Depth => "Raise the limit with '--max-depth'.".into(),
+                Limit::Memory => "Raise the limit with '--max-memory'.".into(),
+            }),
+        })
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = self.code().unwrap_or_default();
+        match self {
+            EvalError::Runtime { message, .. } => write!(f, "{message}"),
+            EvalError::LimitExceeded { limit, max, .. } => {
+                write!(f, "[{code}] Evaluation went over its {limit} limit of {max}")
+            }
+            EvalError::AssertionFailed { actual, expected: None } => {
+                write!(f, "[{code}] Assertion failed: {} is not true", show(&actual.0))
+            }
+            EvalError::AssertionFailed { actual, expected: Some(expected) } => write!(
+                f,
+                "[{code}] Assertion failed: expected {}, but got {}",
+                show(&expected.0),
+                show(&actual.0)
+            ),
+            EvalError::Stopped => write!(f, "Evaluation was stopped"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl From<String> for EvalError {
+    fn from(message: String) -> Self {
+        EvalError::Runtime { message, span: None }
+    }
+}
+
+impl From<&str> for EvalError {
+    fn from(message: &str) -> Self {
+        EvalError::Runtime { message: message.into(), span: None }
+    }
+}
+
+/// Watches an evaluation as it happens, e.g. to trace or debug it.
+///
+/// Evaluation runs on a thread of its own, so observers have to be [`Send`].
+pub trait Observer: Send {
+    /// Called before `expr` is evaluated, `depth` calls deep into the evaluation. Returning an
+    /// error, like [`EvalError::Stopped`], stops the evaluation with that error.
+    fn enter(&mut self, expr: &Spanned<Expr>, depth: usize, ctx: &Context) -> Result<(), EvalError>;
+
+    /// Called before the body of the function `name` is evaluated for the call at `span`. `ctx`
+    /// is the context of the caller.
+    fn call(&mut self, _name: &str, _span: &Span, _ctx: &Context) {}
+
+    /// Called after the body of the innermost function called returns.
+    fn returned(&mut self) {}
+}
+
+impl Observer for () {
+    fn enter(&mut self, _: &Spanned<Expr>, _: usize, _: &Context) -> Result<(), EvalError> {
+        Ok(())
+    }
+}
+
+struct Interpreter<'a> {
+    observer: &'a mut dyn Observer,
+    limits: &'a Limits,
+    depth: usize,
+    steps: u64,
+    /// An estimate of the bytes used by the contexts of every expression being evaluated.
+    memory: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(observer: &'a mut dyn Observer, limits: &'a Limits) -> Self {
+        Self { observer, limits, depth: 0, steps: 0, memory: 0 }
+    }
+}
+
+pub fn interpret(expr: Spanned<Expr>) -> Result<LiteralValue, EvalError> {
+    interpret_with(expr, &mut (), &Limits::default())
+}
+
+/// Interprets a file like [`interpret`] within `limits`, telling `observer` about every step.
+pub fn interpret_with(expr: Spanned<Expr>, observer: &mut dyn Observer, limits: &Limits) -> Result<LiteralValue, EvalError> {
+    if let (Expr::Literal(LiteralValue::Function { body, .. }), _) = expr {
         let ctx = Context {
             idents: HashMap::new()
         };
-        interpret_expr(*body, ctx)
+        on_stack(|| Interpreter::new(observer, limits).interpret_expr(*body, ctx))
     } else {
-        Err("File was not parsed as a function".into())
+        Err(EvalError::from("File was not parsed as a function").at(&expr.1))
     }
 }
 
-fn interpret_expr(expr: Expr, mut ctx: Context) -> Result<LiteralValue, String> {
-    match expr {
-        Expr::Literal(literal) => Ok(literal),
-        Expr::Call { fun } =>  {
-            match *fun {
-                Expr::Literal(LiteralValue::Function { body }) => {
-                    interpret_expr(*body, ctx)
+/// Evaluates a single expression in an existing context, e.g. for a debugger's watch expressions.
+pub fn evaluate(expr: Spanned<Expr>, ctx: &Context, limits: &Limits) -> Result<LiteralValue, EvalError> {
+    on_stack(|| Interpreter::new(&mut (), limits).interpret_expr(expr, ctx.clone()))
+}
+
+thread_local! {
+    /// Whether this thread was started by [`on_stack`].
+    static ON_STACK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `evaluate` on a thread with a stack of [`STACK_SIZE`], whatever the stack of the calling
+/// thread is. Every evaluation goes through here, so a program that evaluates many expressions,
+/// e.g. one default per row, should run all of them within a single call: evaluations on a thread
+/// this started run right away instead of starting another one.
+pub fn on_stack<T: Send>(evaluate: impl FnOnce() -> T + Send) -> T {
+    if ON_STACK.get() {
+        return evaluate();
+    }
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new()
+            .name("evaluation".into())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                ON_STACK.set(true);
+                evaluate()
+            })
+            .expect("Failed to start the evaluation thread");
+        thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+impl Interpreter<'_> {
+    fn interpret_expr(&mut self, expr: Spanned<Expr>, ctx: Context) -> Result<LiteralValue, EvalError> {
+        self.observer.enter(&expr, self.depth, &ctx)?;
+
+        self.steps += 1;
+        let ctx_memory = ctx.idents.len() * std::mem::size_of::<(String, Spanned<Expr>)>();
+        self.memory += ctx_memory;
+        self.check(Limit::Steps, self.steps, &expr.1)?;
+        self.check(Limit::Depth, self.depth as u64, &expr.1)?;
+        self.check(Limit::Memory, self.memory as u64, &expr.1)?;
+
+        let span = expr.1.clone();
+        self.depth += 1;
+        let value = self.step(expr, ctx);
+        self.depth -= 1;
+        self.memory -= ctx_memory;
+
+        let value = value.map_err(|error| error.at(&span))?;
+        self.check(Limit::Memory, (self.memory + value_memory(&value)) as u64, &span)?;
+        Ok(value)
+    }
+
+    fn check(&self, limit: Limit, used: u64, span: &Span) -> Result<(), EvalError> {
+        let max = match limit {
+            Limit::Steps => self.limits.max_steps,
+            Limit::Depth => Some(self.limits.max_depth.map_or(MAX_DEPTH, |max| max.min(MAX_DEPTH)) as u64),
+            Limit::Memory => self.limits.max_memory.map(|max| max as u64),
+        };
+        match max {
+            Some(max) if used > max => Err(EvalError::LimitExceeded { limit, max, span: span.clone() }),
+            _ => Ok(()),
+        }
+    }
+
+    fn step(&mut self, (expr, expr_span): Spanned<Expr>, mut ctx: Context) -> Result<LiteralValue, EvalError> {
+        match expr {
+            Expr::Literal(literal) => Ok(literal),
+            Expr::Call { fun, args } =>  {
+                let (name, params, body) = match *fun {
+                    (Expr::Literal(LiteralValue::Function { params, body }), _) => ("<anonymous>".to_string(), params, body),
+                    (Expr::Ident(ident), span) => {
+                        if !ctx.idents.contains_key(&ident) && is_ident_reserved(&ident) {
+                            let args = args
+                                .into_iter()
+                                .map(|(arg, span)| Ok((self.interpret_expr((arg, span.clone()), ctx.clone())?, span)))
+                                .collect::<Result<Vec<_>, EvalError>>()?;
+                            return match ident.as_str() {
+                                "assert" | "assert_eq" => assert(&ident, args),
+                                _ => Ok(call_builtin(&ident, args.into_iter().map(|(arg, _)| arg).collect())?),
+                            };
+                        }
+                        let LiteralValue::Function { params, body } = self.interpret_expr((Expr::Ident(ident.clone()), span), ctx.clone())? else {
+                            return Err("Cannot call a non function".into())
+                        };
+                        (ident, params, body)
+                    }
+                    _ => return Err("Cannot call a non function".into())
+                };
+
+                if params.len() != args.len() {
+                    return Err(format!("Expected {} arguments but got {}", params.len(), args.len()).into());
                 }
-                Expr::Ident(ident) => {
-                    println!("ident_call");
-                    let LiteralValue::Function { body} = interpret_expr(Expr::Ident(ident), ctx.clone())? else {
-                        return Err("Cannot call a non function".into())
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.interpret_expr(arg, ctx.clone()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.observer.call(&name, &expr_span, &ctx);
+                for (param, arg) in params.into_iter().zip(args) {
+                    let (Expr::Ident(param), span) = param else {
+                        return Err("Expected Ident".into());
                     };
-                        interpret_expr(*body, ctx)
-                    
+                    ctx.idents.insert(param, (Expr::Literal(arg), span));
+                }
+                let value = self.interpret_expr(*body, ctx)?;
+                self.observer.returned();
+                Ok(value)
+            },
+            Expr::Ident(ref ident) => match ctx.idents.get(ident) {
+                // Arguments are evaluated before the call, so looking them up isn't a step of its
+                // own. Stepping into them would jump to the line of the call and back.
+                Some((Expr::Literal(value), _)) => Ok(value.clone()),
+                Some(val) => self.interpret_expr(val.clone(), ctx),
+                None => Err("Unknown Ident".into()),
+            },
+            Expr::Let { ident, value, body } => {
+                let (Expr::Ident(ident), _) = *ident else {
+                    return Err("Expected Ident".into());
+                };
+                ctx.idents.insert(ident, *value);
+                self.interpret_expr(*body, ctx)
+            },
+            Expr::Grouping(expr) => self.interpret_expr(*expr, ctx),
+            Expr::Binary { op, lhs, rhs } => {
+                let lits = (self.interpret_expr(*lhs, ctx.clone())?,self.interpret_expr(*rhs, ctx.clone())?);
+                match (op, lits) {
+                    (BinaryOp::Add, (LiteralValue::Number(lhs), LiteralValue::Number(rhs))) => Ok(LiteralValue::Number(lhs.add(&rhs)?)),
+                    (BinaryOp::Divide, (LiteralValue::Number(lhs), LiteralValue::Number(rhs))) => Ok(LiteralValue::Number(lhs.div(&rhs)?)),
+                    (BinaryOp::ShiftLeft, (LiteralValue::Number(lhs), LiteralValue::Number(rhs))) => Ok(LiteralValue::Number(lhs.shl(&rhs)?)),
+                    (BinaryOp::ShiftRight, (LiteralValue::Number(lhs), LiteralValue::Number(rhs))) => Ok(LiteralValue::Number(lhs.shr(&rhs)?)),
+                    (BinaryOp::BitAnd, (LiteralValue::Number(lhs), LiteralValue::Number(rhs))) => Ok(LiteralValue::Number(lhs.bitand(&rhs)?)),
+                    (BinaryOp::BitXor, (LiteralValue::Number(lhs), LiteralValue::Number(rhs))) => Ok(LiteralValue::Number(lhs.bitxor(&rhs)?)),
+                    (BinaryOp::BitOr, (LiteralValue::Number(lhs), LiteralValue::Number(rhs))) => Ok(LiteralValue::Number(lhs.bitor(&rhs)?)),
+                    (BinaryOp::Add, _) => Err("Only numbers can be added together".into()),
+                    (BinaryOp::Divide, _) => Err("Only numbers can be divided".into()),
+                    (op, _) => Err(format!("'{op}' only works on integers").into()),
                 }
-                _ => Err("Cannot call a non function".into())
+            },
+        
+        }
+    }
+}
+
+/// Shows a value as [`formatter::format_value`] does, for error messages.
+fn show(value: &LiteralValue) -> String {
+    formatter::format_value(value, 60, None)
+}
+
+/// Checks an `assert{condition}` or `assert_eq{actual, expected}` call, which give `true` if they
+/// hold.
+fn assert(name: &str, mut args: Vec<Spanned<LiteralValue>>) -> Result<LiteralValue, EvalError> {
+    match (name, args.len()) {
+        ("assert", 1) => match args.remove(0) {
+            (LiteralValue::Boolean(true), _) => Ok(LiteralValue::Boolean(true)),
+            actual @ (LiteralValue::Boolean(false), _) => {
+                Err(EvalError::AssertionFailed { actual: Box::new(actual), expected: None })
             }
+            _ => Err("assert expects a boolean".into()),
         },
-        Expr::Ident(ref ident) => {
-            if let Some(val) = ctx.idents.get(ident) {
-                interpret_expr(val.clone(), ctx)
+        ("assert_eq", 2) => {
+            let expected = args.pop().unwrap();
+            let actual = args.pop().unwrap();
+            if actual.0 == expected.0 {
+                Ok(LiteralValue::Boolean(true))
             } else {
-                Err("Unknown Ident".into())
-            }
-        },
-        Expr::Let { ident, value, body } => {
-            let Expr::Ident(ident) = *ident else {
-                return Err("Expected Ident".into());
-            };
-            ctx.idents.insert(ident, *value);
-            interpret_expr(*body, ctx)
-        },
-        Expr::Grouping(expr) => interpret_expr(*expr, ctx),
-        Expr::Addition { lhs, rhs } => {
-            let lits = (interpret_expr(*lhs, ctx.clone())?,interpret_expr(*rhs, ctx.clone())?);
-            match lits {
-                (LiteralValue::Number(lhs), LiteralValue::Number(rhs)) => Ok(LiteralValue::Number(lhs + rhs)),
-                _ => Err("Only numbers can be added together".into())
+                Err(EvalError::AssertionFailed { actual: Box::new(actual), expected: Some(Box::new(expected)) })
             }
-        },
-        
+        }
+        ("assert", _) => Err("assert expects one boolean".into()),
+        _ => Err("assert_eq expects two values".into()),
+    }
+}
+
+/// An estimate of the bytes a value takes up, not counting what it shares with the source.
+fn value_memory(value: &LiteralValue) -> usize {
+    std::mem::size_of::<LiteralValue>() + match value {
+        LiteralValue::Number(number) => number.memory(),
+        LiteralValue::String(string) => string.len(),
+        LiteralValue::Array(items) => items.len() * std::mem::size_of::<Spanned<Expr>>(),
+        LiteralValue::Record(fields) => fields
+            .iter()
+            .map(|(name, _)| name.len() + std::mem::size_of::<(String, Spanned<Expr>)>())
+            .sum(),
+        LiteralValue::Boolean(_) | LiteralValue::Function { .. } => 0,
     }
-}
\ No newline at end of file
+}
+
+fn call_builtin(name: &str, args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    match (name, args.as_slice()) {
+        ("add", [LiteralValue::Number(lhs), LiteralValue::Number(rhs)]) => Ok(LiteralValue::Number(lhs.add(rhs)?)),
+        ("trunc", [LiteralValue::Number(number)]) => Ok(LiteralValue::Number(number.trunc()?)),
+        ("round", [LiteralValue::Number(number)]) => Ok(LiteralValue::Number(number.round()?)),
+        ("float", [LiteralValue::Number(number)]) => Ok(LiteralValue::Number(number.to_finite_float()?)),
+        ("num", [LiteralValue::Number(number)]) => Ok(LiteralValue::Number(number.numerator()?)),
+        ("den", [LiteralValue::Number(number)]) => Ok(LiteralValue::Number(number.denominator()?)),
+        ("eq", [LiteralValue::Number(lhs), LiteralValue::Number(rhs)]) => {
+            Ok(LiteralValue::Boolean(lhs.compare(rhs) == Some(Ordering::Equal)))
+        }
+        ("eq", [lhs, rhs]) => Ok(LiteralValue::Boolean(lhs == rhs)),
+        ("lt" | "le" | "gt" | "ge", [LiteralValue::Number(lhs), LiteralValue::Number(rhs)]) => {
+            let ordering = lhs.compare(rhs);
+            Ok(LiteralValue::Boolean(match name {
+                "lt" => ordering == Some(Ordering::Less),
+                "le" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                "gt" => ordering == Some(Ordering::Greater),
+                _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            }))
+        }
+        ("not", [LiteralValue::Boolean(value)]) => Ok(LiteralValue::Boolean(!value)),
+        ("and", [LiteralValue::Boolean(lhs), LiteralValue::Boolean(rhs)]) => Ok(LiteralValue::Boolean(*lhs && *rhs)),
+        ("or", [LiteralValue::Boolean(lhs), LiteralValue::Boolean(rhs)]) => Ok(LiteralValue::Boolean(*lhs || *rhs)),
+        ("len", [LiteralValue::String(string)]) => Ok(LiteralValue::Number(Number::Integer(string.chars().count() as i64))),
+        ("from_json", [LiteralValue::String(text)]) => json::parse(text),
+        ("to_json", [value]) => Ok(LiteralValue::String(json::to_json(value)?.to_string())),
+        ("add", _) => Err("add expects two numbers".into()),
+        ("trunc" | "round" | "float" | "num" | "den", _) => Err(format!("{name} expects one number")),
+        ("eq", _) => Err("eq expects two values".into()),
+        ("lt" | "le" | "gt" | "ge", _) => Err(format!("{name} expects two numbers")),
+        ("not", _) => Err("not expects one boolean".into()),
+        ("and" | "or", _) => Err(format!("{name} expects two booleans")),
+        ("len", _) => Err("len expects one string".into()),
+        ("from_json", _) => Err("from_json expects one string".into()),
+        ("to_json", _) => Err("to_json expects one value".into()),
+        _ => Err(format!("Unknown builtin '{name}'")),
+    }
+}
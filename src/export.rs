@@ -0,0 +1,143 @@
+use std::fmt::Write;
+
+use crate::{
+    parser::Module,
+    schema::{Cardinality, Column, Constraint},
+    sql::{self, Dialect},
+};
+
+/// What `dberd export` renders a schema as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// A Graphviz digraph, e.g. for `dot -Tsvg`.
+    Dot,
+    /// A Mermaid entity relationship diagram.
+    Mermaid,
+    /// `CREATE TABLE` statements in the given SQL dialect.
+    Sql,
+}
+
+/// Renders the entities and relations of `module`, in the order they were declared. `dialect` is
+/// only used for SQL.
+pub fn export(module: &Module, format: Format, dialect: Dialect) -> String {
+    match format {
+        Format::Dot => dot(module),
+        Format::Mermaid => mermaid(module),
+        Format::Sql => sql::ddl(module, dialect),
+    }
+}
+
+fn column_text(column: &Column) -> String {
+    let mut text = format!("{}: {}", column.name.0, column.ty.0);
+    for (constraint, _) in &column.constraints {
+        write!(text, " {constraint}").unwrap();
+    }
+    text
+}
+
+/// Escapes the characters that are special inside a record label.
+fn escape_record(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Entities become record nodes listing their columns, and relations edges from one entity to the
+/// other, labelled with their cardinality.
+fn dot(module: &Module) -> String {
+    let mut out = String::from("digraph schema {\n    node [shape=record];\n");
+
+    if !module.entities.is_empty() {
+        out.push('\n');
+    }
+    for entity in &module.entities {
+        let columns: String = entity
+            .columns
+            .iter()
+            .map(|column| format!("{}\\l", escape_record(&column_text(column))))
+            .collect();
+        writeln!(
+            out,
+            "    \"{}\" [label=\"{{{}|{columns}}}\"];",
+            entity.name.0,
+            escape_record(&entity.name.0)
+        )
+        .unwrap();
+    }
+
+    if !module.relations.is_empty() {
+        out.push('\n');
+    }
+    for relation in &module.relations {
+        writeln!(
+            out,
+            "    \"{}\" -> \"{}\" [label=\"{}\"];",
+            relation.from.0, relation.to.0, relation.cardinality.0
+        )
+        .unwrap();
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// The crow's foot marker for the `to` end of a relation.
+fn mermaid_marker(cardinality: Cardinality) -> &'static str {
+    match (cardinality.min, cardinality.max) {
+        (0, Some(0 | 1)) => "o|",
+        (_, Some(1)) => "||",
+        (0, _) => "o{",
+        _ => "|{",
+    }
+}
+
+/// Relations are drawn as one-to-`cardinality`, since they only say how many rows of `to` each
+/// row of `from` has.
+fn mermaid(module: &Module) -> String {
+    let mut out = String::from("erDiagram\n");
+
+    for entity in &module.entities {
+        if entity.columns.is_empty() {
+            writeln!(out, "    {}", entity.name.0).unwrap();
+            continue;
+        }
+
+        writeln!(out, "    {} {{", entity.name.0).unwrap();
+        for column in &entity.columns {
+            let keys: Vec<_> = column
+                .constraints
+                .iter()
+                .filter_map(|(constraint, _)| match constraint {
+                    Constraint::PrimaryKey => Some("PK"),
+                    Constraint::Unique => Some("UK"),
+                    Constraint::Optional => None,
+                })
+                .collect();
+            write!(out, "        {} {}", column.ty.0, column.name.0).unwrap();
+            if !keys.is_empty() {
+                write!(out, " {}", keys.join(", ")).unwrap();
+            }
+            out.push('\n');
+        }
+        out.push_str("    }\n");
+    }
+
+    for relation in &module.relations {
+        writeln!(
+            out,
+            "    {} ||--{} {} : \"{}\"",
+            relation.from.0,
+            mermaid_marker(relation.cardinality.0),
+            relation.to.0,
+            relation.cardinality.0
+        )
+        .unwrap();
+    }
+
+    out
+}
@@ -0,0 +1,412 @@
+use std::{iter::Peekable, vec};
+
+use crate::{
+    lexer::{Span, Spanned, Token},
+    parser::{Expr, Item, LiteralValue, Module},
+    schema::Entity,
+};
+
+/// How far nested function bodies and `let` values are indented when they don't fit on one line.
+const INDENT: usize = 4;
+
+/// A pretty-printing document, laid out by [`Doc::render`].
+///
+/// Groups are printed flat (every [`Doc::Line`] becomes a space) if they fit in the remaining
+/// width, otherwise every line directly inside of them is broken.
+#[derive(Debug, Clone)]
+enum Doc {
+    Text(String),
+    /// A space when flat, a newline when broken.
+    Line,
+    /// Nothing when flat, a newline when broken.
+    SoftLine,
+    /// Always a newline. Forces every group containing it to break.
+    HardLine,
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+impl Doc {
+    fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    fn nest(doc: Doc) -> Self {
+        Doc::Nest(INDENT, Box::new(doc))
+    }
+
+    fn group(doc: Doc) -> Self {
+        Doc::Group(Box::new(doc))
+    }
+
+    fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        let mut stack = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    out.push_str(text);
+                    column += text.chars().count();
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    out.push(' ');
+                    column += 1;
+                }
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    out.push('\n');
+                    out.extend(std::iter::repeat_n(' ', indent));
+                    column = indent;
+                }
+                Doc::Nest(amount, doc) => stack.push((indent + amount, mode, doc)),
+                Doc::Group(doc) => {
+                    let remaining = width as isize - column as isize;
+                    let mode = if mode == Mode::Flat || fits(remaining, (indent, doc), &stack) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            }
+        }
+
+        out
+    }
+}
+
+/// Checks if `next` fits flat in `remaining` columns, including whatever follows it on the same line.
+fn fits(mut remaining: isize, next: (usize, &Doc), rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(next.0, Mode::Flat, next.1)];
+    let mut rest = rest.iter().rev();
+
+    while remaining >= 0 {
+        let Some((indent, mode, doc)) = stack.pop().or_else(|| rest.next().copied()) else {
+            return true;
+        };
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine => return true,
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::Nest(amount, doc) => stack.push((indent + amount, mode, doc)),
+            Doc::Group(doc) => stack.push((indent, mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+
+    false
+}
+
+/// How function literals are printed.
+#[derive(Debug, Clone, Copy)]
+enum Functions<'a> {
+    /// In full, as they were written.
+    Source,
+    /// As `<fn arity=2>`, which is all that matters about a value. If the name and text of the
+    /// file they were defined in are known, they are shown with the line they start on.
+    Summary(Option<(&'a str, &'a str)>),
+}
+
+struct Formatter<'a> {
+    /// The source being formatted, if any. Number literals are copied from it as they were
+    /// written, so `0xFF` doesn't become `255`.
+    source: Option<&'a str>,
+    comments: Peekable<vec::IntoIter<Spanned<String>>>,
+    functions: Functions<'a>,
+}
+
+impl Formatter<'_> {
+    /// Takes every comment that appears before `position` so it can be printed ahead of the
+    /// expression starting there.
+    fn comments_before(&mut self, position: usize) -> Vec<Doc> {
+        let mut docs = Vec::new();
+        while let Some((comment, _)) = self.comments.next_if(|(_, span)| span.start < position) {
+            docs.push(Doc::Text(comment));
+            docs.push(Doc::HardLine);
+        }
+        docs
+    }
+
+    fn expr(&mut self, (expr, span): &Spanned<Expr>) -> Doc {
+        let mut docs = self.comments_before(span.start);
+
+        docs.push(match expr {
+            Expr::Literal(literal) => self.literal(literal, span),
+            Expr::Call { fun, args } => {
+                Doc::Concat(vec![Doc::text(":"), self.expr(fun), self.list("{", args, "}")])
+            }
+            Expr::Ident(ident) => Doc::text(ident),
+            Expr::Let { ident, value, body } => {
+                let head = Doc::group(Doc::Concat(vec![
+                    Doc::text("let "),
+                    self.expr(ident),
+                    Doc::text(" ="),
+                    Doc::nest(Doc::Concat(vec![Doc::Line, self.expr(value)])),
+                    Doc::Line,
+                    Doc::text("in"),
+                ]));
+                Doc::group(Doc::Concat(vec![head, Doc::Line, self.expr(body)]))
+            }
+            Expr::Grouping(expr) => Doc::group(Doc::Concat(vec![
+                Doc::text("("),
+                Doc::nest(Doc::Concat(vec![Doc::SoftLine, self.expr(expr)])),
+                Doc::SoftLine,
+                Doc::text(")"),
+            ])),
+            Expr::Binary { op, lhs, rhs } => {
+                let lhs = self.expr(lhs);
+                Doc::group(Doc::Concat(vec![
+                    lhs,
+                    Doc::nest(Doc::Concat(vec![Doc::Line, Doc::text(format!("{op} ")), self.expr(rhs)])),
+                ]))
+            }
+        });
+
+        Doc::Concat(docs)
+    }
+
+    fn literal(&mut self, literal: &LiteralValue, span: &Span) -> Doc {
+        match literal {
+            LiteralValue::Number(number) => match self.source {
+                Some(source) => Doc::text(
+                    source
+                        .chars()
+                        .skip(span.start)
+                        .take(span.len())
+                        .filter(|c| !c.is_whitespace())
+                        .collect::<String>(),
+                ),
+                None => Doc::text(number.to_string()),
+            },
+            LiteralValue::String(string) => Doc::text(format!("{string:?}")),
+            LiteralValue::Boolean(boolean) => Doc::text(boolean.to_string()),
+            LiteralValue::Array(items) => self.list("[", items, "]"),
+            LiteralValue::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| Doc::Concat(vec![Doc::text(format!("{}: ", field_name(name))), self.expr(value)]))
+                    .collect();
+                delimited("{", fields, "}")
+            }
+            LiteralValue::Function { params, body } if matches!(self.functions, Functions::Summary(_)) => {
+                let mut summary = format!("<fn arity={}", params.len());
+                if let Functions::Summary(Some((file, text))) = self.functions {
+                    let start = params.first().map_or(body.1.start, |(_, span)| span.start);
+                    let line = text.chars().take(start).filter(|c| *c == '\n').count() + 1;
+                    summary.push_str(&format!(" defined at {file}:{line}"));
+                }
+                Doc::text(format!("{summary}>"))
+            }
+            LiteralValue::Function { params, body } => Doc::group(Doc::Concat(vec![
+                self.list("{", params, "}"),
+                Doc::text(" ->"),
+                Doc::nest(Doc::Concat(vec![Doc::Line, self.expr(body)])),
+            ])),
+        }
+    }
+
+    /// The items of a module, separated by blank lines.
+    fn module(&mut self, module: &Module) -> Vec<Doc> {
+        let mut docs = Vec::new();
+        for item in module.items() {
+            if !docs.is_empty() {
+                docs.extend([Doc::HardLine, Doc::HardLine]);
+            }
+
+            match item {
+                Item::Expr(expr) => docs.push(self.expr(expr)),
+                Item::Test(test) => {
+                    docs.extend(self.comments_before(test.name.1.start));
+                    docs.push(Doc::group(Doc::Concat(vec![
+                        Doc::text(format!("test {:?} =", test.name.0)),
+                        Doc::nest(Doc::Concat(vec![Doc::Line, self.expr(&test.body)])),
+                    ])));
+                }
+                Item::Entity(entity) => docs.push(self.entity(entity)),
+                Item::Relation(relation) => {
+                    docs.extend(self.comments_before(relation.span.start));
+                    docs.push(Doc::text(format!(
+                        "relation {} {} {}",
+                        relation.from.0, relation.cardinality.0, relation.to.0
+                    )));
+                }
+            }
+        }
+        docs
+    }
+
+    /// Prints an entity on one line if it fits, otherwise with one column per line.
+    fn entity(&mut self, entity: &Entity) -> Doc {
+        let comments = self.comments_before(entity.span.start);
+        let mut header = format!("entity {}", entity.name.0);
+        if let Some((name, _)) = &entity.renamed_from {
+            header.push_str(&format!(" was {name}"));
+        }
+        let mut docs = vec![Doc::text(format!("{header} {{"))];
+        if entity.columns.is_empty() {
+            docs.push(Doc::text("}"));
+        }
+
+        let mut columns = Vec::new();
+        for (i, column) in entity.columns.iter().enumerate() {
+            if i > 0 {
+                columns.push(Doc::text(","));
+            }
+            columns.push(Doc::Line);
+            columns.extend(self.comments_before(column.name.1.start));
+            let mut text = format!("{}: {}", column.name.0, column.ty.0);
+            for (constraint, _) in &column.constraints {
+                text.push_str(&format!(" {constraint}"));
+            }
+            columns.push(Doc::Text(text));
+            if let Some(default) = &column.default {
+                columns.extend([Doc::text(" default "), self.expr(default)]);
+            }
+            for check in &column.checks {
+                columns.extend([Doc::text(" check "), self.expr(check)]);
+            }
+            if let Some((name, _)) = &column.renamed_from {
+                columns.push(Doc::Text(format!(" was {name}")));
+            }
+        }
+        if !columns.is_empty() {
+            docs.push(Doc::nest(Doc::Concat(columns)));
+            docs.extend([Doc::Line, Doc::text("}")]);
+        }
+        Doc::Concat(comments.into_iter().chain([Doc::group(Doc::Concat(docs))]).collect())
+    }
+
+    /// Prints comma separated items between delimiters, one per line if they don't fit.
+    fn list(&mut self, open: &str, items: &[Spanned<Expr>], close: &str) -> Doc {
+        let items = items.iter().map(|item| self.expr(item)).collect();
+        delimited(open, items, close)
+    }
+}
+
+/// Separates `items` with commas between `open` and `close`, one per line if they don't fit.
+fn delimited(open: &str, items: Vec<Doc>, close: &str) -> Doc {
+    if items.is_empty() {
+        return Doc::text(format!("{open}{close}"));
+    }
+
+    let mut elements = vec![Doc::SoftLine];
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            elements.push(Doc::text(","));
+            elements.push(Doc::Line);
+        }
+        elements.push(item);
+    }
+    Doc::group(Doc::Concat(vec![
+        Doc::text(open),
+        Doc::nest(Doc::Concat(elements)),
+        Doc::SoftLine,
+        Doc::text(close),
+    ]))
+}
+
+/// A record field name, quoted unless it is a plain word.
+fn field_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_word = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_word {
+        name.to_string()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+/// Extracts the text of every comment token from the source.
+fn comments(source: &str, tokens: &[Spanned<Token>]) -> Vec<Spanned<String>> {
+    tokens
+        .iter()
+        .filter(|(token, _)| *token == Token::Comment)
+        .map(|(_, span): &(Token, Span)| {
+            let text: String = source.chars().skip(span.start).take(span.len()).collect();
+            (text.trim_end().to_string(), span.clone())
+        })
+        .collect()
+}
+
+/// Pretty-prints `module` in the canonical dberd style, breaking lines that would be wider than
+/// `width`. Comments from `tokens` are kept and placed before the expression that follows them.
+/// Top-level declarations are separated by a blank line.
+pub fn format(source: &str, tokens: &[Spanned<Token>], module: &Module, width: usize) -> String {
+    let mut formatter = Formatter {
+        source: Some(source),
+        comments: comments(source, tokens).into_iter().peekable(),
+        functions: Functions::Source,
+    };
+
+    let mut docs = formatter.module(module);
+    for (comment, _) in formatter.comments {
+        if !docs.is_empty() {
+            docs.push(Doc::HardLine);
+        }
+        docs.push(Doc::Text(comment));
+    }
+
+    let mut formatted = Doc::Concat(docs).render(width);
+    formatted.push('\n');
+    formatted
+}
+
+/// Pretty-prints a module that wasn't parsed from source, e.g. one imported from SQL.
+pub fn format_module(module: &Module, width: usize) -> String {
+    let mut formatter = Formatter {
+        source: None,
+        comments: Vec::new().into_iter().peekable(),
+        functions: Functions::Source,
+    };
+    let mut formatted = Doc::Concat(formatter.module(module)).render(width);
+    formatted.push('\n');
+    formatted
+}
+
+/// Pretty-prints a single expression without comments or a trailing newline, e.g. for showing
+/// it in a tooltip.
+pub fn format_expr(expr: &Spanned<Expr>, width: usize) -> String {
+    let mut formatter = Formatter {
+        source: None,
+        comments: Vec::new().into_iter().peekable(),
+        functions: Functions::Source,
+    };
+    formatter.expr(expr).render(width)
+}
+
+/// Prints a value for people to read, breaking lines at `width`.
+///
+/// Numbers, strings and booleans are printed the way they would be written in source, so they
+/// parse back to the same value. Arrays and records have no literal syntax, and are printed as
+/// `[1, 2]` and `{name: value}` only to be read. Functions are only summarized as `<fn arity=N>`,
+/// with where they were defined if `file` gives the name and text of the file they come from.
+pub fn format_value(value: &LiteralValue, width: usize, file: Option<(&str, &str)>) -> String {
+    let mut formatter = Formatter {
+        source: None,
+        comments: Vec::new().into_iter().peekable(),
+        functions: Functions::Summary(file),
+    };
+    formatter.literal(value, &(0..0)).render(width)
+}
+
+/// Prints what a name is bound to: a value as by [`format_value`], or the expression a `let`
+/// binds it to, which is only evaluated once it is used.
+pub fn format_binding(expr: &Spanned<Expr>, width: usize, file: Option<(&str, &str)>) -> String {
+    match expr {
+        (Expr::Literal(value), _) => format_value(value, width, file),
+        expr => format_expr(expr, width),
+    }
+}
@@ -0,0 +1,889 @@
+//! `dberd import sql`: reads `CREATE TABLE` statements back as entities and relations.
+//!
+//! Only the parts of SQL that dberd can say something about are read: tables, their columns and
+//! types, primary keys, unique and foreign keys, and `NOT NULL`. Anything else is skipped with a
+//! warning, so that a schema dump can be imported as it is.
+
+use crate::{
+    diagnostics::{Diagnostic, Label, Severity},
+    lexer::{Span, Spanned},
+    parser::Module,
+    schema::{Cardinality, Column, ColumnType, Constraint, Entity, Relation},
+    sql,
+};
+
+/// The formats `dberd import` can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// `CREATE TABLE` statements, as written by Postgres, SQLite or MySQL.
+    Sql,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A keyword or an unquoted name.
+    Word(String),
+    /// A name in `"double quotes"`, `` `backticks` `` or `[brackets]`.
+    Quoted(String),
+    /// A `'string'` or a number.
+    Literal(String),
+    Symbol(char),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{word}'"),
+            Token::Quoted(name) => format!("'\"{name}\"'"),
+            Token::Literal(literal) => format!("'{literal}'"),
+            Token::Symbol(c) => format!("'{c}'"),
+        }
+    }
+}
+
+fn invalid(span: Span, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        code: "E0011",
+        message: "Invalid SQL found.".into(),
+        span: span.clone(),
+        labels: vec![Label { span, message }],
+        help: None,
+    }
+}
+
+fn unsupported(span: Span, message: &str, label: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        code: "W0004",
+        message: message.into(),
+        span: span.clone(),
+        labels: vec![Label { span, message: label }],
+        help: None,
+    }
+}
+
+/// Splits SQL into tokens, skipping whitespace and `--` and `/* */` comments.
+fn lex(source: &str) -> Result<Vec<Spanned<Token>>, Diagnostic> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        i += 1;
+
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '-' if chars.get(i) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i) == Some(&'*') => {
+                i += 1;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(invalid(start..i, "This comment is never closed.".into()));
+                }
+                i += 2;
+                continue;
+            }
+            '"' | '`' | '[' | '\'' => {
+                let close = if c == '[' { ']' } else { c };
+                let mut text = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Err(invalid(start..i, format!("Expected a closing {close}."))),
+                        // Quotes are escaped by doubling them.
+                        Some(&next) if next == close && close != ']' && chars.get(i + 1) == Some(&close) => {
+                            text.push(close);
+                            i += 2;
+                        }
+                        Some(&next) if next == close => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&next) => {
+                            text.push(next);
+                            i += 1;
+                        }
+                    }
+                }
+                if c == '\'' {
+                    Token::Literal(text)
+                } else {
+                    Token::Quoted(text)
+                }
+            }
+            c if c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                Token::Literal(chars[start..i].iter().collect())
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$')) {
+                    i += 1;
+                }
+                Token::Word(chars[start..i].iter().collect())
+            }
+            c => Token::Symbol(c),
+        };
+        tokens.push((token, start..i));
+    }
+
+    Ok(tokens)
+}
+
+struct SqlColumn {
+    name: Spanned<String>,
+    ty: ColumnType,
+    nullable: bool,
+}
+
+struct ForeignKey {
+    columns: Vec<Spanned<String>>,
+    table: Spanned<String>,
+}
+
+/// A key declared either with a column or on its own, e.g. by `ALTER TABLE`.
+enum Key {
+    Primary(Vec<Spanned<String>>),
+    Unique(Vec<Spanned<String>>),
+    Foreign(ForeignKey),
+}
+
+struct Table {
+    name: Spanned<String>,
+    columns: Vec<SqlColumn>,
+    primary_key: Vec<Spanned<String>>,
+    unique: Vec<Vec<Spanned<String>>>,
+    foreign_keys: Vec<ForeignKey>,
+}
+
+impl Table {
+    fn add(&mut self, key: Key) {
+        match key {
+            Key::Primary(columns) => self.primary_key = columns,
+            Key::Unique(columns) => self.unique.push(columns),
+            Key::Foreign(key) => self.foreign_keys.push(key),
+        }
+    }
+
+    fn column(&self, name: &str) -> Option<&SqlColumn> {
+        self.columns.iter().find(|column| column.name.0.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether `columns` are declared unique together, as the primary key or a unique key.
+    fn is_unique(&self, columns: &[Spanned<String>]) -> bool {
+        let same = |key: &[Spanned<String>]| {
+            key.len() == columns.len()
+                && key
+                    .iter()
+                    .all(|(name, _)| columns.iter().any(|(column, _)| column.eq_ignore_ascii_case(name)))
+        };
+        same(&self.primary_key) || self.unique.iter().any(|key| same(key))
+    }
+}
+
+/// Words that end a column's type and start one of its constraints.
+const COLUMN_CONSTRAINTS: &[&str] = &[
+    "CONSTRAINT",
+    "PRIMARY",
+    "NOT",
+    "NULL",
+    "UNIQUE",
+    "REFERENCES",
+    "DEFAULT",
+    "CHECK",
+    "COLLATE",
+    "GENERATED",
+    "AS",
+    "AUTOINCREMENT",
+    "AUTO_INCREMENT",
+    "COMMENT",
+];
+
+/// Words that start a table constraint rather than a column.
+const TABLE_CONSTRAINTS: &[&str] = &[
+    "CONSTRAINT",
+    "PRIMARY",
+    "UNIQUE",
+    "FOREIGN",
+    "CHECK",
+    "INDEX",
+    "KEY",
+    "FULLTEXT",
+    "SPATIAL",
+    "EXCLUDE",
+];
+
+struct Parser {
+    tokens: Vec<Spanned<Token>>,
+    pos: usize,
+    /// Where the source ends, for errors at the end of it.
+    end: usize,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Parser {
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(token, _)| token)
+    }
+
+    fn span(&self) -> Span {
+        match self.tokens.get(self.pos) {
+            Some((_, span)) => span.clone(),
+            None => self.end..self.end,
+        }
+    }
+
+    /// Where the last token taken ends.
+    fn previous_end(&self) -> usize {
+        self.pos.checked_sub(1).map_or(0, |pos| self.tokens[pos].1.end)
+    }
+
+    fn bump(&mut self) {
+        self.pos = (self.pos + 1).min(self.tokens.len());
+    }
+
+    fn keyword_at(&self, offset: usize, keyword: &str) -> bool {
+        matches!(self.peek_at(offset), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.keyword_at(0, keyword)
+    }
+
+    fn is_any_keyword(&self, keywords: &[&str]) -> bool {
+        keywords.iter().any(|keyword| self.is_keyword(keyword))
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek_at(0) == Some(&Token::Symbol(symbol))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn error(&self, expected: &str) -> Diagnostic {
+        let found = match self.peek_at(0) {
+            Some(token) => token.describe(),
+            None => "the end of the file".into(),
+        };
+        invalid(self.span(), format!("Expected {expected}. Found {found}."))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Diagnostic> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{keyword}'")))
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), Diagnostic> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{symbol}'")))
+        }
+    }
+
+    /// A name, of which only the last part is kept if it is qualified like `public.users`.
+    fn name(&mut self) -> Result<Spanned<String>, Diagnostic> {
+        loop {
+            let name = match self.peek_at(0) {
+                Some(Token::Word(name) | Token::Quoted(name)) => (name.clone(), self.span()),
+                _ => return Err(self.error("a name")),
+            };
+            self.bump();
+            if !self.eat_symbol('.') {
+                return Ok(name);
+            }
+        }
+    }
+
+    /// A parenthesized list of column names.
+    fn names(&mut self) -> Result<Vec<Spanned<String>>, Diagnostic> {
+        self.expect_symbol('(')?;
+        let mut names = Vec::new();
+        loop {
+            names.push(self.name()?);
+            // MySQL allows a prefix length, and every dialect a sort order.
+            if self.is_symbol('(') {
+                self.skip();
+            }
+            if !self.eat_keyword("ASC") {
+                self.eat_keyword("DESC");
+            }
+            if self.eat_symbol(')') {
+                return Ok(names);
+            }
+            self.expect_symbol(',')?;
+        }
+    }
+
+    /// Skips a token, or everything up to the matching `)` if it is a `(`.
+    fn skip(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.peek_at(0) {
+                None => return,
+                Some(Token::Symbol('(')) => depth += 1,
+                Some(Token::Symbol(')')) if depth > 0 => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Skips tokens until a `,` or `)` of the enclosing list, the end of the statement or one of
+    /// `until`, returning the span of what was skipped.
+    fn skip_until(&mut self, until: &[&str]) -> Span {
+        let start = self.span().start;
+        while !(self.peek_at(0).is_none()
+            || self.is_symbol(',')
+            || self.is_symbol(')')
+            || self.is_symbol(';')
+            || self.is_any_keyword(until))
+        {
+            self.skip();
+        }
+        start..self.previous_end().max(start)
+    }
+
+    fn skip_statement(&mut self) -> Span {
+        let start = self.span().start;
+        while !(self.peek_at(0).is_none() || self.is_symbol(';')) {
+            self.skip();
+        }
+        start..self.previous_end().max(start)
+    }
+
+    fn statements(&mut self) -> (Vec<Table>, Vec<Diagnostic>) {
+        let mut tables: Vec<Table> = Vec::new();
+        let mut errors = Vec::new();
+
+        while self.peek_at(0).is_some() {
+            if self.eat_symbol(';') {
+                continue;
+            }
+
+            let is_create_table = self.is_keyword("CREATE")
+                && (self.keyword_at(1, "TABLE")
+                    || (self.keyword_at(1, "TEMP") || self.keyword_at(1, "TEMPORARY")) && self.keyword_at(2, "TABLE"));
+            let result = if is_create_table {
+                self.create_table().map(|table| tables.push(table))
+            } else if self.is_keyword("ALTER") && self.keyword_at(1, "TABLE") {
+                self.alter_table(&mut tables)
+            } else {
+                let span = self.skip_statement();
+                self.warnings.push(unsupported(
+                    span,
+                    "Unsupported statement skipped.",
+                    "Only CREATE TABLE and ALTER TABLE ... ADD statements for keys are imported.".into(),
+                ));
+                Ok(())
+            };
+
+            match result {
+                Ok(()) if self.peek_at(0).is_none() || self.is_symbol(';') => {}
+                Ok(()) => errors.push(self.error("';'")),
+                Err(error) => errors.push(error),
+            }
+            self.skip_statement();
+        }
+
+        (tables, errors)
+    }
+
+    fn create_table(&mut self) -> Result<Table, Diagnostic> {
+        self.expect_keyword("CREATE")?;
+        if !self.eat_keyword("TEMP") {
+            self.eat_keyword("TEMPORARY");
+        }
+        self.expect_keyword("TABLE")?;
+        if self.eat_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+        }
+
+        let mut table = Table {
+            name: self.name()?,
+            columns: Vec::new(),
+            primary_key: Vec::new(),
+            unique: Vec::new(),
+            foreign_keys: Vec::new(),
+        };
+        self.expect_symbol('(')?;
+        loop {
+            if self.is_any_keyword(TABLE_CONSTRAINTS) {
+                if let Some(key) = self.table_constraint()? {
+                    table.add(key);
+                }
+            } else {
+                self.column(&mut table)?;
+            }
+
+            if self.eat_symbol(')') {
+                break;
+            }
+            self.expect_symbol(',')?;
+        }
+
+        let span = self.skip_statement();
+        if !span.is_empty() {
+            self.warnings.push(unsupported(
+                span,
+                "Table options skipped.",
+                "dberd doesn't say how tables are stored.".into(),
+            ));
+        }
+        Ok(table)
+    }
+
+    /// `ALTER TABLE name ADD key`, as written for foreign keys between tables that refer to each
+    /// other.
+    fn alter_table(&mut self, tables: &mut [Table]) -> Result<(), Diagnostic> {
+        let start = self.span().start;
+        self.expect_keyword("ALTER")?;
+        self.expect_keyword("TABLE")?;
+        if self.eat_keyword("IF") {
+            self.expect_keyword("EXISTS")?;
+        }
+        self.eat_keyword("ONLY");
+        let name = self.name()?;
+
+        if !(self.eat_keyword("ADD")
+            && (self.is_keyword("CONSTRAINT") || self.is_any_keyword(&["PRIMARY", "UNIQUE", "FOREIGN"])))
+        {
+            let span = self.skip_statement();
+            self.warnings.push(unsupported(
+                start..span.end,
+                "Unsupported statement skipped.",
+                "Only ALTER TABLE ... ADD statements for keys are imported.".into(),
+            ));
+            return Ok(());
+        }
+
+        let key = self.table_constraint()?;
+        match tables
+            .iter_mut()
+            .find(|table| table.name.0.eq_ignore_ascii_case(&name.0))
+        {
+            Some(table) => {
+                if let Some(key) = key {
+                    table.add(key);
+                }
+            }
+            None => self.warnings.push(unsupported(
+                name.1,
+                "Key on an unknown table skipped.",
+                format!("'{}' isn't created before this.", name.0),
+            )),
+        }
+        Ok(())
+    }
+
+    /// A key declared on its own, or `None` if it is a kind of constraint dberd can't express.
+    fn table_constraint(&mut self) -> Result<Option<Key>, Diagnostic> {
+        if self.eat_keyword("CONSTRAINT") {
+            self.name()?;
+        }
+
+        if self.eat_keyword("PRIMARY") {
+            self.expect_keyword("KEY")?;
+            let columns = self.names()?;
+            self.unsupported_clause();
+            Ok(Some(Key::Primary(columns)))
+        } else if self.eat_keyword("UNIQUE") {
+            if !self.eat_keyword("KEY") {
+                self.eat_keyword("INDEX");
+            }
+            if !self.is_symbol('(') {
+                self.name()?;
+            }
+            let columns = self.names()?;
+            self.unsupported_clause();
+            Ok(Some(Key::Unique(columns)))
+        } else if self.eat_keyword("FOREIGN") {
+            self.expect_keyword("KEY")?;
+            if !self.is_symbol('(') {
+                self.name()?;
+            }
+            let columns = self.names()?;
+            let table = self.references()?;
+            Ok(Some(Key::Foreign(ForeignKey { columns, table })))
+        } else if self.is_any_keyword(TABLE_CONSTRAINTS) {
+            let span = self.skip_until(&[]);
+            self.warnings.push(unsupported(
+                span,
+                "Unsupported table constraint skipped.",
+                "Only primary, unique and foreign keys are imported.".into(),
+            ));
+            Ok(None)
+        } else {
+            Err(self.error("a primary, unique or foreign key"))
+        }
+    }
+
+    /// Warns about anything after a key, like SQLite's `ON CONFLICT`.
+    fn unsupported_clause(&mut self) {
+        let span = self.skip_until(COLUMN_CONSTRAINTS);
+        if !span.is_empty() {
+            self.warnings.push(unsupported(
+                span,
+                "Unsupported clause skipped.",
+                "dberd only knows which columns a key is made of.".into(),
+            ));
+        }
+    }
+
+    /// `REFERENCES table (columns)` and what happens when the referenced row changes, returning
+    /// the referenced table.
+    fn references(&mut self) -> Result<Spanned<String>, Diagnostic> {
+        self.expect_keyword("REFERENCES")?;
+        let table = self.name()?;
+        if self.is_symbol('(') {
+            self.names()?;
+        }
+
+        let start = self.span().start;
+        loop {
+            if self.eat_keyword("ON") {
+                // `DELETE` or `UPDATE`, then `CASCADE`, `RESTRICT`, `SET NULL`, `NO ACTION`...
+                self.bump();
+                if self.is_keyword("SET") || self.is_keyword("NO") {
+                    self.bump();
+                }
+                self.bump();
+            } else if self.eat_keyword("MATCH") || self.eat_keyword("INITIALLY") {
+                self.bump();
+            } else if self.is_keyword("NOT") && self.keyword_at(1, "DEFERRABLE") {
+                self.bump();
+                self.bump();
+            } else if !self.eat_keyword("DEFERRABLE") {
+                break;
+            }
+        }
+        if self.previous_end() > start {
+            self.warnings.push(unsupported(
+                start..self.previous_end(),
+                "Referential actions skipped.",
+                "dberd doesn't say what happens when a referenced row changes.".into(),
+            ));
+        }
+
+        Ok(table)
+    }
+
+    /// A column definition, adding the keys declared with it to `table`.
+    fn column(&mut self, table: &mut Table) -> Result<(), Diagnostic> {
+        let name = self.name()?;
+        let ty = self.column_type()?;
+        let mut nullable = true;
+
+        loop {
+            if self.eat_keyword("CONSTRAINT") {
+                self.name()?;
+            }
+
+            if self.eat_keyword("PRIMARY") {
+                self.expect_keyword("KEY")?;
+                if !self.eat_keyword("ASC") {
+                    self.eat_keyword("DESC");
+                }
+                table.primary_key = vec![name.clone()];
+            } else if self.eat_keyword("NOT") {
+                self.expect_keyword("NULL")?;
+                nullable = false;
+            } else if self.eat_keyword("NULL") {
+                nullable = true;
+            } else if self.eat_keyword("UNIQUE") {
+                self.eat_keyword("KEY");
+                table.unique.push(vec![name.clone()]);
+            } else if self.is_keyword("REFERENCES") {
+                let referenced = self.references()?;
+                table.foreign_keys.push(ForeignKey {
+                    columns: vec![name.clone()],
+                    table: referenced,
+                });
+            } else if self.is_symbol(',') || self.is_symbol(')') || self.peek_at(0).is_none() {
+                break;
+            } else {
+                let start = self.span().start;
+                self.skip();
+                let span = self.skip_until(&["CONSTRAINT", "PRIMARY", "NOT", "NULL", "UNIQUE", "REFERENCES"]);
+                self.warnings.push(unsupported(
+                    start..span.end.max(self.previous_end()),
+                    "Unsupported column constraint skipped.",
+                    "Only keys and NOT NULL are imported.".into(),
+                ));
+            }
+        }
+
+        table.columns.push(SqlColumn { name, ty, nullable });
+        Ok(())
+    }
+
+    /// A type like `INTEGER` or `VARCHAR(255)`, mapped to the closest dberd type.
+    fn column_type(&mut self) -> Result<ColumnType, Diagnostic> {
+        let start = self.span().start;
+        let mut words = Vec::new();
+        let mut arguments = Vec::new();
+        loop {
+            match self.peek_at(0) {
+                Some(Token::Word(word)) if !self.is_any_keyword(COLUMN_CONSTRAINTS) => {
+                    words.push(word.to_ascii_lowercase());
+                    self.bump();
+                }
+                Some(Token::Symbol('(')) if !words.is_empty() => {
+                    self.bump();
+                    while let Some(Token::Literal(argument) | Token::Word(argument)) = self.peek_at(0) {
+                        arguments.push(argument.clone());
+                        self.bump();
+                        if !self.eat_symbol(',') {
+                            break;
+                        }
+                    }
+                    self.expect_symbol(')')?;
+                }
+                _ => break,
+            }
+        }
+        let span = start..self.previous_end().max(start);
+
+        let ty = match words.first().map(String::as_str) {
+            // MySQL's booleans are TINYINT(1).
+            Some("tinyint") if arguments == ["1"] => Some(ColumnType::Bool),
+            Some(
+                "int" | "integer" | "tinyint" | "smallint" | "mediumint" | "bigint" | "int2" | "int4" | "int8"
+                | "serial" | "smallserial" | "bigserial",
+            ) => Some(ColumnType::Int),
+            Some("real" | "float" | "float4" | "float8" | "double" | "decimal" | "numeric" | "dec") => {
+                Some(ColumnType::Float)
+            }
+            Some(
+                "char" | "character" | "varchar" | "nchar" | "nvarchar" | "varchar2" | "text" | "tinytext"
+                | "mediumtext" | "longtext" | "clob" | "citext" | "string",
+            ) => Some(ColumnType::String),
+            Some("bool" | "boolean" | "bit") => Some(ColumnType::Bool),
+            _ => None,
+        };
+
+        Ok(ty.unwrap_or_else(|| {
+            let label = if words.is_empty() {
+                "This column has no type, so it was imported as a string.".into()
+            } else {
+                format!("dberd has no '{}' type, so it was imported as a string.", words.join(" "))
+            };
+            self.warnings.push(unsupported(span, "Unsupported type replaced.", label));
+            ColumnType::String
+        }))
+    }
+}
+
+/// The dberd name for a SQL name, which may need to be changed to be a valid identifier.
+fn identifier((name, span): &Spanned<String>, warnings: &mut Vec<Diagnostic>) -> Spanned<String> {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !ident.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        ident.insert(0, 'x');
+    }
+    if matches!(ident.as_str(), "let" | "in" | "true" | "false") {
+        ident.push('_');
+    }
+
+    if ident != *name {
+        warnings.push(unsupported(
+            span.clone(),
+            "Name changed.",
+            format!("'{name}' isn't a valid dberd name, so it was imported as '{ident}'."),
+        ));
+    }
+    (ident, 0..0)
+}
+
+fn relation(from: &str, (min, max): (u64, Option<u64>), to: &str) -> Relation {
+    Relation {
+        from: (from.to_string(), 0..0),
+        cardinality: (Cardinality { min, max }, 0..0),
+        to: (to.to_string(), 0..0),
+        span: 0..0,
+    }
+}
+
+/// Turns tables into entities, and foreign keys into a relation each way.
+///
+/// Tables made of nothing but foreign keys to two tables are taken to be join tables, and become
+/// relations with many rows on both sides instead of an entity.
+fn module(tables: &[Table], warnings: &mut Vec<Diagnostic>) -> Module {
+    let names: Vec<String> = tables.iter().map(|table| identifier(&table.name, warnings).0).collect();
+    let entity = |name: &str| {
+        tables
+            .iter()
+            .position(|table| table.name.0.eq_ignore_ascii_case(name))
+    };
+
+    let mut module = Module::default();
+    for (table, name) in tables.iter().zip(&names) {
+        let is_join_table = table.foreign_keys.len() == 2
+            && table.foreign_keys.iter().all(|key| entity(&key.table.0).is_some())
+            && table.columns.iter().all(|column| {
+                table.foreign_keys.iter().any(|key| {
+                    key.columns
+                        .iter()
+                        .any(|(name, _)| name.eq_ignore_ascii_case(&column.name.0))
+                })
+            });
+        if is_join_table {
+            let [a, b] = [0, 1].map(|i| names[entity(&table.foreign_keys[i].table.0).unwrap()].as_str());
+            module.relations.push(relation(a, (0, None), b));
+            module.relations.push(relation(b, (0, None), a));
+            continue;
+        }
+
+        if table.primary_key.is_empty() {
+            warnings.push(unsupported(
+                table.name.1.clone(),
+                "Table without a primary key imported.",
+                format!("'{}' has no primary key, which every dberd entity needs.", table.name.0),
+            ));
+        }
+
+        // Foreign keys become relations, which add their columns back when the module is exported,
+        // so keeping the columns as well would add them twice. Columns of the primary key stay.
+        let references: Vec<&Spanned<String>> = table
+            .foreign_keys
+            .iter()
+            .filter(|key| entity(&key.table.0).is_some())
+            .flat_map(|key| &key.columns)
+            .filter(|(name, _)| !table.primary_key.iter().any(|(key, _)| key.eq_ignore_ascii_case(name)))
+            .collect();
+        let columns = table
+            .columns
+            .iter()
+            .filter(|column| !references.iter().any(|(name, _)| name.eq_ignore_ascii_case(&column.name.0)))
+            .map(|column| {
+                let mut constraints = Vec::new();
+                if table.primary_key.iter().any(|(key, _)| key.eq_ignore_ascii_case(&column.name.0)) {
+                    constraints.push((Constraint::PrimaryKey, 0..0));
+                } else if column.nullable {
+                    constraints.push((Constraint::Optional, 0..0));
+                }
+                if table
+                    .unique
+                    .iter()
+                    .any(|key| matches!(key.as_slice(), [(name, _)] if name.eq_ignore_ascii_case(&column.name.0)))
+                {
+                    constraints.push((Constraint::Unique, 0..0));
+                }
+                Column {
+                    name: identifier(&column.name, warnings),
+                    ty: (column.ty, 0..0),
+                    constraints,
+                    default: None,
+                    checks: Vec::new(),
+                    renamed_from: None,
+                }
+            })
+            .collect();
+        for columns in table.unique.iter().filter(|columns| columns.len() > 1) {
+            let span = columns[0].1.start..columns[columns.len() - 1].1.end;
+            warnings.push(unsupported(
+                span,
+                "Unique key skipped.",
+                "dberd can only mark single columns as unique.".into(),
+            ));
+        }
+        module.entities.push(Entity {
+            name: (name.clone(), 0..0),
+            renamed_from: None,
+            columns,
+            span: 0..0,
+        });
+
+        for key in &table.foreign_keys {
+            let Some(parent) = entity(&key.table.0) else {
+                warnings.push(unsupported(
+                    key.table.1.clone(),
+                    "Foreign key to an unknown table skipped.",
+                    format!("'{}' isn't created by this file.", key.table.0),
+                ));
+                continue;
+            };
+            let nullable = key
+                .columns
+                .iter()
+                .any(|(name, _)| table.column(name).is_some_and(|column| column.nullable));
+
+            let keys = &tables[parent].primary_key;
+            let generated = keys.iter().map(|key| {
+                format!("{}_{}", sql::snake_case(&names[parent]), identifier(key, &mut Vec::new()).0)
+            });
+            for ((column, span), generated) in key.columns.iter().zip(generated) {
+                if references.iter().any(|(name, _)| name == column) && *column != generated {
+                    warnings.push(unsupported(
+                        span.clone(),
+                        "Foreign key column renamed.",
+                        format!("'{column}' is part of a relation now, which names it '{generated}'."),
+                    ));
+                }
+            }
+
+            let children = if table.is_unique(&key.columns) { (0, Some(1)) } else { (0, None) };
+            let parents = if nullable { (0, Some(1)) } else { (1, Some(1)) };
+            module.relations.push(relation(&names[parent], children, name));
+            module.relations.push(relation(name, parents, &names[parent]));
+        }
+    }
+
+    module
+}
+
+/// Reads SQL DDL as a module of entities and relations, along with warnings about what couldn't
+/// be imported. The module is only usable if none of the diagnostics are errors.
+pub fn sql(source: &str) -> (Module, Vec<Diagnostic>) {
+    let tokens = match lex(source) {
+        Ok(tokens) => tokens,
+        Err(error) => return (Module::default(), vec![error]),
+    };
+
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: source.chars().count(),
+        warnings: Vec::new(),
+    };
+    let (tables, errors) = parser.statements();
+    let mut diagnostics = parser.warnings;
+    let module = module(&tables, &mut diagnostics);
+
+    diagnostics.extend(errors);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    (module, diagnostics)
+}
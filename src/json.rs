@@ -0,0 +1,77 @@
+//! Conversions between dberd values and JSON, for the `from_json` and `to_json` builtins and
+//! `dberd run --output json`.
+
+use num_bigint::BigInt;
+use serde_json::{Map, Number as JsonNumber, Value};
+
+use crate::{
+    lexer::Spanned,
+    number::Number,
+    parser::{Expr, LiteralValue},
+};
+
+/// Reads a JSON value. Objects become records. Integers up to `u64::MAX` stay exact, even those
+/// that don't fit in an `i64`, but larger ones are read as floats.
+pub fn from_json(value: &Value) -> Result<LiteralValue, String> {
+    let literal = |value| Ok((Expr::Literal(from_json(value)?), 0..0));
+    Ok(match value {
+        Value::Null => return Err("null has no dberd value".into()),
+        Value::Bool(boolean) => LiteralValue::Boolean(*boolean),
+        Value::String(string) => LiteralValue::String(string.clone()),
+        Value::Number(number) => match (number.as_i64(), number.as_u64(), number.as_f64()) {
+            (Some(integer), _, _) => LiteralValue::Number(Number::Integer(integer)),
+            (_, Some(integer), _) => LiteralValue::Number(Number::from(BigInt::from(integer))),
+            (_, _, Some(float)) => LiteralValue::Number(Number::Float(float)),
+            _ => return Err(format!("{number} is out of range")),
+        },
+        Value::Array(items) => LiteralValue::Array(items.iter().map(literal).collect::<Result<_, String>>()?),
+        Value::Object(fields) => LiteralValue::Record(
+            fields
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), literal(value)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+    })
+}
+
+/// Parses JSON text into a dberd value.
+pub fn parse(text: &str) -> Result<LiteralValue, String> {
+    let value: Value = serde_json::from_str(text).map_err(|error| format!("Invalid JSON: {error}"))?;
+    from_json(&value)
+}
+
+fn item_to_json((expr, _): &Spanned<Expr>) -> Result<Value, String> {
+    match expr {
+        Expr::Literal(value) => to_json(value),
+        _ => Err("Unevaluated array items have no JSON form".into()),
+    }
+}
+
+/// Writes a value as JSON. Functions have no JSON form, and neither do fractions or floats that
+/// aren't finite, since JSON can't hold them exactly.
+pub fn to_json(value: &LiteralValue) -> Result<Value, String> {
+    Ok(match value {
+        LiteralValue::Number(Number::Integer(integer)) => Value::from(*integer),
+        LiteralValue::Number(Number::Big(big)) => match u64::try_from(big) {
+            Ok(integer) => Value::from(integer),
+            Err(_) => return Err(format!("{big} is too big for JSON")),
+        },
+        LiteralValue::Number(number @ Number::Rational(_)) => {
+            return Err(format!("{number} has no exact JSON form; convert it with float first"))
+        }
+        LiteralValue::Number(Number::Float(float)) => match JsonNumber::from_f64(*float) {
+            Some(number) => Value::Number(number),
+            None => return Err(format!("{float} has no JSON form")),
+        },
+        LiteralValue::String(string) => Value::String(string.clone()),
+        LiteralValue::Boolean(boolean) => Value::Bool(*boolean),
+        LiteralValue::Array(items) => Value::Array(items.iter().map(item_to_json).collect::<Result<_, _>>()?),
+        LiteralValue::Record(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), item_to_json(value)?)))
+                .collect::<Result<Map<_, _>, String>>()?,
+        ),
+        LiteralValue::Function { .. } => return Err("Functions have no JSON form".into()),
+    })
+}
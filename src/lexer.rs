@@ -1,8 +1,11 @@
-use std::fmt::Display;
+use std::{fmt::Display, ops::Range};
 
-use chumsky::prelude::*;
+use chumsky::{error::Error, prelude::Simple};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub type Span = Range<usize>;
+pub type Spanned<T> = (T, Span);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Token {
     Equals,
 
@@ -18,56 +21,29 @@ pub enum Token {
     RightParen,
 
     Comma,
+    Dot,
 
     Arrow,
     Let,
     In,
 
-    Newline,
-    Space,
+    Comment,
 
     True,
     False,
 
-    Quote,
-
-    One,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Zero,
-
-    A,
-    B,
-    C,
-    D,
-    E,
-    F,
-    G,
-    H,
-    I,
-    J,
-    K,
-    L,
-    M,
-    N,
-    O,
-    P,
-    Q,
-    R,
-    S,
-    T,
-    U,
-    V,
-    W,
-    X,
-    Y,
-    Z,
+    Ampersand,
+    Pipe,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
+
+    /// A number literal as it was written, e.g. `0x1F` or `2.5e-3`. Its digits are checked by
+    /// the parser, so that errors can point at the exact character.
+    Number(String),
+    /// A string literal, with its escapes already replaced.
+    Str(String),
+    Ident(String),
 }
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -83,151 +59,250 @@ impl Display for Token {
             Token::LeftParen => "(",
             Token::RightParen => ")",
             Token::Comma => ",",
+            Token::Dot => ".",
             Token::Arrow => "->",
-            Token::Newline => "\\n",
+            Token::Comment => "#",
             Token::False => "false",
             Token::True => "true",
-            Token::Quote => "\"",
-            Token::One => "1",
-            Token::Two => "2",
-            Token::Three => "3",
-            Token::Four => "4",
-            Token::Five => "5",
-            Token::Six => "6",
-            Token::Seven => "7",
-            Token::Eight => "8",
-            Token::Nine => "9",
-            Token::Zero => "0",
-            Token::A => "a",
-            Token::B => "b",
-            Token::C => "c",
-            Token::D => "d",
-            Token::E => "e",
-            Token::F => "f",
-            Token::G => "g",
-            Token::H => "h",
-            Token::I => "i",
-            Token::J => "j",
-            Token::K => "k",
-            Token::L => "l",
-            Token::M => "m",
-            Token::N => "n",
-            Token::O => "o",
-            Token::P => "p",
-            Token::Q => "q",
-            Token::R => "r",
-            Token::S => "s",
-            Token::T => "t",
-            Token::U => "u",
-            Token::V => "v",
-            Token::W => "w",
-            Token::X => "x",
-            Token::Y => "y",
-            Token::Z => "z",
+            Token::Ampersand => "&",
+            Token::Pipe => "|",
+            Token::Caret => "^",
+            Token::ShiftLeft => "<<",
+            Token::ShiftRight => ">>",
+            Token::Number(number) => number,
+            Token::Str(string) => return write!(f, "{string:?}"),
+            Token::Ident(ident) => ident,
             Token::Let => "let",
             Token::In => "in",
-            Token::Space => " ",
         };
 
         write!(f, "{}", string)
     }
 }
 
-pub fn token() -> impl Parser<char, Token, Error = Simple<char>> {
-    let equals = just("=").to(Token::Equals);
-    let plus = just("+").to(Token::Plus);
-    let minus = just("-").to(Token::Minus);
-    let star = just("*").to(Token::Star);
-    let slash = just("/").to(Token::Slash);
-
-    let colon = just(":").to(Token::Colon);
-    let left_brace = just("{").to(Token::LeftBrace);
-    let right_brace = just("}").to(Token::RightBrace);
-    let left_paren = just("(").to(Token::LeftParen);
-    let right_paren = just(")").to(Token::RightParen);
-
-    let comma = just(",").to(Token::Comma);
-
-    let arrow = just("->").to(Token::Arrow);
-
-    let newline = just("\n").to(Token::Newline);
-    let space = just(" ").to(Token::Space);
-
-    let true_ = just("true").to(Token::True);
-    let false_ = just("false").to(Token::False);
-
-    let quote = just("\"").to(Token::Quote);
-
-    let number = filter(|c: &char| c.is_numeric()).map(|c: char| match c {
-        '1' => Token::One,
-        '2' => Token::Two,
-        '3' => Token::Three,
-        '4' => Token::Four,
-        '5' => Token::Five,
-        '6' => Token::Six,
-        '7' => Token::Seven,
-        '8' => Token::Eight,
-        '9' => Token::Nine,
-        '0' => Token::Zero,
-        _ => unreachable!(),
-    });
-
-    let letter = filter(|c: &char| c.is_alphabetic()).map(|c: char| match c {
-        'a' => Token::A,
-        'b' => Token::B,
-        'c' => Token::C,
-        'd' => Token::D,
-        'e' => Token::E,
-        'f' => Token::F,
-        'g' => Token::G,
-        'h' => Token::H,
-        'i' => Token::I,
-        'j' => Token::J,
-        'k' => Token::K,
-        'l' => Token::L,
-        'm' => Token::M,
-        'n' => Token::N,
-        'o' => Token::O,
-        'p' => Token::P,
-        'q' => Token::Q,
-        'r' => Token::R,
-        's' => Token::S,
-        't' => Token::T,
-        'u' => Token::U,
-        'v' => Token::V,
-        'w' => Token::W,
-        'x' => Token::X,
-        'y' => Token::Y,
-        'z' => Token::Z,
-        _ => unreachable!(),
-    });
-
-    let let_ = just("let").to(Token::Let);
-    let in_ = just("in").to(Token::In);
-
-    choice((
-        // Arrow must take priority over minus
-        arrow,
-        equals,
-        plus,
-        minus,
-        star,
-        slash,
-        colon,
-        left_brace,
-        right_brace,
-        left_paren,
-        right_paren,
-        comma,
-        newline,
-        space,
-        true_,
-        false_,
-        quote,
-        let_,
-        in_,
-        number,
-        letter,
-    ))
-    .padded_by(just(" ").repeated())
+/// The characters that can start a token, besides digits, letters and `"`.
+const SYMBOLS: &str = "=+-*/:{}(),.#&|^<>";
+const DIGITS: &str = "0123456789";
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const WHITESPACE: &str = " \t\r\n";
+
+/// Lexes a whole source file. Whitespace between tokens is skipped, but comments are kept as
+/// [`Token::Comment`] so that the formatter can put them back; the parser filters them out.
+///
+/// Everything outside of strings and comments is ASCII, so this works on bytes, but spans still
+/// count chars like everywhere else.
+pub fn lex(source: &str) -> Result<Vec<Spanned<Token>>, Simple<char>> {
+    let mut lexer = Lexer {
+        source,
+        bytes: source.as_bytes(),
+        offset: 0,
+        position: 0,
+    };
+    // Most tokens are a few bytes long, so this rarely needs to grow.
+    let mut tokens = Vec::with_capacity(source.len() / 4);
+
+    loop {
+        while matches!(lexer.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            lexer.advance(1);
+        }
+        let Some(byte) = lexer.peek() else {
+            return Ok(tokens);
+        };
+
+        let start = lexer.position;
+        let token = lexer.token(byte)?;
+        tokens.push((token, start..lexer.position));
+    }
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    bytes: &'a [u8],
+    /// Where the lexer is, in bytes.
+    offset: usize,
+    /// Where the lexer is, in chars.
+    position: usize,
+}
+
+impl Lexer<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.offset).copied()
+    }
+
+    fn peek_at(&self, ahead: usize) -> Option<u8> {
+        self.bytes.get(self.offset + ahead).copied()
+    }
+
+    /// Skips `count` ASCII bytes.
+    fn advance(&mut self, count: usize) {
+        self.offset += count;
+        self.position += count;
+    }
+
+    /// Skips bytes up to the next one that `stop` returns true for, which has to be ASCII.
+    fn skip_until(&mut self, stop: impl Fn(u8) -> bool) {
+        while let Some(byte) = self.peek() {
+            if stop(byte) {
+                break;
+            }
+            // Only the first byte of a UTF-8 sequence starts a char.
+            if byte & 0xC0 != 0x80 {
+                self.position += 1;
+            }
+            self.offset += 1;
+        }
+    }
+
+    fn take(&mut self, count: usize, token: Token) -> Token {
+        self.advance(count);
+        token
+    }
+
+    fn token(&mut self, byte: u8) -> Result<Token, Simple<char>> {
+        Ok(match byte {
+            b'-' if self.peek_at(1) == Some(b'>') => self.take(2, Token::Arrow),
+            b'=' => self.take(1, Token::Equals),
+            b'+' => self.take(1, Token::Plus),
+            b'-' => self.take(1, Token::Minus),
+            b'*' => self.take(1, Token::Star),
+            b'/' => self.take(1, Token::Slash),
+            b':' => self.take(1, Token::Colon),
+            b'{' => self.take(1, Token::LeftBrace),
+            b'}' => self.take(1, Token::RightBrace),
+            b'(' => self.take(1, Token::LeftParen),
+            b')' => self.take(1, Token::RightParen),
+            b',' => self.take(1, Token::Comma),
+            b'.' => self.take(1, Token::Dot),
+            b'&' => self.take(1, Token::Ampersand),
+            b'|' => self.take(1, Token::Pipe),
+            b'^' => self.take(1, Token::Caret),
+            b'<' | b'>' => {
+                self.advance(1);
+                if self.peek() != Some(byte) {
+                    return Err(self.unexpected([byte as char]));
+                }
+                self.take(1, if byte == b'<' { Token::ShiftLeft } else { Token::ShiftRight })
+            }
+            b'#' => {
+                self.skip_until(|byte| byte == b'\n');
+                Token::Comment
+            }
+            b'0'..=b'9' => self.number(),
+            b'"' => {
+                self.advance(1);
+                self.string().map_err(|error| error.with_label("a string literal"))?
+            }
+            b'a'..=b'z' | b'A'..=b'Z' => self.word(),
+            _ => {
+                let expected = [SYMBOLS, "\"", DIGITS, LETTERS, WHITESPACE].concat();
+                let expected = expected.chars().map(Some).chain([None]);
+                return Err(self.error(expected));
+            }
+        })
+    }
+
+    /// Everything that could belong to a number is taken, even if it isn't a valid digit, so that
+    /// `0b12` is one malformed literal rather than `0b1` followed by `2`. A sign is only part of
+    /// the literal right after an exponent.
+    fn number(&mut self) -> Token {
+        let start = self.offset;
+        self.advance(1);
+        loop {
+            match (self.peek(), self.peek_at(1), self.peek_at(2)) {
+                (Some(b'e' | b'E'), Some(b'+' | b'-'), Some(digit)) if digit.is_ascii_digit() => self.advance(3),
+                (Some(b'.'), Some(digit), _) if digit.is_ascii_digit() => self.advance(2),
+                (Some(byte), _, _) if byte.is_ascii_alphanumeric() || byte == b'_' => self.advance(1),
+                _ => break,
+            }
+        }
+        Token::Number(self.source[start..self.offset].to_string())
+    }
+
+    /// Keywords are only keywords as whole words, so `int` is an identifier rather than `in`
+    /// followed by `t`.
+    fn word(&mut self) -> Token {
+        let start = self.offset;
+        self.skip_until(|byte| !byte.is_ascii_alphanumeric() && byte != b'_');
+        match &self.source[start..self.offset] {
+            "let" => Token::Let,
+            "in" => Token::In,
+            "true" => Token::True,
+            "false" => Token::False,
+            word => Token::Ident(word.to_string()),
+        }
+    }
+
+    /// Lexes the rest of a string literal after its opening quote.
+    fn string(&mut self) -> Result<Token, Simple<char>> {
+        let mut string = String::new();
+        loop {
+            let start = self.offset;
+            self.skip_until(|byte| byte == b'"' || byte == b'\\');
+            string.push_str(&self.source[start..self.offset]);
+
+            match self.peek() {
+                Some(b'"') => return Ok(self.take(1, Token::Str(string))),
+                Some(_) => {
+                    self.advance(1);
+                    string.push(self.escape()?);
+                }
+                None => return Err(self.unexpected(['"', '\\'])),
+            }
+        }
+    }
+
+    /// Lexes an escape sequence after its backslash.
+    fn escape(&mut self) -> Result<char, Simple<char>> {
+        let escaped = match self.peek() {
+            Some(b'\\') => '\\',
+            Some(b'"') => '"',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'0') => '\0',
+            Some(b'u') => return self.unicode_escape(),
+            _ => return Err(self.unexpected(['\\', '"', 'n', 'r', 't', '0', 'u'])),
+        };
+        self.advance(1);
+        Ok(escaped)
+    }
+
+    /// Lexes an escape like `u{1F600}`, with one to six hex digits.
+    fn unicode_escape(&mut self) -> Result<char, Simple<char>> {
+        let start = self.position;
+        self.advance(1);
+        if self.peek() != Some(b'{') {
+            return Err(self.unexpected(['{']));
+        }
+        self.advance(1);
+
+        let digits = self.offset;
+        while self.offset - digits < 6 && self.peek().is_some_and(|byte| byte.is_ascii_hexdigit()) {
+            self.advance(1);
+        }
+        if self.offset == digits {
+            return Err(self.unexpected([]));
+        }
+        let hex = &self.source[digits..self.offset];
+        if self.peek() != Some(b'}') {
+            return Err(self.unexpected(['}']));
+        }
+        self.advance(1);
+
+        u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| Simple::custom(start..self.position, "Invalid unicode escape"))
+    }
+
+    fn unexpected<const N: usize>(&self, expected: [char; N]) -> Simple<char> {
+        self.error(expected.into_iter().map(Some))
+    }
+
+    /// An error for the char the lexer is at, or for the end of the file.
+    fn error(&self, expected: impl IntoIterator<Item = Option<char>>) -> Simple<char> {
+        let found = self.source[self.offset..].chars().next();
+        let end = self.position + usize::from(found.is_some());
+        Simple::expected_input_found(self.position..end, expected, found)
+    }
 }
@@ -0,0 +1,32 @@
+//! dberd, a small functional language for describing database schemas. The `dberd` binary is a
+//! command line interface over these modules.
+//!
+//! To evaluate code inside another program, e.g. a service running code it doesn't trust, lex and
+//! parse it with [`diagnostics::lex`] and [`diagnostics::parse`], check it with
+//! [`resolver::resolve`], then evaluate it with [`interpret_with`] and the [`Limits`] for that
+//! evaluation.
+
+pub mod ast;
+pub mod codes;
+pub mod dap;
+pub mod debugger;
+pub mod diagnostics;
+pub mod diff;
+pub mod enterpreter;
+pub mod export;
+pub mod formatter;
+pub mod import;
+pub mod json;
+pub mod lexer;
+pub mod lints;
+pub mod lsp;
+pub mod number;
+pub mod parser;
+pub mod resolver;
+pub mod schema;
+pub mod sql;
+pub mod test_runner;
+pub mod types;
+pub mod validate;
+
+pub use enterpreter::{interpret_with, EvalError, Limit, Limits};
@@ -0,0 +1,219 @@
+use crate::{
+    diagnostics::{Diagnostic, Label, Severity},
+    enterpreter::{self, Context},
+    lexer::Spanned,
+    parser::{Expr, LiteralValue, Module},
+    resolver::{BindingKind, Resolution},
+    schema,
+};
+
+/// A check for code that is valid, but probably not what was meant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Lint {
+    /// A `let` binding that is never used.
+    UnusedBinding,
+    /// A binding with the same name as one that is already in scope.
+    Shadowing,
+    /// A function bound with `let` that is used, but never called.
+    UncalledFunction,
+    /// A `check` that doesn't depend on any column, so it always passes or always fails.
+    ConstantCondition,
+}
+
+impl Lint {
+    pub fn code(self) -> &'static str {
+        match self {
+            Lint::UnusedBinding => "W0001",
+            Lint::Shadowing => "W0002",
+            Lint::UncalledFunction => "W0003",
+            Lint::ConstantCondition => "W0005",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedBinding => "unused-binding",
+            Lint::Shadowing => "shadowing",
+            Lint::UncalledFunction => "uncalled-function",
+            Lint::ConstantCondition => "constant-condition",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct LintOptions {
+    /// Don't report a lint at all.
+    #[arg(long, value_name = "LINT")]
+    pub allow: Vec<Lint>,
+    /// Report a lint as an error instead of a warning.
+    #[arg(long, value_name = "LINT")]
+    pub deny: Vec<Lint>,
+    /// Report every lint that isn't allowed as an error.
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+impl LintOptions {
+    /// How a lint should be reported, or `None` if it is allowed.
+    fn severity(&self, lint: Lint) -> Option<Severity> {
+        if self.allow.contains(&lint) {
+            None
+        } else if self.deny_warnings || self.deny.contains(&lint) {
+            Some(Severity::Error)
+        } else {
+            Some(Severity::Warning)
+        }
+    }
+}
+
+/// Counts how each binding is used.
+#[derive(Debug, Clone, Copy, Default)]
+struct Usage {
+    references: usize,
+    /// Whether the binding is called, or passed to a function which might call it.
+    called: bool,
+}
+
+fn collect_usage((expr, _): &Spanned<Expr>, resolution: &Resolution, usage: &mut [Usage]) {
+    match expr {
+        Expr::Literal(LiteralValue::Array(items)) => {
+            for item in items {
+                collect_usage(item, resolution, usage);
+            }
+        }
+        Expr::Literal(LiteralValue::Function { body, .. }) => collect_usage(body, resolution, usage),
+        Expr::Literal(_) => {}
+        Expr::Call { fun, args } => {
+            for (_, span) in [fun.as_ref()].into_iter().chain(args) {
+                if let Some(&binding) = resolution.references.get(span) {
+                    usage[binding].called = true;
+                }
+            }
+            collect_usage(fun, resolution, usage);
+            for arg in args {
+                collect_usage(arg, resolution, usage);
+            }
+        }
+        Expr::Ident(_) => {}
+        Expr::Let { value, body, .. } => {
+            collect_usage(value, resolution, usage);
+            collect_usage(body, resolution, usage);
+        }
+        Expr::Grouping(expr) => collect_usage(expr, resolution, usage),
+        Expr::Binary { lhs, rhs, .. } => {
+            collect_usage(lhs, resolution, usage);
+            collect_usage(rhs, resolution, usage);
+        }
+    }
+}
+
+fn is_function((expr, _): &Spanned<Expr>) -> bool {
+    match expr {
+        Expr::Literal(LiteralValue::Function { .. }) => true,
+        Expr::Grouping(expr) => is_function(expr),
+        _ => false,
+    }
+}
+
+/// Runs every lint that isn't allowed over a resolved module.
+pub fn lint(module: &Module, resolution: &Resolution, options: &LintOptions) -> Vec<Diagnostic> {
+    let mut usage = vec![Usage::default(); resolution.bindings.len()];
+    for &binding in resolution.references.values() {
+        usage[binding].references += 1;
+    }
+    let defaults_and_checks = module
+        .entities
+        .iter()
+        .flat_map(|entity| &entity.columns)
+        .flat_map(|column| column.default.iter().chain(&column.checks));
+    for expr in module.exprs().chain(defaults_and_checks) {
+        collect_usage(expr, resolution, &mut usage);
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut report = |lint: Lint, message: &str, labels: Vec<Label>| {
+        if let Some(severity) = options.severity(lint) {
+            diagnostics.push(Diagnostic {
+                severity,
+                code: lint.code(),
+                message: message.into(),
+                span: labels[0].span.clone(),
+                labels,
+                help: Some(format!("Pass '--allow {}' to silence this.", lint.name())),
+            });
+        }
+    };
+
+    for (i, binding) in resolution.bindings.iter().enumerate() {
+        let name = binding.name;
+
+        // Columns with the same name are duplicates, which the schema validation reports.
+        let shadowed = resolution.bindings[..i].iter().find(|earlier| {
+            !matches!(binding.kind, BindingKind::Column { .. })
+                && earlier.name == name
+                && earlier.scope.start <= binding.span.start
+                && binding.span.end <= earlier.scope.end
+        });
+        if let Some(shadowed) = shadowed {
+            report(
+                Lint::Shadowing,
+                "Shadowed identifier found.",
+                vec![
+                    Label {
+                        span: binding.span.clone(),
+                        message: format!("'{name}' shadows an earlier binding."),
+                    },
+                    Label {
+                        span: shadowed.span.clone(),
+                        message: format!("'{name}' was first bound here."),
+                    },
+                ],
+            );
+        }
+
+        let BindingKind::Let { value } = binding.kind else {
+            continue;
+        };
+        if usage[i].references == 0 {
+            report(
+                Lint::UnusedBinding,
+                "Unused binding found.",
+                vec![Label {
+                    span: binding.span.clone(),
+                    message: format!("'{name}' is never used."),
+                }],
+            );
+        } else if !usage[i].called && is_function(value) {
+            report(
+                Lint::UncalledFunction,
+                "Function is never called.",
+                vec![Label {
+                    span: binding.span.clone(),
+                    message: format!("'{name}' is used, but never called."),
+                }],
+            );
+        }
+    }
+
+    for entity in &module.entities {
+        for check in entity.columns.iter().flat_map(|column| &column.checks) {
+            if schema::uses_columns(entity, check) {
+                continue;
+            }
+            // Checks that fail to evaluate are already reported by the schema validation.
+            if let Ok(LiteralValue::Boolean(value)) = enterpreter::evaluate(check.clone(), &Context::default(), &schema::limits()) {
+                report(
+                    Lint::ConstantCondition,
+                    "Constant condition found.",
+                    vec![Label {
+                        span: check.1.clone(),
+                        message: format!("This check doesn't use any column, so it is always {value}."),
+                    }],
+                );
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
@@ -0,0 +1,508 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    diagnostics::{self, Diagnostic, Severity},
+    formatter,
+    lexer::{Span, Spanned},
+    lints::{self, LintOptions},
+    number::Number,
+    parser::{BinaryOp, Expr, Item, LiteralValue, Module, BUILTINS},
+    resolver::{self, BindingKind, Resolution},
+    schema::{Column, ColumnType},
+};
+
+const KEYWORDS: &[&str] = &["let", "in", "true", "false", "test", "entity", "relation", "was", "default", "check"];
+
+// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+const SYMBOL_FIELD: u8 = 8;
+const SYMBOL_FUNCTION: u8 = 12;
+const SYMBOL_VARIABLE: u8 = 13;
+const SYMBOL_STRUCT: u8 = 23;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+
+/// An open document and the result of parsing its latest contents.
+struct Document {
+    text: String,
+    module: Option<Module>,
+}
+
+/// Runs the language server on stdin and stdout until the client sends `exit`.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    serve(io::stdin().lock(), io::stdout().lock())
+}
+
+/// Serves requests read from `input`, writing responses and notifications to `output`, until the
+/// client sends `exit` or the input ends.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> Result<(), Box<dyn Error>> {
+    let mut documents = HashMap::new();
+
+    while let Some(content) = read_content(&mut input)? {
+        let message: Value = match serde_json::from_slice(&content) {
+            Ok(message) => message,
+            Err(error) => {
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": PARSE_ERROR, "message": format!("Invalid JSON: {error}") },
+                });
+                write_message(&mut output, &response)?;
+                continue;
+            }
+        };
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        if method == "exit" {
+            break;
+        }
+
+        let Some(id) = message.get("id") else {
+            if let Some(notification) = handle_notification(&mut documents, method, params) {
+                write_message(&mut output, &notification)?;
+            }
+            continue;
+        };
+
+        let response = match handle_request(&documents, method, params) {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": METHOD_NOT_FOUND, "message": format!("Unknown method '{method}'") },
+            }),
+        };
+        write_message(&mut output, &response)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a message framed with a `Content-Length` header, which the debug adapter uses too.
+/// Returns `None` once the input ends.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    match read_content(input)? {
+        Some(content) => Ok(Some(serde_json::from_slice(&content)?)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the content of a message framed with a `Content-Length` header, without parsing it.
+fn read_content(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header"));
+    };
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    Ok(Some(content))
+}
+
+/// Writes a message framed with a `Content-Length` header.
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    output.flush()
+}
+
+fn handle_notification(
+    documents: &mut HashMap<String, Document>,
+    method: &str,
+    params: &Value,
+) -> Option<Value> {
+    let uri = params["textDocument"]["uri"].as_str()?.to_string();
+
+    let text = match method {
+        "textDocument/didOpen" => params["textDocument"]["text"].as_str()?.to_string(),
+        "textDocument/didChange" => params["contentChanges"]
+            .as_array()?
+            .last()?["text"]
+            .as_str()?
+            .to_string(),
+        "textDocument/didClose" => {
+            documents.remove(&uri);
+            return Some(publish_diagnostics(&uri, Vec::new()));
+        }
+        _ => return None,
+    };
+
+    let (module, errors) = match diagnostics::lex(&text)
+        .and_then(|tokens| diagnostics::parse(&text, tokens))
+    {
+        Ok(module) => {
+            let resolution = resolver::resolve(&module);
+            let lints = lints::lint(&module, &resolution, &LintOptions::default());
+            let errors = resolution.diagnostics.into_iter().chain(lints).collect();
+            (Some(module), errors)
+        }
+        Err(errors) => (None, errors),
+    };
+    let diagnostics = errors.iter().map(|error| diagnostic(&text, error)).collect();
+    documents.insert(uri.clone(), Document { text, module });
+
+    Some(publish_diagnostics(&uri, diagnostics))
+}
+
+/// Answers a request, or returns `None` if the method isn't supported.
+fn handle_request(documents: &HashMap<String, Document>, method: &str, params: &Value) -> Option<Value> {
+    if method == "initialize" {
+        return Some(json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "completionProvider": {},
+                "documentSymbolProvider": true,
+            },
+            "serverInfo": { "name": "dberd", "version": env!("CARGO_PKG_VERSION") },
+        }));
+    }
+    if method == "shutdown" {
+        return Some(Value::Null);
+    }
+
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+    let document = documents.get(uri);
+    let offset = document.map(|document| offset(&document.text, &params["position"]));
+    let analysis = document.and_then(|document| {
+        let module = document.module.as_ref()?;
+        Some((&document.text, module, resolver::resolve(module)))
+    });
+
+    match method {
+        "textDocument/hover" => Some(
+            analysis
+                .and_then(|(text, module, resolution)| hover(text, module, &resolution, offset?))
+                .unwrap_or(Value::Null),
+        ),
+        "textDocument/definition" => Some(
+            analysis
+                .and_then(|(text, module, resolution)| {
+                    let offset = offset?;
+                    let (_, span) = module.exprs().find_map(|expr| ident_at(expr, offset))?;
+                    let span = resolution.binding(span)?.span;
+                    Some(json!({ "uri": uri, "range": range(text, span) }))
+                })
+                .unwrap_or(Value::Null),
+        ),
+        "textDocument/completion" => Some(Value::Array(completion(
+            analysis.and_then(|(_, _, resolution)| Some((resolution, offset?))),
+        ))),
+        "textDocument/documentSymbol" => Some(Value::Array(
+            analysis
+                .map(|(text, module, _)| module_symbols(text, module))
+                .unwrap_or_default(),
+        )),
+        _ => None,
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn diagnostic(text: &str, diagnostic: &Diagnostic) -> Value {
+    let mut message = diagnostic.message.clone();
+    for label in &diagnostic.labels {
+        message.push('\n');
+        message.push_str(&label.message);
+    }
+    if let Some(help) = &diagnostic.help {
+        message.push('\n');
+        message.push_str(help);
+    }
+
+    json!({
+        "range": range(text, &diagnostic.span),
+        "severity": match diagnostic.severity {
+            Severity::Error => SEVERITY_ERROR,
+            Severity::Warning => SEVERITY_WARNING,
+        },
+        "code": diagnostic.code,
+        "source": "dberd",
+        "message": message,
+    })
+}
+
+/// Converts a char offset into an LSP position, which counts columns in UTF-16 code units.
+fn position(text: &str, offset: usize) -> Value {
+    let mut line = 0;
+    let mut character = 0;
+    for c in text.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16();
+        }
+    }
+    json!({ "line": line, "character": character })
+}
+
+/// Converts an LSP position back into a char offset, clamping it to the end of its line.
+fn offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+
+    let mut offset = 0;
+    let mut current_line = 0;
+    let mut current_character = 0;
+    for c in text.chars() {
+        if current_line == line && (current_character >= character || c == '\n') {
+            break;
+        }
+        if c == '\n' {
+            current_line += 1;
+        } else if current_line == line {
+            current_character += c.len_utf16();
+        }
+        offset += 1;
+    }
+    offset
+}
+
+fn range(text: &str, span: &Span) -> Value {
+    json!({ "start": position(text, span.start), "end": position(text, span.end) })
+}
+
+fn contains(span: &Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+fn children(expr: &Expr) -> Vec<&Spanned<Expr>> {
+    match expr {
+        Expr::Literal(LiteralValue::Array(items)) => items.iter().collect(),
+        Expr::Literal(LiteralValue::Record(fields)) => fields.iter().map(|(_, value)| value).collect(),
+        Expr::Literal(LiteralValue::Function { params, body }) => {
+            params.iter().chain([body.as_ref()]).collect()
+        }
+        Expr::Literal(_) | Expr::Ident(_) => Vec::new(),
+        Expr::Call { fun, args } => [fun.as_ref()].into_iter().chain(args).collect(),
+        Expr::Let { ident, value, body } => vec![ident, value, body],
+        Expr::Grouping(expr) => vec![expr],
+        Expr::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+    }
+}
+
+/// Finds the innermost identifier at `offset`.
+fn ident_at((expr, span): &Spanned<Expr>, offset: usize) -> Option<(&str, &Span)> {
+    if let Expr::Ident(name) = expr {
+        return contains(span, offset).then_some((name, span));
+    }
+    children(expr)
+        .into_iter()
+        .filter(|child| contains(&child.1, offset))
+        .find_map(|child| ident_at(child, offset))
+}
+
+/// The name [`type_of`] gives the values of `column`.
+fn column_type(column: &Column) -> &'static str {
+    match column.ty.0 {
+        ColumnType::Int => "int",
+        ColumnType::Float => "float",
+        ColumnType::String => "string",
+        ColumnType::Bool => "boolean",
+    }
+}
+
+/// Infers the type of an expression from what its identifiers are bound to, if it can be known
+/// without running it.
+fn type_of(expr: &Spanned<Expr>, resolution: &Resolution) -> Option<&'static str> {
+    match &expr.0 {
+        Expr::Literal(literal) => Some(match literal {
+            LiteralValue::Number(Number::Integer(_) | Number::Big(_)) => "int",
+            LiteralValue::Number(Number::Rational(_)) => "rational",
+            LiteralValue::Number(Number::Float(_)) => "float",
+            LiteralValue::String(_) => "string",
+            LiteralValue::Boolean(_) => "boolean",
+            LiteralValue::Array(_) => "array",
+            LiteralValue::Record(_) => "record",
+            LiteralValue::Function { .. } => "function",
+        }),
+        Expr::Binary { op, lhs, rhs } => match (op, type_of(lhs, resolution), type_of(rhs, resolution)) {
+            (BinaryOp::Add, Some("int"), Some("int")) => Some("int"),
+            (BinaryOp::Add | BinaryOp::Divide, Some("float"), _)
+            | (BinaryOp::Add | BinaryOp::Divide, _, Some("float")) => Some("float"),
+            (BinaryOp::Add | BinaryOp::Divide, _, _) => Some("number"),
+            // The bitwise operators only ever give integers.
+            _ => Some("int"),
+        },
+        Expr::Grouping(expr) => type_of(expr, resolution),
+        Expr::Let { body, .. } => type_of(body, resolution),
+        Expr::Ident(_) => match resolution.binding(&expr.1)?.kind {
+            BindingKind::Let { value } => type_of(value, resolution),
+            BindingKind::Parameter => None,
+            BindingKind::Column { column } => Some(column_type(column)),
+        },
+        Expr::Call { fun, .. } => {
+            let mut fun = fun.as_ref();
+            loop {
+                match &fun.0 {
+                    Expr::Literal(LiteralValue::Function { body, .. }) => {
+                        return type_of(body, resolution)
+                    }
+                    Expr::Grouping(expr) => fun = expr,
+                    Expr::Ident(_) => match resolution.binding(&fun.1)?.kind {
+                        BindingKind::Let { value } => fun = value,
+                        BindingKind::Parameter | BindingKind::Column { .. } => return None,
+                    },
+                    _ => return None,
+                }
+            }
+        }
+    }
+}
+
+fn hover(text: &str, module: &Module, resolution: &Resolution, offset: usize) -> Option<Value> {
+    let (name, span) = module.exprs().find_map(|expr| ident_at(expr, offset))?;
+
+    let contents = match resolution.binding(span).map(|binding| binding.kind) {
+        Some(BindingKind::Let { value }) => format!(
+            "```dberd\n{name}: {}\n```\n```dberd\nlet {name} = {}\n```",
+            type_of(value, resolution).unwrap_or("unknown"),
+            formatter::format_expr(value, 60)
+        ),
+        Some(BindingKind::Parameter) => format!("```dberd\n{name}: unknown\n```\nFunction parameter"),
+        Some(BindingKind::Column { column }) => format!("```dberd\n{name}: {}\n```\nColumn", column_type(column)),
+        None if BUILTINS.contains(&name) => format!("```dberd\n{name}: builtin\n```"),
+        None => format!("`{name}` is not defined"),
+    };
+
+    Some(json!({
+        "contents": { "kind": "markdown", "value": contents },
+        "range": range(text, span),
+    }))
+}
+
+fn completion(analysis: Option<(Resolution, usize)>) -> Vec<Value> {
+    let mut items = Vec::new();
+
+    if let Some((resolution, offset)) = analysis {
+        let mut seen = Vec::new();
+        for binding in resolution.visible_at(offset).rev() {
+            if seen.contains(&binding.name) {
+                continue;
+            }
+            seen.push(binding.name);
+
+            let ty = match binding.kind {
+                BindingKind::Let { value } => type_of(value, &resolution),
+                BindingKind::Parameter => None,
+                BindingKind::Column { column } => Some(column_type(column)),
+            };
+            items.push(json!({
+                "label": binding.name,
+                "kind": if ty == Some("function") { COMPLETION_FUNCTION } else { COMPLETION_VARIABLE },
+                "detail": ty.unwrap_or("unknown"),
+            }));
+        }
+    }
+
+    items.extend(BUILTINS.iter().map(|builtin| {
+        json!({ "label": builtin, "kind": COMPLETION_FUNCTION, "detail": "builtin" })
+    }));
+    items.extend(KEYWORDS.iter().map(|keyword| {
+        json!({ "label": keyword, "kind": COMPLETION_KEYWORD })
+    }));
+
+    items
+}
+
+/// Lists every test, entity and the `let` bindings in the module, in source order.
+fn module_symbols(text: &str, module: &Module) -> Vec<Value> {
+    let mut symbols = Vec::new();
+    for item in module.items() {
+        match item {
+            Item::Expr(expr) => document_symbols(text, expr, &mut symbols),
+            Item::Test(test) => {
+                let mut children = Vec::new();
+                document_symbols(text, &test.body, &mut children);
+                symbols.push(json!({
+                    "name": format!("test {:?}", test.name.0),
+                    "kind": SYMBOL_FUNCTION,
+                    "range": range(text, &(test.name.1.start..test.body.1.end)),
+                    "selectionRange": range(text, &test.name.1),
+                    "children": children,
+                }));
+            }
+            Item::Entity(entity) => {
+                let children: Vec<_> = entity
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        json!({
+                            "name": column.name.0,
+                            "detail": column.ty.0.to_string(),
+                            "kind": SYMBOL_FIELD,
+                            "range": range(text, &(column.name.1.start..column.ty.1.end)),
+                            "selectionRange": range(text, &column.name.1),
+                        })
+                    })
+                    .collect();
+                symbols.push(json!({
+                    "name": entity.name.0,
+                    "kind": SYMBOL_STRUCT,
+                    "range": range(text, &entity.span),
+                    "selectionRange": range(text, &entity.name.1),
+                    "children": children,
+                }));
+            }
+            Item::Relation(_) => {}
+        }
+    }
+    symbols
+}
+
+/// Lists every `let` binding, nesting the bindings made inside of a binding's value under it.
+fn document_symbols(text: &str, (expr, span): &Spanned<Expr>, symbols: &mut Vec<Value>) {
+    if let Expr::Let { ident, value, body } = expr {
+        if let (Expr::Ident(name), ident_span) = ident.as_ref() {
+            let mut children = Vec::new();
+            document_symbols(text, value, &mut children);
+
+            let is_function = matches!(value.0, Expr::Literal(LiteralValue::Function { .. }));
+            symbols.push(json!({
+                "name": name,
+                "kind": if is_function { SYMBOL_FUNCTION } else { SYMBOL_VARIABLE },
+                "range": range(text, span),
+                "selectionRange": range(text, ident_span),
+                "children": children,
+            }));
+        }
+        document_symbols(text, body, symbols);
+        return;
+    }
+
+    for child in children(expr) {
+        document_symbols(text, child, symbols);
+    }
+}
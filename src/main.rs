@@ -1,178 +1,501 @@
-use std::error::Error;
+use std::{error::Error, process::ExitCode};
 
-use ariadne::{sources, ColorGenerator, Label, Report, Span};
-use chumsky::{primitive::end, Parser};
-use enterpreter::interpret;
-use parser::expr;
-
-mod lexer;
-mod parser;
-pub mod enterpreter;
+use dberd::{
+    ast, codes, dap, debugger, diagnostics, diff, export, formatter, import, json, lints, lsp, resolver, sql,
+    test_runner, validate,
+};
+use dberd::{
+    diagnostics::{MessageFormat, Reported, Severity},
+    enterpreter::{self, interpret_with, EvalError, Limits},
+    lexer::{Spanned, Token},
+    parser::{LiteralValue, Module},
+};
 
 #[derive(clap::Parser)]
-enum Cli {
-    Tokenize { source: String },
+struct Cli {
+    /// How to print diagnostics.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    message_format: MessageFormat,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// How `dberd run` prints the value a file evaluates to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Output {
+    /// As dberd source.
+    #[default]
+    Human,
+    /// As JSON, for other tools to read. Fails for values that JSON can't hold, like functions.
+    Json,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = <Cli as clap::Parser>::parse();
+/// What `dberd parse` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Emit {
+    /// The syntax tree as JSON, in the format documented in `src/ast.rs`.
+    AstJson,
+}
+
+/// What kind of file `dberd run` reads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Input {
+    /// dberd source.
+    #[default]
+    Source,
+    /// A syntax tree written by `dberd parse --emit ast-json`.
+    AstJson,
+}
 
-    match args {
-        Cli::Tokenize { source } => {
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Print the tokens of a source file, then check and evaluate it like `run`.
+    Tokenize {
+        source: String,
+        /// Print every step of the evaluation to stderr.
+        #[arg(long)]
+        trace: bool,
+        #[command(flatten)]
+        limits: Limits,
+    },
+    /// Evaluate a source file and print the value it evaluates to.
+    Run {
+        source: String,
+        #[arg(long, value_enum, default_value_t)]
+        input: Input,
+        #[arg(long, value_enum, default_value_t)]
+        output: Output,
+        #[command(flatten)]
+        limits: Limits,
+    },
+    /// Parse a source file and print its syntax tree.
+    Parse {
+        source: String,
+        #[arg(long, value_enum)]
+        emit: Emit,
+    },
+    /// Rewrite source files in the canonical dberd style.
+    Fmt {
+        #[arg(required = true)]
+        sources: Vec<String>,
+        /// Don't write anything, just fail if any file isn't already formatted.
+        #[arg(long)]
+        check: bool,
+        /// The line width to break long expressions at.
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+    },
+    /// Run the language server over stdin and stdout.
+    Lsp,
+    /// Run the debug adapter over stdin and stdout.
+    Dap,
+    /// Check source files for code that is valid, but probably a mistake.
+    Lint {
+        #[arg(required = true)]
+        sources: Vec<String>,
+        #[command(flatten)]
+        options: lints::LintOptions,
+    },
+    /// Run the tests declared in source files, or in every `.dberd` file under a directory.
+    Test {
+        #[arg(default_value = ".")]
+        sources: Vec<String>,
+        /// Only run the tests whose name contains this.
+        #[arg(long)]
+        filter: Option<String>,
+        #[command(flatten)]
+        limits: Limits,
+    },
+    /// Evaluate a source file in an interactive step debugger.
+    Debug {
+        source: String,
+        /// Run until this line is reached instead of stopping at the start. Can be repeated.
+        #[arg(long = "break", short, value_name = "LINE")]
+        breakpoints: Vec<usize>,
+        #[command(flatten)]
+        limits: Limits,
+    },
+    /// Render the entities and relations declared in a source file as a diagram.
+    Export {
+        source: String,
+        #[arg(long, value_enum)]
+        format: export::Format,
+        /// The SQL dialect to write with `--format sql`.
+        #[arg(long, value_enum, default_value_t)]
+        dialect: sql::Dialect,
+    },
+    /// Compare two versions of a schema, printing what changed or SQL that migrates between them.
+    Diff {
+        old: String,
+        new: String,
+        /// Print the statements that migrate a database from the old schema to the new one.
+        #[arg(long)]
+        sql: bool,
+        /// The SQL dialect to write with `--sql`.
+        #[arg(long, value_enum, default_value_t)]
+        dialect: sql::Dialect,
+        /// Only treat entities and columns annotated with `was` as renamed.
+        #[arg(long)]
+        no_guess: bool,
+    },
+    /// Convert a schema from another format into dberd source, printed to stdout.
+    Import {
+        #[arg(value_enum)]
+        from: import::Format,
+        source: String,
+        /// The line width to break long entities at.
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+    },
+    /// Check sample data against a schema: one JSON or CSV file per table, named after it.
+    Validate {
+        schema: String,
+        /// Data files, or directories to search for `.json` and `.csv` files.
+        #[arg(required = true)]
+        data: Vec<String>,
+    },
+    /// Explain a diagnostic code in detail, or list them all.
+    Explain {
+        /// A code like E0005.
+        code: Option<String>,
+    },
+}
+
+fn main() -> ExitCode {
+    let Cli { message_format: format, command } = <Cli as clap::Parser>::parse();
+
+    // Every command runs on the one thread evaluations need, rather than starting one for each
+    // default, check and row it evaluates.
+    enterpreter::on_stack(|| match run(format, command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            // JSON diagnostics already said what went wrong, and a summary would not be JSON.
+            if !(format == MessageFormat::Json && error.is::<Reported>()) {
+                diagnostics::emit_error(format, None, None, &error.to_string());
+            }
+            ExitCode::FAILURE
+        }
+    })
+}
+
+fn run(format: MessageFormat, command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Tokenize {
+            source,
+            trace,
+            limits,
+        } => {
             let source_text = std::fs::read_to_string(source.clone())?;
 
             let source = Box::leak(Box::new(source));
-            let tokens = match lexer::token()
-                .repeated()
-                .then_ignore(end())
-                .parse(source_text.clone())
-            {
-                Ok(tokens) => tokens,
-                Err(errors) => {
-                    for error in errors {
-                        let mut colors = ColorGenerator::new();
-
-                        let mut report = Report::build(
-                            ariadne::ReportKind::Error,
-                            source.as_str(),
-                            error.span().start(),
-                        )
-                        .with_code("E0001")
-                        .with_message("Failed to lex.");
-                        match error.reason() {
-                            chumsky::error::SimpleReason::Unexpected => {
-                                let expected_len = error.expected().len();
-                                let expected_label = format!(
-                                    "Expected one of: {}. Found: {}.",
-                                    error
-                                        .expected()
-                                        .enumerate()
-                                        .map(|(i, c)| {
-                                            if let Some(c) = c {
-                                                let mut entry = format!("{c}");
-                                                if i < expected_len - 1 {
-                                                    entry.push_str(", ")
-                                                }
-                                                entry
-                                            } else {
-                                                String::new()
-                                            }
-                                        })
-                                        .collect::<String>(),
-                                    error
-                                        .found()
-                                        .map(|c| format!("'{c}'"))
-                                        .unwrap_or_else(|| String::from("this"))
-                                );
-
-                                report = report.with_label(
-                                    Label::new((source.as_str(), error.span()))
-                                        .with_message(expected_label)
-                                        .with_color(colors.next()),
-                                );
-                            }
-                            chumsky::error::SimpleReason::Unclosed {
-                                span: _,
-                                delimiter: _,
-                            } => unreachable!("Delimiters are not lexed"),
-                            chumsky::error::SimpleReason::Custom(_) => {
-                                unreachable!("Lexer doesn't produce custom errors")
-                            }
-                        }
-
-                        report
-                            .finish()
-                            .eprint(sources(vec![(source.as_str(), source_text.as_str())]))?;
-                    }
-                    return Err("Failed to lex".into());
+            let tokens = lex(format, source, &source_text)?;
+
+            println!("{tokens:?}");
+
+            let module = parse(format, source, &source_text, tokens)?;
+            resolve(format, source, Some(&source_text), &module)?;
+            println!("{:?}", module);
+
+            let expr = module.expr.ok_or("There is nothing to evaluate")?;
+            let value = if trace {
+                interpret_with(expr, &mut debugger::Tracer::new(&source_text), &limits)
+            } else {
+                interpret_with(expr, &mut (), &limits)
+            };
+            print_value(format, source, Some(&source_text), value)?;
+        }
+        Command::Run {
+            source,
+            input,
+            output,
+            limits,
+        } => {
+            let source_text = std::fs::read_to_string(source.clone())?;
+
+            let source = Box::leak(Box::new(source));
+            let module = match input {
+                Input::Source => {
+                    let tokens = lex(format, source, &source_text)?;
+                    parse(format, source, &source_text, tokens)?
                 }
+                Input::AstJson => load_ast(format, source, &source_text)?,
+            };
+            // The spans of a loaded syntax tree point into a source file we don't have.
+            let source_text = match input {
+                Input::Source => Some(source_text.as_str()),
+                Input::AstJson => None,
             };
+            resolve(format, source, source_text, &module)?;
 
-            println!("{tokens:?}");
+            let expr = module.expr.ok_or("There is nothing to evaluate")?;
+            let span = expr.1.clone();
+            match (output, interpret_with(expr, &mut (), &limits)) {
+                (Output::Json, Ok(value)) => match json::to_json(&value) {
+                    Ok(json) => println!("{json}"),
+                    // The value has no JSON form as a whole, so the error is about the whole file.
+                    Err(message) => print_value(format, source, source_text, Err(EvalError::from(message).at(&span)))?,
+                },
+                (_, value) => print_value(format, source, source_text, value)?,
+            }
+        }
+        Command::Parse { source, emit: Emit::AstJson } => {
+            let source_text = std::fs::read_to_string(source.clone())?;
 
-            let expr = match expr().then_ignore(end()).parse(tokens) {
-                Ok(tokens) => tokens,
-                Err(errors) => {
-                    for error in errors {
-                        let mut colors = ColorGenerator::new();
-
-                        let mut report = Report::build(
-                            ariadne::ReportKind::Error,
-                            source.as_str(),
-                            error.span().start(),
-                        );
-
-                        match error.reason() {
-                            chumsky::error::SimpleReason::Unexpected => {
-                                let expected_len = error.expected().len();
-                                let expected_label = format!(
-                                    "Expected one of: {}. Found: {}.",
-                                    error
-                                        .expected()
-                                        .enumerate()
-                                        .map(|(i, c)| {
-                                            if let Some(c) = c {
-                                                let mut entry = format!("{c}");
-                                                if i < expected_len - 1 {
-                                                    entry.push_str(", ")
-                                                }
-                                                entry
-                                            } else {
-                                                String::new()
-                                            }
-                                        })
-                                        .collect::<String>(),
-                                    error
-                                        .found()
-                                        .map(|c| format!("'{c}'"))
-                                        .unwrap_or_else(|| String::from("this"))
-                                );
-
-                                report = report
-                                    .with_code("E0002")
-                                    .with_message("Unexpected character found.")
-                                    .with_label(
-                                        Label::new((source.as_str(), error.span()))
-                                            .with_message(expected_label)
-                                            .with_color(colors.next()),
-                                    );
-                            }
-                            chumsky::error::SimpleReason::Unclosed { span, delimiter } => {
-                                report = report
-                                    .with_code("E0003")
-                                    .with_message("Unclosed delimiter found.")
-                                    .with_label(
-                                        Label::new((source.as_str(), span.clone()))
-                                            .with_color(colors.next())
-                                            .with_message("Unclosed delimiter started here!"),
-                                    )
-                                    .with_label(
-                                        Label::new((source.as_str(), error.span()))
-                                            .with_color(colors.next())
-                                            .with_order(1)
-                                            .with_message(format!(
-                                                "Expected a closing '{delimiter}' in this code."
-                                            )),
-                                    );
-                            }
-                            chumsky::error::SimpleReason::Custom(message) => {
-                                report = report.with_code("E0004")
-                                .with_message(message)
-                                .with_label(Label::new((source.as_str(), error.span())).with_color(colors.next()).with_message(error.label().unwrap_or_else(|| "Error occured here")))
-                            }
-                        }
-                        report
-                            .finish()
-                            .eprint(sources(vec![(source.as_str(), source_text.as_str())]))?;
+            let source = Box::leak(Box::new(source));
+            let tokens = lex(format, source, &source_text)?;
+            let module = parse(format, source, &source_text, tokens)?;
+            println!("{}", serde_json::to_string_pretty(&ast::to_json(&module))?);
+        }
+        Command::Fmt {
+            sources,
+            check,
+            width,
+        } => {
+            let mut unformatted = 0;
+
+            for source in sources {
+                let source_text = std::fs::read_to_string(source.clone())?;
+
+                let source = Box::leak(Box::new(source));
+                let tokens = lex(format, source, &source_text)?;
+                let module = parse(format, source, &source_text, tokens.clone())?;
+
+                let formatted = formatter::format(&source_text, &tokens, &module, width);
+                if formatted == source_text {
+                    continue;
+                }
+
+                if check {
+                    diagnostics::emit_error(format, Some(source), None, &format!("{source} is not formatted"));
+                    unformatted += 1;
+                } else {
+                    std::fs::write(source.as_str(), formatted)?;
+                }
+            }
+
+            if unformatted > 0 {
+                return Err(Reported(format!("{unformatted} file(s) need formatting")).into());
+            }
+        }
+        Command::Lsp => lsp::run()?,
+        Command::Dap => dap::run()?,
+        Command::Lint { sources, options } => {
+            let mut errors = 0;
+
+            for source in sources {
+                let source_text = std::fs::read_to_string(source.clone())?;
+
+                let source = Box::leak(Box::new(source));
+                let tokens = lex(format, source, &source_text)?;
+                let module = parse(format, source, &source_text, tokens)?;
+
+                let resolution = resolver::resolve(&module);
+                let lints = lints::lint(&module, &resolution, &options);
+                for diagnostic in resolution.diagnostics.iter().chain(&lints) {
+                    if diagnostic.severity == Severity::Error {
+                        errors += 1;
                     }
-                    return Err("Failed to parse".into());
+                    diagnostic.emit(format, source, &source_text)?;
                 }
+            }
+
+            if errors > 0 {
+                return Err(Reported(format!("{errors} error(s) found")).into());
+            }
+        }
+        Command::Test {
+            sources,
+            filter,
+            limits,
+        } => test_runner::run(&sources, filter.as_deref(), &limits, format)?,
+        Command::Debug {
+            source,
+            breakpoints,
+            limits,
+        } => {
+            let source_text = std::fs::read_to_string(source.clone())?;
+
+            let source = Box::leak(Box::new(source));
+            let tokens = lex(format, source, &source_text)?;
+            let module = parse(format, source, &source_text, tokens)?;
+            resolve(format, source, Some(&source_text), &module)?;
+            let expr = module.expr.ok_or("There is nothing to evaluate")?;
+
+            let mut debugger = debugger::Debugger::new(source, &source_text, breakpoints);
+            match interpret_with(expr, &mut debugger, &limits) {
+                // Quitting the debugger isn't a failure of the program.
+                Err(EvalError::Stopped) => {}
+                value => print_value(format, source, Some(&source_text), value)?,
+            }
+        }
+        Command::Export {
+            source,
+            format: export_format,
+            dialect,
+        } => {
+            let module = schema(format, source)?;
+            print!("{}", export::export(&module, export_format, dialect));
+        }
+        Command::Diff {
+            old,
+            new,
+            sql,
+            dialect,
+            no_guess,
+        } => {
+            let old = schema(format, old)?;
+            let new = schema(format, new)?;
+            let renames = diff::renames(&old, &new, !no_guess)?;
+
+            if sql {
+                print!("{}", sql::migration(&old, &new, &renames, dialect));
+            } else {
+                print!("{}", diff::summary(&old, &new, &renames));
+            }
+        }
+        Command::Import { from, source, width } => {
+            let source_text = std::fs::read_to_string(source.clone())?;
+
+            let source = Box::leak(Box::new(source));
+            let (module, diagnostics) = match from {
+                import::Format::Sql => import::sql(&source_text),
             };
-            println!("{:?}", expr);
 
-            println!("{:?}", interpret(expr).unwrap());
+            let mut errors = 0;
+            for diagnostic in &diagnostics {
+                if diagnostic.severity == Severity::Error {
+                    errors += 1;
+                }
+                diagnostic.emit(format, source, &source_text)?;
+            }
+            if errors > 0 {
+                return Err(Reported(format!("{errors} error(s) found")).into());
+            }
+
+            print!("{}", formatter::format_module(&module, width));
+        }
+        Command::Validate { schema: source, data } => {
+            let module = schema(format, source)?;
+            let violations = validate::validate(&module, &data)?;
+            for violation in &violations {
+                violation.emit(format);
+            }
+            if !violations.is_empty() {
+                return Err(Reported(format!("{} violation(s) found", violations.len())).into());
+            }
+        }
+        Command::Explain { code: Some(code) } => {
+            let explanation = codes::explanation(&code)
+                .ok_or_else(|| format!("'{code}' is not a dberd diagnostic code"))?;
+            print!("{}", explanation.render());
+        }
+        Command::Explain { code: None } => {
+            for explanation in codes::EXPLANATIONS {
+                println!("{}: {}", explanation.code, explanation.title);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints a diagnostic, with the source it points into if we have it.
+fn emit(
+    diagnostic: &diagnostics::Diagnostic,
+    format: MessageFormat,
+    source: &'static str,
+    source_text: Option<&str>,
+) -> std::io::Result<()> {
+    match source_text {
+        Some(source_text) => diagnostic.emit(format, source, source_text),
+        None => {
+            diagnostic.emit_detached(format, source);
+            Ok(())
         }
     }
+}
 
+fn print_value(
+    format: MessageFormat,
+    source: &'static str,
+    source_text: Option<&str>,
+    value: Result<LiteralValue, EvalError>,
+) -> Result<(), Box<dyn Error>> {
+    let file = source_text.map(|source_text| (source as &str, source_text));
+    match value {
+        Ok(value) => println!("{}", formatter::format_value(&value, 80, file)),
+        Err(error) => {
+            match error.diagnostic() {
+                Some(diagnostic) => emit(&diagnostic, format, source, source_text)?,
+                None => diagnostics::emit_error(format, Some(source), error.code(), &error.to_string()),
+            }
+            return Err(Reported("Failed to evaluate".into()).into());
+        }
+    }
     Ok(())
 }
+
+fn lex(format: MessageFormat, source: &'static str, source_text: &str) -> Result<Vec<Spanned<Token>>, Box<dyn Error>> {
+    diagnostics::lex(source_text).or_else(|diagnostics| {
+        for diagnostic in diagnostics {
+            diagnostic.emit(format, source, source_text)?;
+        }
+        Err(Reported("Failed to lex".into()).into())
+    })
+}
+
+fn parse(
+    format: MessageFormat,
+    source: &'static str,
+    source_text: &str,
+    tokens: Vec<Spanned<Token>>,
+) -> Result<Module, Box<dyn Error>> {
+    diagnostics::parse(source_text, tokens).or_else(|diagnostics| {
+        for diagnostic in diagnostics {
+            diagnostic.emit(format, source, source_text)?;
+        }
+        Err(Reported("Failed to parse".into()).into())
+    })
+}
+
+/// Reads a syntax tree written by `dberd parse --emit ast-json`.
+fn load_ast(format: MessageFormat, source: &'static str, text: &str) -> Result<Module, Box<dyn Error>> {
+    let loaded = serde_json::from_str(text)
+        .map_err(|error| format!("Invalid JSON: {error}"))
+        .and_then(|value| ast::from_json(&value));
+    match loaded {
+        Ok(module) => Ok(module),
+        Err(message) => {
+            diagnostics::emit_error(format, Some(source), None, &message);
+            Err(Reported("Failed to load the syntax tree".into()).into())
+        }
+    }
+}
+
+/// Reads a source file that declares a schema, reporting anything that is wrong with it.
+fn schema(format: MessageFormat, source: String) -> Result<Module, Box<dyn Error>> {
+    let source_text = std::fs::read_to_string(source.clone())?;
+
+    let source = Box::leak(Box::new(source));
+    let tokens = lex(format, source, &source_text)?;
+    let module = parse(format, source, &source_text, tokens)?;
+    resolve(format, source, Some(&source_text), &module)?;
+    Ok(module)
+}
+
+fn resolve(
+    format: MessageFormat,
+    source: &'static str,
+    source_text: Option<&str>,
+    module: &Module,
+) -> Result<(), Box<dyn Error>> {
+    let diagnostics = resolver::resolve(module).diagnostics;
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    for diagnostic in diagnostics {
+        emit(&diagnostic, format, source, source_text)?;
+    }
+    Err(Reported("Failed to resolve".into()).into())
+}
@@ -0,0 +1,233 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, ToPrimitive, Zero};
+
+/// A dberd number.
+///
+/// Integers that don't fit in an `i64` are promoted to a [`BigInt`], and dividing integers gives
+/// an exact [`BigRational`]. Results are always kept in the smallest kind that can hold them, so
+/// `4 / 2` is the integer `2`. Exact numbers are widened to floats whenever they are combined
+/// with one. Going the other way has to be asked for with the `trunc` or `round` builtins.
+///
+/// Floats are always finite, like the float literals are: results that would be infinite or NaN
+/// are errors instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    Big(BigInt),
+    Rational(BigRational),
+    Float(f64),
+}
+
+impl From<BigInt> for Number {
+    fn from(big: BigInt) -> Self {
+        match big.to_i64() {
+            Some(integer) => Number::Integer(integer),
+            None => Number::Big(big),
+        }
+    }
+}
+
+impl From<BigRational> for Number {
+    fn from(rational: BigRational) -> Self {
+        if rational.is_integer() {
+            Number::from(rational.to_integer())
+        } else {
+            Number::Rational(rational)
+        }
+    }
+}
+
+impl Number {
+    /// Converts an exact number to a rational, or returns `None` for floats.
+    fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Number::Integer(integer) => Some(BigRational::from_integer(BigInt::from(*integer))),
+            Number::Big(big) => Some(BigRational::from_integer(big.clone())),
+            Number::Rational(rational) => Some(rational.clone()),
+            Number::Float(_) => None,
+        }
+    }
+
+    /// Converts a number to a float, failing if it is too big for one. Only floats are finite, so
+    /// the error says how long the number is rather than printing all of it.
+    pub fn to_finite_float(&self) -> Result<Number, String> {
+        finite(self.to_float(), || {
+            let integer = self.to_rational().map_or_else(String::new, |rational| rational.trunc().to_string());
+            let digits = integer.trim_start_matches('-').len();
+            format!("A number with {digits} digits is too big for a float")
+        })
+    }
+
+    pub fn to_float(&self) -> f64 {
+        match self {
+            Number::Integer(integer) => *integer as f64,
+            Number::Big(big) => big.to_f64().unwrap_or(f64::NAN),
+            Number::Rational(rational) => rational.to_f64().unwrap_or(f64::NAN),
+            Number::Float(float) => *float,
+        }
+    }
+
+    /// Truncates a number towards zero.
+    pub fn trunc(&self) -> Result<Number, String> {
+        match self {
+            Number::Float(float) => float_to_integer(float.trunc()),
+            number => Ok(Number::from(number.to_rational().unwrap().trunc())),
+        }
+    }
+
+    /// Rounds a number to the closest integer, rounding halfway cases away from zero.
+    pub fn round(&self) -> Result<Number, String> {
+        match self {
+            Number::Float(float) => float_to_integer(float.round()),
+            number => Ok(Number::from(number.to_rational().unwrap().round())),
+        }
+    }
+
+    /// The numerator of an exact number in its lowest terms.
+    pub fn numerator(&self) -> Result<Number, String> {
+        let rational = self
+            .to_rational()
+            .ok_or_else(|| format!("The float {self} has no numerator"))?;
+        Ok(Number::from(rational.numer().clone()))
+    }
+
+    /// The denominator of an exact number in its lowest terms.
+    pub fn denominator(&self) -> Result<Number, String> {
+        let rational = self
+            .to_rational()
+            .ok_or_else(|| format!("The float {self} has no denominator"))?;
+        Ok(Number::from(rational.denom().clone()))
+    }
+
+    pub fn add(&self, rhs: &Number) -> Result<Number, String> {
+        match (self, rhs) {
+            (Number::Integer(lhs), Number::Integer(rhs)) => Ok(lhs
+                .checked_add(*rhs)
+                .map(Number::Integer)
+                .unwrap_or_else(|| Number::from(BigInt::from(*lhs) + rhs))),
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                finite(self.to_float() + rhs.to_float(), || format!("{self} + {rhs} is too big for a float"))
+            }
+            (lhs, rhs) => Ok(Number::from(lhs.to_rational().unwrap() + rhs.to_rational().unwrap())),
+        }
+    }
+
+    pub fn div(&self, rhs: &Number) -> Result<Number, String> {
+        if rhs.is_zero() {
+            return Err(format!("Can't divide {self} by zero"));
+        }
+
+        match (self, rhs) {
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                finite(self.to_float() / rhs.to_float(), || format!("{self} / {rhs} is too big for a float"))
+            }
+            (lhs, rhs) => Ok(Number::from(lhs.to_rational().unwrap() / rhs.to_rational().unwrap())),
+        }
+    }
+
+    /// Compares exact numbers exactly, and anything compared with a float as floats. Nothing is
+    /// ordered with NaN.
+    pub fn compare(&self, rhs: &Number) -> Option<Ordering> {
+        match (self.to_rational(), rhs.to_rational()) {
+            (Some(lhs), Some(rhs)) => Some(lhs.cmp(&rhs)),
+            _ => self.to_float().partial_cmp(&rhs.to_float()),
+        }
+    }
+
+    pub fn bitand(&self, rhs: &Number) -> Result<Number, String> {
+        Ok(Number::from(self.to_integer("&")? & rhs.to_integer("&")?))
+    }
+
+    pub fn bitor(&self, rhs: &Number) -> Result<Number, String> {
+        Ok(Number::from(self.to_integer("|")? | rhs.to_integer("|")?))
+    }
+
+    pub fn bitxor(&self, rhs: &Number) -> Result<Number, String> {
+        Ok(Number::from(self.to_integer("^")? ^ rhs.to_integer("^")?))
+    }
+
+    pub fn shl(&self, rhs: &Number) -> Result<Number, String> {
+        Ok(Number::from(self.to_integer("<<")? << rhs.to_shift("<<")?))
+    }
+
+    /// Shifts right, rounding towards negative infinity like Rust's `>>` on signed integers.
+    pub fn shr(&self, rhs: &Number) -> Result<Number, String> {
+        Ok(Number::from(self.to_integer(">>")? >> rhs.to_shift(">>")?))
+    }
+
+    /// Converts an integer to a [`BigInt`] for a bitwise operator, which doesn't work on anything
+    /// else.
+    fn to_integer(&self, op: &str) -> Result<BigInt, String> {
+        match self {
+            Number::Integer(integer) => Ok(BigInt::from(*integer)),
+            Number::Big(big) => Ok(big.clone()),
+            number => Err(format!("'{op}' only works on integers, but got {number}")),
+        }
+    }
+
+    /// Converts the right hand side of a shift to a number of bits.
+    fn to_shift(&self, op: &str) -> Result<usize, String> {
+        match self {
+            Number::Integer(shift) if (0..=MAX_SHIFT as i64).contains(shift) => Ok(*shift as usize),
+            Number::Integer(_) | Number::Big(_) => Err(format!(
+                "'{op}' can only shift by 0 to {MAX_SHIFT} bits, but got {self}"
+            )),
+            number => Err(format!("'{op}' only works on integers, but got {number}")),
+        }
+    }
+
+    /// An estimate of the bytes a number takes up on the heap.
+    pub fn memory(&self) -> usize {
+        match self {
+            Number::Integer(_) | Number::Float(_) => 0,
+            Number::Big(big) => big.bits().div_ceil(8) as usize,
+            Number::Rational(rational) => {
+                (rational.numer().bits().div_ceil(8) + rational.denom().bits().div_ceil(8)) as usize
+            }
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(integer) => *integer == 0,
+            Number::Big(big) => big.is_zero(),
+            Number::Rational(rational) => rational.is_zero(),
+            Number::Float(float) => *float == 0.0,
+        }
+    }
+}
+
+/// The largest amount a number can be shifted by, which keeps `1 << n` from using up all memory.
+const MAX_SHIFT: usize = 1 << 16;
+
+/// Makes a float, or the error from `error` if `float` is infinite or NaN.
+fn finite(float: f64, error: impl FnOnce() -> String) -> Result<Number, String> {
+    if float.is_finite() {
+        Ok(Number::Float(float))
+    } else {
+        Err(error())
+    }
+}
+
+fn float_to_integer(float: f64) -> Result<Number, String> {
+    BigInt::from_f64(float)
+        .map(Number::from)
+        .ok_or_else(|| format!("{float:?} can't be converted to an integer"))
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Integer(integer) => write!(f, "{integer}"),
+            Number::Big(big) => write!(f, "{big}"),
+            // Printed as a division so that it evaluates to the same rational when parsed again.
+            Number::Rational(rational) => write!(f, "{}/{}", rational.numer(), rational.denom()),
+            // Debug always includes a decimal point or exponent, so floats stay floats when the
+            // output is parsed again.
+            Number::Float(float) => write!(f, "{float:?}"),
+        }
+    }
+}
@@ -1,40 +1,177 @@
+// chumsky's errors are as big as they are, and only ever built while parsing fails.
+#![allow(clippy::result_large_err)]
+
+use std::fmt::Display;
+
 use chumsky::prelude::*;
+use num_bigint::BigInt;
 
-use crate::lexer::Token;
+use crate::{
+    formatter,
+    lexer::{Span, Spanned, Token},
+    number::Number,
+    schema::{Cardinality, Column, ColumnType, Constraint, Entity, Relation},
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
-    Number(u64),
+    Number(Number),
     String(String),
     Boolean(bool),
-    Array(Vec<Expr>),
-    Function { body: Box<Expr> },
+    Array(Vec<Spanned<Expr>>),
+    /// Named fields, e.g. from a JSON object. Field names are unique and kept in order.
+    Record(Vec<(String, Spanned<Expr>)>),
+    Function {
+        params: Vec<Spanned<Expr>>,
+        body: Box<Spanned<Expr>>,
+    },
 }
 
-#[derive(Debug, Clone)]
+impl Display for LiteralValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", formatter::format_value(self, 80, None))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Literal(LiteralValue),
-    Call { fun: Box<Expr> },
+    Call {
+        fun: Box<Spanned<Expr>>,
+        args: Vec<Spanned<Expr>>,
+    },
     Ident(String),
     Let {
-        ident: Box<Expr>,
-        value: Box<Expr>,
-        body: Box<Expr>
+        ident: Box<Spanned<Expr>>,
+        value: Box<Spanned<Expr>>,
+        body: Box<Spanned<Expr>>
     },
-    Grouping(Box<Expr>),
-    Addition {
-        lhs: Box<Expr>,
-        rhs: Box<Expr>
+    Grouping(Box<Spanned<Expr>>),
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Spanned<Expr>>,
+        rhs: Box<Spanned<Expr>>
     }
 }
 
-pub fn is_ident_reserved(ident: impl AsRef<str>) -> bool {
-    match ident.as_ref() {
-        "add" => true,
-        _ => false,
+/// A `test "name" = expr` declaration. The test passes if its body evaluates to anything but
+/// `false` without an error.
+#[derive(Debug, Clone)]
+pub struct Test {
+    pub name: Spanned<String>,
+    pub body: Spanned<Expr>,
+}
+
+/// A whole source file: the expression it evaluates to, and the tests and schema declared around
+/// it.
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub tests: Vec<Test>,
+    pub entities: Vec<Entity>,
+    pub relations: Vec<Relation>,
+    /// Files that only declare tests or a schema don't need one.
+    pub expr: Option<Spanned<Expr>>,
+}
+
+/// A top-level declaration or expression of a [`Module`].
+#[derive(Debug, Clone, Copy)]
+pub enum Item<'a> {
+    Test(&'a Test),
+    Entity(&'a Entity),
+    Relation(&'a Relation),
+    Expr(&'a Spanned<Expr>),
+}
+
+impl Item<'_> {
+    fn start(&self) -> usize {
+        match self {
+            Item::Test(test) => test.name.1.start,
+            Item::Entity(entity) => entity.span.start,
+            Item::Relation(relation) => relation.span.start,
+            Item::Expr((_, span)) => span.start,
+        }
+    }
+}
+
+impl Module {
+    /// Every top-level expression, including test bodies, in source order.
+    pub fn exprs(&self) -> impl Iterator<Item = &Spanned<Expr>> {
+        let mut exprs: Vec<_> = self.tests.iter().map(|test| &test.body).chain(&self.expr).collect();
+        exprs.sort_by_key(|(_, span)| span.start);
+        exprs.into_iter()
+    }
+
+    /// Every declaration and the main expression, in source order.
+    pub fn items(&self) -> Vec<Item<'_>> {
+        let mut items: Vec<_> = self
+            .tests
+            .iter()
+            .map(Item::Test)
+            .chain(self.entities.iter().map(Item::Entity))
+            .chain(self.relations.iter().map(Item::Relation))
+            .chain(self.expr.iter().map(Item::Expr))
+            .collect();
+        items.sort_by_key(Item::start);
+        items
+    }
+}
+
+/// The binary operators, from the most to the least tightly binding. Operators with the same
+/// precedence group from the left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Divide,
+    Add,
+    ShiftLeft,
+    ShiftRight,
+    BitAnd,
+    BitXor,
+    BitOr,
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryOp::Divide => write!(f, "/"),
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::ShiftLeft => write!(f, "<<"),
+            BinaryOp::ShiftRight => write!(f, ">>"),
+            BinaryOp::BitAnd => write!(f, "&"),
+            BinaryOp::BitXor => write!(f, "^"),
+            BinaryOp::BitOr => write!(f, "|"),
+        }
     }
 }
 
+/// Identifiers that are provided by the language and can't be bound with `let`.
+pub const BUILTINS: &[&str] = &[
+    "add", "trunc", "round", "float", "num", "den", "assert", "assert_eq", "eq", "lt", "le", "gt", "ge", "not", "and",
+    "or", "len", "from_json", "to_json",
+];
+
+pub fn is_ident_reserved(ident: impl AsRef<str>) -> bool {
+    BUILTINS.contains(&ident.as_ref())
+}
+
+/// Like `select!`, but reports `expected` when the token doesn't match, so that syntax errors can
+/// say what should have been there.
+fn select<T>(
+    expected: Token,
+    select: impl Fn(&Token) -> Option<T> + Clone,
+) -> impl Parser<Token, T, Error = Simple<Token>> + Clone {
+    let at_end = expected.clone();
+    filter_map(move |span, token| match select(&token) {
+        Some(output) => Ok(output),
+        None => Err(Simple::expected_input_found(span, [Some(expected.clone())], Some(token))),
+    })
+    // `filter_map` doesn't say what it expected at the end of the input either.
+    .or(end()
+        .try_map(|_, span| Err(Simple::custom(span, "")))
+        .map_err(move |error: Simple<Token>| {
+            Simple::expected_input_found(error.span(), [Some(at_end.clone())], error.found().cloned())
+        }))
+}
+
 fn literal() -> impl Parser<Token, LiteralValue, Error = Simple<Token>> + Clone {
     let boolean = just(Token::True)
         .or(just(Token::False))
@@ -43,123 +180,386 @@ fn literal() -> impl Parser<Token, LiteralValue, Error = Simple<Token>> + Clone
             Token::False => LiteralValue::Boolean(false),
             _ => unreachable!(),
         });
-    let number = (choice((
-        just(Token::One),
-        just(Token::Two),
-        just(Token::Three),
-        just(Token::Four),
-        just(Token::Five),
-        just(Token::Six),
-        just(Token::Seven),
-        just(Token::Eight),
-        just(Token::Nine),
-        just(Token::Zero),
-    ))
-    .map(|token| match token {
-        Token::One => 1,
-        Token::Two => 2,
-        Token::Three => 3,
-        Token::Four => 4,
-        Token::Five => 5,
-        Token::Six => 6,
-        Token::Seven => 7,
-        Token::Eight => 8,
-        Token::Nine => 9,
-        Token::Zero => 0,
-        _ => unreachable!(),
-    }))
-    .repeated()
-    .at_least(1)
-    .map(|nums| {
-        LiteralValue::Number(
-            nums.into_iter()
-                .rev()
-                .enumerate()
-                .fold(0, |acc, (i, cur)| acc + cur * 10u64.pow(i as _)),
+    let number = just(Token::Minus)
+        .or_not()
+        .then(
+            select(Token::Number(String::new()), |token| match token {
+                Token::Number(number) => Some(number.clone()),
+                _ => None,
+            })
+            .map_with_span(|number, span| (number, span)),
         )
+        .validate(|(minus, (text, span)), _, emit| {
+            LiteralValue::Number(number(&text, span, minus.is_some(), emit))
+        });
+    let string = select(Token::Str(String::new()), |token| match token {
+        Token::Str(string) => Some(LiteralValue::String(string.clone())),
+        _ => None,
     });
-    choice((boolean, number))
-}
-
-fn ident(allow_reserved: bool) -> impl Parser<Token, Expr, Error = Simple<Token>> + Clone {
-      filter(|t| match t {
-        Token::A => true,
-        Token::B => true,
-        Token::C => true,
-        Token::D => true,
-        Token::E => true,
-        Token::F => true,
-        Token::G => true,
-        Token::H => true,
-        Token::I => true,
-        Token::J => true,
-        Token::K => true,
-        Token::L => true,
-        Token::M => true,
-        Token::N => true,
-        Token::O => true,
-        Token::P => true,
-        Token::Q => true,
-        Token::R => true,
-        Token::S => true,
-        Token::T => true,
-        Token::U => true,
-        Token::V => true,
-        Token::W => true,
-        Token::X => true,
-        Token::Y => true,
-        Token::Z => true,
-        _ => false,
+
+    choice((boolean, number, string))
+}
+
+/// Reads a number literal, reporting every character that doesn't belong in it.
+///
+/// Literals are decimal unless they start with `0x`, `0o` or `0b`, and single underscores can
+/// separate their digits. Decimal literals with a fraction or an exponent are floats.
+fn number(text: &str, span: Span, negative: bool, emit: &mut dyn FnMut(Simple<Token>)) -> Number {
+    let at = |i: usize| span.start + i..span.start + i + 1;
+    let chars: Vec<char> = text.chars().collect();
+
+    let radix = match chars.get(..2) {
+        Some(['0', 'x']) => Some((16, "hexadecimal", "a")),
+        Some(['0', 'o']) => Some((8, "octal", "an")),
+        Some(['0', 'b']) => Some((2, "binary", "a")),
+        _ => None,
+    };
+    underscores(&chars, radix.map_or(10, |(radix, ..)| radix), span.start, emit);
+
+    if let Some((radix, name, article)) = radix {
+        let mut value = BigInt::from(0);
+        let mut has_digits = false;
+        let mut has_others = false;
+        for (i, c) in chars.iter().enumerate().skip(2) {
+            if *c == '_' {
+                continue;
+            }
+            match c.to_digit(radix) {
+                Some(digit) => {
+                    value = value * radix + digit;
+                    has_digits = true;
+                }
+                None => {
+                    emit(Simple::custom(at(i), format!("'{c}' is not {article} {name} digit.")));
+                    has_others = true;
+                }
+            }
+        }
+        if !has_digits && !has_others {
+            emit(Simple::custom(span, format!("This {name} literal has no digits.")));
+        }
+        return Number::from(if negative { -value } else { value });
+    }
+
+    // The lexer only ever puts a digit after a `.`, and a sign right after an exponent.
+    let mut literal = String::from(if negative { "-" } else { "" });
+    let mut is_float = false;
+    let mut in_exponent = false;
+    let mut exponent_digits = false;
+    for (i, c) in chars.iter().copied().enumerate() {
+        match c {
+            '0'..='9' => {
+                literal.push(c);
+                exponent_digits |= in_exponent;
+            }
+            '_' => {}
+            '.' if !is_float => {
+                literal.push(c);
+                is_float = true;
+            }
+            'e' | 'E' if !in_exponent => {
+                literal.push('e');
+                is_float = true;
+                in_exponent = true;
+            }
+            '+' | '-' => literal.push(c),
+            _ => emit(Simple::custom(at(i), format!("'{c}' is not a decimal digit."))),
+        }
+    }
+    if in_exponent && !exponent_digits {
+        emit(Simple::custom(span.clone(), "This exponent has no digits."));
+        literal.push('0');
+    }
+
+    if !is_float {
+        let integer: BigInt = literal.parse().expect("Integer literals are always made of valid digits");
+        return Number::from(integer);
+    }
+    let float: f64 = literal.parse().expect("Float literals are always made of valid digits");
+    if float.is_infinite() {
+        emit(Simple::custom(span, "Float literal is out of range."));
+    }
+    Number::Float(float)
+}
+
+/// Reports every run of underscores in a number literal that isn't a single one between two
+/// digits, like in `1__0` or `1_`.
+fn underscores(chars: &[char], radix: u32, start: usize, emit: &mut dyn FnMut(Simple<Token>)) {
+    let is_digit = |i: Option<usize>| i.and_then(|i| chars.get(i)).is_some_and(|c| c.is_digit(radix));
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '_' {
+            i += 1;
+            continue;
+        }
+        let end = (i..chars.len()).find(|&j| chars[j] != '_').unwrap_or(chars.len());
+        let span = start + i..start + end;
+        if end - i > 1 {
+            emit(Simple::custom(span, "Only one '_' can separate digits."));
+        } else if !is_digit(i.checked_sub(1)) || !is_digit(Some(end)) {
+            emit(Simple::custom(span, "'_' can only separate digits."));
+        }
+        i = end;
+    }
+}
+
+fn ident(allow_reserved: bool) -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone {
+    select(Token::Ident(String::new()), |token| match token {
+        Token::Ident(ident) => Some(ident.clone()),
+        _ => None,
     })
-    .map(|t| t.to_string())
-    .repeated()
-    .at_least(1)
-    .map(|chars| chars.into_iter().collect())
     .validate(move |string: String, span, emit| {if !allow_reserved && is_ident_reserved(&string) {
             emit(Simple::custom(span, format!("'{string}' is a reserved keyword")));   
         }
         Expr::Ident(string)
     })
+    .map_with_span(|ident, span| (ident, span))
 }
 
-pub fn expr() -> impl Parser<Token, Expr, Error = Simple<Token>> {
+fn binary(lhs: Spanned<Expr>, (op, rhs): (BinaryOp, Spanned<Expr>)) -> Spanned<Expr> {
+    let span = lhs.1.start..rhs.1.end;
+    (Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span)
+}
+
+pub fn expr() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone {
     recursive(|expr| {
-        let grouping = just(Token::LeftParen).ignore_then(expr.clone()).then_ignore(just(Token::RightParen)).map(|e| Expr::Grouping(Box::new(e)));
-        let literal = literal().map(Expr::Literal).or(grouping);
+        // Labels only start after the first token, since chumsky also puts them on errors from
+        // alternatives that were never really taken.
+        let grouping = just(Token::LeftParen).ignore_then(expr.clone().then_ignore(just(Token::RightParen)).labelled("a parenthesized expression")).map_with_span(|e, span| (Expr::Grouping(Box::new(e)), span));
+        let literal = literal().map_with_span(|literal, span| (Expr::Literal(literal), span)).or(grouping);
         let p_ident = ident(true).or(literal);
 
         let call = just(Token::Colon)
-            .ignore_then(expr.clone())
-            .then_ignore(just(Token::LeftBrace))
-            .then_ignore(just(Token::RightBrace))
-            .map(|expr| Expr::Call {
+            .ignore_then(
+                expr.clone()
+                    .then_ignore(just(Token::LeftBrace))
+                    .then(expr.clone().separated_by(just(Token::Comma)).allow_trailing())
+                    .then_ignore(just(Token::RightBrace))
+                    .labelled("a function call"),
+            )
+            .map_with_span(|(expr, args), span| (Expr::Call {
                 fun: Box::new(expr),
-            })
+                args,
+            }, span))
             .or(p_ident);
 
         let function = just(Token::LeftBrace)
-            .ignored()
-            .then_ignore(just(Token::RightBrace))
-            .then_ignore(just(Token::Arrow))
-            .then(expr.clone())
-            .map(|(_, expr)| Expr::Literal(LiteralValue::Function {
+            .ignore_then(
+                ident(false).separated_by(just(Token::Comma)).allow_trailing()
+                    .then_ignore(just(Token::RightBrace))
+                    .then_ignore(just(Token::Arrow))
+                    .then(expr.clone())
+                    .labelled("a function"),
+            )
+            .map_with_span(|(params, expr), span| (Expr::Literal(LiteralValue::Function {
+                params,
                 body: Box::new(expr),
-            }))
+            }), span))
             .or(call);
 
         let let_ = just(Token::Let)
-            .ignore_then(ident(false))
-            .then_ignore(just(Token::Equals))
-            .then(expr.clone())
-            .then_ignore(just(Token::In))
-            .then(expr)
-            .map(|((ident, value), body)| Expr::Let { ident: Box::new(ident), value: Box::new(value), body: Box::new(body) })
+            .ignore_then(
+                ident(false)
+                    .then_ignore(just(Token::Equals))
+                    .then(expr.clone())
+                    .then_ignore(just(Token::In))
+                    .then(expr)
+                    .labelled("a let binding"),
+            )
+            .map_with_span(|((ident, value), body), span| (Expr::Let { ident: Box::new(ident), value: Box::new(value), body: Box::new(body) }, span))
             .or(function);
-        
 
-        let addition = let_.clone().then_ignore(just(Token::Plus)).then(let_.clone()).map(|(lhs, rhs)| Expr::Addition { lhs: Box::new(lhs), rhs: Box::new(rhs) }).or(let_);
+        let division = let_.clone()
+            .then(just(Token::Slash).to(BinaryOp::Divide).then(let_).repeated())
+            .foldl(binary);
+        let addition = division.clone()
+            .then(just(Token::Plus).to(BinaryOp::Add).then(division).repeated())
+            .foldl(binary);
+        let shift = addition.clone()
+            .then(choice((
+                just(Token::ShiftLeft).to(BinaryOp::ShiftLeft),
+                just(Token::ShiftRight).to(BinaryOp::ShiftRight),
+            )).then(addition).repeated())
+            .foldl(binary);
+        let bit_and = shift.clone()
+            .then(just(Token::Ampersand).to(BinaryOp::BitAnd).then(shift).repeated())
+            .foldl(binary);
+        let bit_xor = bit_and.clone()
+            .then(just(Token::Caret).to(BinaryOp::BitXor).then(bit_and).repeated())
+            .foldl(binary);
+
+        bit_xor.clone()
+            .then(just(Token::Pipe).to(BinaryOp::BitOr).then(bit_xor).repeated())
+            .foldl(binary)
+    })
+}
+
+/// A word that is only a keyword at the start of a declaration, so it can still be used as an
+/// identifier elsewhere.
+fn keyword(word: &str) -> impl Parser<Token, Token, Error = Simple<Token>> + Clone {
+    just(Token::Ident(word.into()))
+}
+
+fn name() -> impl Parser<Token, Spanned<String>, Error = Simple<Token>> + Clone {
+    select(Token::Ident(String::new()), |token| match token {
+        Token::Ident(name) => Some(name.clone()),
+        _ => None,
+    })
+    .map_with_span(|name, span| (name, span))
+}
+
+/// Parses a name that must be one of `names`, reporting the ones it could have been otherwise.
+fn named<T>(
+    kind: &'static str,
+    names: &'static [&'static str],
+    from_name: fn(&str) -> Option<T>,
+) -> impl Parser<Token, Spanned<T>, Error = Simple<Token>> + Clone {
+    name().try_map(move |(name, span), _| match from_name(&name) {
+        Some(value) => Ok((value, span)),
+        None => Err(Simple::custom(
+            span,
+            format!("'{name}' is not a {kind}. Expected one of: {}.", names.join(", ")),
+        )),
+    })
+}
+
+/// A `was old_name` annotation, saying what an entity or column used to be called.
+fn renamed_from() -> impl Parser<Token, Option<Spanned<String>>, Error = Simple<Token>> + Clone {
+    keyword("was").ignore_then(name()).or_not()
+}
 
-        
-        addition
+fn cardinality() -> impl Parser<Token, Spanned<Cardinality>, Error = Simple<Token>> + Clone {
+    let bound = select(Token::Number(String::new()), |token| match token {
+        Token::Number(number) => Some(number.clone()),
+        _ => None,
     })
+    .try_map(|number, span| {
+        number
+            .parse::<u64>()
+            .map_err(|_| Simple::custom(span, format!("'{number}' is not a cardinality.")))
+    });
+
+    bound
+        .clone()
+        .then(
+            just(Token::Dot)
+                .ignore_then(just(Token::Dot))
+                .ignore_then(bound.map(Some).or(just(Token::Star).to(None)))
+                .or_not(),
+        )
+        .validate(|(min, max), span: Span, emit| {
+            let max = max.unwrap_or(Some(min));
+            if max.is_some_and(|max| max < min) {
+                emit(Simple::custom(span.clone(), "The upper bound is lower than the lower bound."));
+            }
+            (Cardinality { min, max }, span)
+        })
+}
+
+/// What can follow the type of a column.
+enum Modifier {
+    Constraint(Spanned<Constraint>),
+    Default(Spanned<Expr>),
+    Check(Spanned<Expr>),
+}
+
+enum Declaration {
+    Test(Test),
+    Entity(Entity),
+    Relation(Relation),
+}
+
+/// Parses a whole source file.
+pub fn module() -> impl Parser<Token, Module, Error = Simple<Token>> {
+    let column = name()
+        .then_ignore(just(Token::Colon))
+        .then(named("column type", ColumnType::NAMES, ColumnType::from_name))
+        .then(
+            choice((
+                keyword("default").ignore_then(expr()).map(Modifier::Default),
+                keyword("check").ignore_then(expr()).map(Modifier::Check),
+                named("constraint", Constraint::NAMES, Constraint::from_name).map(Modifier::Constraint),
+            ))
+            .repeated(),
+        )
+        .then(renamed_from())
+        .validate(|(((name, ty), modifiers), renamed_from), _, emit| {
+            let mut column = Column {
+                name,
+                ty,
+                constraints: Vec::new(),
+                default: None,
+                checks: Vec::new(),
+                renamed_from,
+            };
+            for modifier in modifiers {
+                match modifier {
+                    Modifier::Constraint(constraint) => column.constraints.push(constraint),
+                    Modifier::Default((_, span)) if column.default.is_some() => {
+                        emit(Simple::custom(span, "A column can only have one default."));
+                    }
+                    Modifier::Default(default) => column.default = Some(default),
+                    Modifier::Check(check) => column.checks.push(check),
+                }
+            }
+            column
+        });
+
+    let entity = keyword("entity")
+        .ignore_then(
+            name()
+                .then(renamed_from())
+                .then_ignore(just(Token::LeftBrace))
+                .then(column.separated_by(just(Token::Comma)).allow_trailing())
+                .then_ignore(just(Token::RightBrace))
+                .labelled("an entity"),
+        )
+        .map_with_span(|((name, renamed_from), columns), span| {
+            Declaration::Entity(Entity {
+                name,
+                renamed_from,
+                columns,
+                span,
+            })
+        });
+
+    let relation = keyword("relation")
+        .ignore_then(name().then(cardinality()).then(name()).labelled("a relation"))
+        .map_with_span(|((from, cardinality), to), span| {
+            Declaration::Relation(Relation { from, cardinality, to, span })
+        });
+
+    let test = keyword("test")
+        .ignore_then(
+            select(Token::Str(String::new()), |token| match token {
+                Token::Str(name) => Some(name.clone()),
+                _ => None,
+            })
+            .map_with_span(|name, span| (name, span))
+            .then_ignore(just(Token::Equals))
+            .then(expr())
+            .labelled("a test"),
+        )
+        .map(|(name, body)| Declaration::Test(Test { name, body }));
+
+    let item = choice((test, entity, relation));
+
+    item.clone()
+        .repeated()
+        .then(expr().or_not())
+        .then(item.repeated())
+        .map(|((before, expr), after)| {
+            let mut module = Module {
+                expr,
+                ..Module::default()
+            };
+            for item in before.into_iter().chain(after) {
+                match item {
+                    Declaration::Test(test) => module.tests.push(test),
+                    Declaration::Entity(entity) => module.entities.push(entity),
+                    Declaration::Relation(relation) => module.relations.push(relation),
+                }
+            }
+            module
+        })
 }
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use crate::{
+    diagnostics::{Diagnostic, Label, Severity},
+    lexer::{Span, Spanned},
+    parser::{Expr, LiteralValue, Module, BUILTINS},
+    schema::{self, Column},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum BindingKind<'a> {
+    Let { value: &'a Spanned<Expr> },
+    Parameter,
+    /// A column, which the checks of its entity can refer to.
+    Column { column: &'a Column },
+}
+
+/// A name introduced by a `let`, a function parameter or a column.
+#[derive(Debug, Clone, Copy)]
+pub struct Binding<'a> {
+    pub name: &'a str,
+    /// The identifier that introduced the binding.
+    pub span: &'a Span,
+    /// The part of the source the binding can be referred to from.
+    pub scope: &'a Span,
+    pub kind: BindingKind<'a>,
+}
+
+/// Every binding in a file, and the binding each identifier refers to.
+#[derive(Debug)]
+pub struct Resolution<'a> {
+    pub bindings: Vec<Binding<'a>>,
+    /// Maps the span of every identifier to the index of its binding in `bindings`. Identifiers
+    /// that refer to builtins or to nothing at all aren't included.
+    pub references: HashMap<Span, usize>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Resolution<'a> {
+    /// Finds the binding an identifier refers to, or the binding it introduces.
+    pub fn binding(&self, span: &Span) -> Option<&Binding<'a>> {
+        self.references
+            .get(span)
+            .map(|&i| &self.bindings[i])
+            .or_else(|| self.bindings.iter().find(|binding| binding.span == span))
+    }
+
+    /// Lists the bindings that are in scope at `offset`, outermost first.
+    pub fn visible_at(&self, offset: usize) -> impl DoubleEndedIterator<Item = &Binding<'a>> {
+        self.bindings
+            .iter()
+            .filter(move |binding| binding.scope.start <= offset && offset <= binding.scope.end)
+    }
+}
+
+struct Resolver<'a> {
+    resolution: Resolution<'a>,
+    /// Indices of the bindings that are currently in scope, innermost last.
+    scope: Vec<usize>,
+}
+
+impl<'a> Resolver<'a> {
+    fn bind(&mut self, (ident, span): &'a Spanned<Expr>, scope: &'a Span, kind: BindingKind<'a>) {
+        let Expr::Ident(name) = ident else {
+            return;
+        };
+        self.push(Binding {
+            name,
+            span,
+            scope,
+            kind,
+        });
+    }
+
+    fn push(&mut self, binding: Binding<'a>) {
+        self.scope.push(self.resolution.bindings.len());
+        self.resolution.bindings.push(binding);
+    }
+
+    fn reference(&mut self, name: &str, span: &Span) {
+        let binding = self
+            .scope
+            .iter()
+            .rev()
+            .find(|&&i| self.resolution.bindings[i].name == name);
+
+        if let Some(&binding) = binding {
+            self.resolution.references.insert(span.clone(), binding);
+        } else if !BUILTINS.contains(&name) {
+            let candidates = self
+                .scope
+                .iter()
+                .map(|&i| self.resolution.bindings[i].name)
+                .chain(BUILTINS.iter().copied());
+
+            self.resolution.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: "E0005",
+                message: "Unknown identifier found.".into(),
+                span: span.clone(),
+                labels: vec![Label {
+                    span: span.clone(),
+                    message: format!("'{name}' is not defined in this scope."),
+                }],
+                help: suggestion(name, candidates).map(|name| format!("Did you mean '{name}'?")),
+            });
+        }
+    }
+
+    fn expr(&mut self, (expr, span): &'a Spanned<Expr>) {
+        match expr {
+            Expr::Literal(LiteralValue::Array(items)) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            Expr::Literal(LiteralValue::Function { params, body }) => {
+                let depth = self.scope.len();
+                for param in params {
+                    self.bind(param, &body.1, BindingKind::Parameter);
+                }
+                self.expr(body);
+                self.scope.truncate(depth);
+            }
+            Expr::Literal(_) => {}
+            Expr::Call { fun, args } => {
+                self.expr(fun);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Ident(name) => self.reference(name, span),
+            Expr::Let { ident, value, body } => {
+                self.expr(value);
+
+                let depth = self.scope.len();
+                self.bind(ident, &body.1, BindingKind::Let { value });
+                self.expr(body);
+                self.scope.truncate(depth);
+            }
+            Expr::Grouping(expr) => self.expr(expr),
+            Expr::Binary { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+        }
+    }
+}
+
+/// Resolves every identifier in `module` to the binding it refers to, reporting identifiers that
+/// aren't bound anywhere, and validates the schema it declares.
+///
+/// Resolution is lexical: a name has to be bound around the place it is used. The interpreter
+/// looks names up in the bindings of the call that is running instead, so a function can use a
+/// name bound where it is called rather than where it is defined. Such names are still reported
+/// as unknown, since they only work for some of the places the function is called from.
+pub fn resolve(module: &Module) -> Resolution<'_> {
+    let mut resolver = Resolver {
+        resolution: Resolution {
+            bindings: Vec::new(),
+            references: HashMap::new(),
+            diagnostics: Vec::new(),
+        },
+        scope: Vec::new(),
+    };
+    for expr in module.exprs() {
+        resolver.expr(expr);
+    }
+    for entity in &module.entities {
+        // Defaults are evaluated on their own, while checks see every column of the row.
+        for default in entity.columns.iter().filter_map(|column| column.default.as_ref()) {
+            resolver.expr(default);
+        }
+        for column in &entity.columns {
+            resolver.push(Binding {
+                name: &column.name.0,
+                span: &column.name.1,
+                scope: &entity.span,
+                kind: BindingKind::Column { column },
+            });
+        }
+        for check in entity.columns.iter().flat_map(|column| &column.checks) {
+            resolver.expr(check);
+        }
+        resolver.scope.clear();
+    }
+    resolver.resolution.diagnostics.extend(schema::validate(module));
+    resolver.resolution
+}
+
+/// Picks the candidate closest to `name`, if any is close enough to be a likely typo.
+pub fn suggestion<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.len() / 3).max(1);
+
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
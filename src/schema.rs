@@ -0,0 +1,437 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    diagnostics::{Diagnostic, Label, Severity},
+    lexer::{Span, Spanned},
+    enterpreter::{self, Context, EvalError, Limits},
+    parser::{Expr, LiteralValue, Module, BUILTINS},
+    resolver,
+    types::{self, Type},
+};
+
+/// The type of the values a column holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int,
+    Float,
+    String,
+    Bool,
+}
+
+impl ColumnType {
+    pub const NAMES: &'static [&'static str] = &["int", "float", "string", "bool"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(ColumnType::Int),
+            "float" => Some(ColumnType::Float),
+            "string" => Some(ColumnType::String),
+            "bool" => Some(ColumnType::Bool),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnType::Int => write!(f, "int"),
+            ColumnType::Float => write!(f, "float"),
+            ColumnType::String => write!(f, "string"),
+            ColumnType::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    /// The column is part of the entity's primary key.
+    PrimaryKey,
+    /// No two rows have the same value in the column.
+    Unique,
+    /// The column may be left empty, i.e. be SQL `NULL`.
+    Optional,
+}
+
+impl Constraint {
+    pub const NAMES: &'static [&'static str] = &["pk", "unique", "optional"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pk" => Some(Constraint::PrimaryKey),
+            "unique" => Some(Constraint::Unique),
+            "optional" => Some(Constraint::Optional),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constraint::PrimaryKey => write!(f, "pk"),
+            Constraint::Unique => write!(f, "unique"),
+            Constraint::Optional => write!(f, "optional"),
+        }
+    }
+}
+
+/// A `name: type constraints...` column of an entity.
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: Spanned<String>,
+    pub ty: Spanned<ColumnType>,
+    pub constraints: Vec<Spanned<Constraint>>,
+    /// The value of the column in rows that don't give one, from `default expr`.
+    pub default: Option<Spanned<Expr>>,
+    /// Conditions from `check expr` that every row has to meet. They can refer to every column of
+    /// the entity.
+    pub checks: Vec<Spanned<Expr>>,
+    /// The name given after `was`, which the column had in an earlier version of the schema.
+    pub renamed_from: Option<Spanned<String>>,
+}
+
+impl Column {
+    pub fn has(&self, constraint: Constraint) -> bool {
+        self.constraints.iter().any(|(c, _)| *c == constraint)
+    }
+}
+
+/// An `entity Name { columns... }` declaration: a table of the schema.
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub name: Spanned<String>,
+    /// The name given after `was`, which the entity had in an earlier version of the schema.
+    pub renamed_from: Option<Spanned<String>>,
+    pub columns: Vec<Column>,
+    pub span: Span,
+}
+
+impl Entity {
+    /// The columns making up the primary key.
+    pub fn primary_key(&self) -> impl Iterator<Item = &Column> {
+        self.columns.iter().filter(|column| column.has(Constraint::PrimaryKey))
+    }
+}
+
+/// How many rows of one entity a row of another is related to: `1`, `0..1` or `1..*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cardinality {
+    pub min: u64,
+    /// `None` if there is no upper bound.
+    pub max: Option<u64>,
+}
+
+impl Display for Cardinality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{max}"),
+            Some(max) => write!(f, "{}..{max}", self.min),
+            None => write!(f, "{}..*", self.min),
+        }
+    }
+}
+
+/// A `relation From cardinality To` declaration: every row of `from` is related to
+/// `cardinality` rows of `to`.
+#[derive(Debug, Clone)]
+pub struct Relation {
+    pub from: Spanned<String>,
+    pub cardinality: Spanned<Cardinality>,
+    pub to: Spanned<String>,
+    pub span: Span,
+}
+
+fn duplicate(kind: &str, (name, span): &Spanned<String>, first: &Span) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        code: "E0008",
+        message: format!("Duplicate {kind} found."),
+        span: span.clone(),
+        labels: vec![
+            Label {
+                span: span.clone(),
+                message: format!("'{name}' is declared again here."),
+            },
+            Label {
+                span: first.clone(),
+                message: format!("'{name}' was first declared here."),
+            },
+        ],
+        help: None,
+    }
+}
+
+/// How far defaults and checks may go while the schema is validated or exported, so that a runaway
+/// expression can't hang an editor.
+pub fn limits() -> Limits {
+    Limits {
+        max_steps: Some(100_000),
+        max_depth: Some(256),
+        max_memory: None,
+    }
+}
+
+/// The checks of `entity` that a row doesn't meet, with the error they stopped at if they
+/// couldn't be evaluated. `row` maps column names to their values; checks that refer to a column
+/// the row has no value for are skipped, like SQL skips checks on `NULL`.
+pub fn failed_checks<'a>(
+    entity: &'a Entity,
+    row: &HashMap<String, LiteralValue>,
+) -> Vec<(&'a Spanned<Expr>, Option<EvalError>)> {
+    let ctx = Context {
+        idents: row
+            .iter()
+            .map(|(name, value)| (name.clone(), (Expr::Literal(value.clone()), 0..0)))
+            .collect(),
+    };
+    let mut failed = Vec::new();
+    for check in entity.columns.iter().flat_map(|column| &column.checks) {
+        let mut names = Vec::new();
+        free_idents(check, &mut Vec::new(), &mut names);
+        if names.iter().any(|name| {
+            entity.columns.iter().any(|column| column.name.0 == *name) && !row.contains_key(*name)
+        }) {
+            continue;
+        }
+        match enterpreter::evaluate(check.clone(), &ctx, &limits()) {
+            Ok(LiteralValue::Boolean(true)) => {}
+            Ok(_) => failed.push((check, None)),
+            Err(error) => failed.push((check, Some(error))),
+        }
+    }
+    failed
+}
+
+/// Whether `expr` refers to any of the columns of `entity`.
+pub fn uses_columns(entity: &Entity, expr: &Spanned<Expr>) -> bool {
+    free_names(expr)
+        .iter()
+        .any(|name| entity.columns.iter().any(|column| column.name.0 == *name))
+}
+
+/// The identifiers in `expr` that aren't bound within it, builtins included.
+pub fn free_names(expr: &Spanned<Expr>) -> Vec<&str> {
+    let mut names = Vec::new();
+    free_idents(expr, &mut Vec::new(), &mut names);
+    names
+}
+
+/// Collects the identifiers in `expr` that aren't bound within it.
+fn free_idents<'a>((expr, _): &'a Spanned<Expr>, bound: &mut Vec<&'a str>, names: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Literal(LiteralValue::Array(items)) => {
+            for item in items {
+                free_idents(item, bound, names);
+            }
+        }
+        Expr::Literal(LiteralValue::Function { params, body }) => {
+            let depth = bound.len();
+            bound.extend(params.iter().filter_map(|param| match &param.0 {
+                Expr::Ident(name) => Some(name.as_str()),
+                _ => None,
+            }));
+            free_idents(body, bound, names);
+            bound.truncate(depth);
+        }
+        Expr::Literal(_) => {}
+        Expr::Ident(name) if !bound.contains(&name.as_str()) => names.push(name),
+        Expr::Ident(_) => {}
+        Expr::Call { fun, args } => {
+            free_idents(fun, bound, names);
+            for arg in args {
+                free_idents(arg, bound, names);
+            }
+        }
+        Expr::Let { ident, value, body } => {
+            free_idents(value, bound, names);
+            let depth = bound.len();
+            if let (Expr::Ident(name), _) = ident.as_ref() {
+                bound.push(name);
+            }
+            free_idents(body, bound, names);
+            bound.truncate(depth);
+        }
+        Expr::Grouping(inner) => free_idents(inner, bound, names),
+        Expr::Binary { lhs, rhs, .. } => {
+            free_idents(lhs, bound, names);
+            free_idents(rhs, bound, names);
+        }
+    }
+}
+
+fn invalid_default(span: &Span, message: String, help: Option<String>) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        code: "E0013",
+        message: "Invalid default found.".into(),
+        span: span.clone(),
+        labels: vec![Label {
+            span: span.clone(),
+            message,
+        }],
+        help,
+    }
+}
+
+/// Type-checks the defaults and checks of an entity, and makes sure that the defaults can be
+/// evaluated and meet the checks.
+fn validate_expressions(entity: &Entity, diagnostics: &mut Vec<Diagnostic>) {
+    let columns: HashMap<String, Type> = entity
+        .columns
+        .iter()
+        .map(|column| (column.name.0.clone(), Type::from(column.ty.0)))
+        .collect();
+
+    // Expressions that use names that aren't bound are reported by the resolver, and would only
+    // fail again here.
+    let unbound = |expr: &Spanned<Expr>, columns: &HashMap<String, Type>| {
+        free_names(expr)
+            .iter()
+            .any(|name| !columns.contains_key(*name) && !BUILTINS.contains(name))
+    };
+
+    let mut ill_typed = Vec::new();
+    let mut defaults = HashMap::new();
+    for column in &entity.columns {
+        for check in &column.checks {
+            if unbound(check, &columns) {
+                ill_typed.push(&check.1);
+                continue;
+            }
+            match types::infer(check, &columns) {
+                Ok(Type::Bool | Type::Unknown) => continue,
+                Ok(ty) => diagnostics.push(types::mismatch(
+                    check.1.clone(),
+                    format!("A check has to be a boolean, but this is {ty}."),
+                )),
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+            ill_typed.push(&check.1);
+        }
+
+        let Some(default) = &column.default else {
+            continue;
+        };
+        let (name, ty) = (&column.name.0, column.ty.0);
+        if unbound(default, &HashMap::new()) {
+            continue;
+        }
+        match types::infer(default, &HashMap::new()) {
+            Ok(inferred) if !inferred.fits(ty) => {
+                diagnostics.push(types::mismatch(
+                    default.1.clone(),
+                    format!("'{name}' is {}, but its default is {inferred}.", Type::from(ty)),
+                ));
+                continue;
+            }
+            Ok(_) => {}
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                continue;
+            }
+        }
+        match enterpreter::evaluate(default.clone(), &Context::default(), &limits()) {
+            Ok(value) if !types::of(&value).fits(ty) => diagnostics.push(types::mismatch(
+                default.1.clone(),
+                format!("'{name}' is {}, but its default is {}.", Type::from(ty), types::of(&value)),
+            )),
+            Ok(value) => {
+                defaults.insert(name.clone(), value);
+            }
+            Err(error) => {
+                let message = match &error {
+                    EvalError::Runtime { message, .. } => format!("{message}."),
+                    EvalError::LimitExceeded { limit, max, .. } => {
+                        format!("Evaluating it went over the {limit} limit of {max}.")
+                    }
+                    EvalError::AssertionFailed { .. } => "An assertion failed while evaluating it.".into(),
+                    EvalError::Stopped => unreachable!("Defaults are evaluated without an observer to stop them"),
+                };
+                diagnostics.push(invalid_default(&default.1, message, None));
+            }
+        }
+    }
+
+    // A row of nothing but defaults has to meet the checks, at least where it has a value. Checks
+    // that don't type-check would only fail again.
+    for (check, error) in failed_checks(entity, &defaults) {
+        if ill_typed.contains(&&check.1) {
+            continue;
+        }
+        let message = match error {
+            Some(EvalError::Runtime { message, .. }) => format!("The defaults make this check fail: {message}."),
+            _ => "The defaults don't meet this check.".into(),
+        };
+        diagnostics.push(invalid_default(
+            &check.1,
+            message,
+            Some("Change the defaults so that a row that only has defaults meets every check.".into()),
+        ));
+    }
+}
+
+/// Checks that entity and column names are unique, that every entity has a primary key, that
+/// relations only refer to declared entities, and that defaults and checks fit their columns.
+pub fn validate(module: &Module) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let mut entities: HashMap<&str, &Span> = HashMap::new();
+    for entity in &module.entities {
+        match entities.get(entity.name.0.as_str()) {
+            Some(first) => diagnostics.push(duplicate("entity", &entity.name, first)),
+            None => {
+                entities.insert(&entity.name.0, &entity.name.1);
+            }
+        }
+
+        let mut columns: HashMap<&str, &Span> = HashMap::new();
+        for column in &entity.columns {
+            match columns.get(column.name.0.as_str()) {
+                Some(first) => diagnostics.push(duplicate("column", &column.name, first)),
+                None => {
+                    columns.insert(&column.name.0, &column.name.1);
+                }
+            }
+        }
+
+        if entity.primary_key().next().is_none() {
+            let (name, span) = &entity.name;
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: "E0010",
+                message: "Entity without a primary key found.".into(),
+                span: span.clone(),
+                labels: vec![Label {
+                    span: span.clone(),
+                    message: format!("'{name}' has no primary key."),
+                }],
+                help: Some("Mark its identifying column with 'pk', e.g. 'id: int pk'.".into()),
+            });
+        }
+
+        validate_expressions(entity, &mut diagnostics);
+    }
+
+    for relation in &module.relations {
+        for (name, span) in [&relation.from, &relation.to] {
+            if entities.contains_key(name.as_str()) {
+                continue;
+            }
+            let candidates = module.entities.iter().map(|entity| entity.name.0.as_str());
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: "E0009",
+                message: "Unknown entity found.".into(),
+                span: span.clone(),
+                labels: vec![Label {
+                    span: span.clone(),
+                    message: format!("'{name}' is not declared as an entity."),
+                }],
+                help: resolver::suggestion(name, candidates).map(|name| format!("Did you mean '{name}'?")),
+            });
+        }
+    }
+
+    diagnostics
+}
@@ -0,0 +1,820 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    diff::Renames,
+    enterpreter::{self, Context},
+    formatter,
+    lexer::Spanned,
+    number::Number,
+    parser::{BinaryOp, Expr, LiteralValue, Module},
+    schema::{self, Cardinality, ColumnType, Constraint, Entity, Relation},
+};
+
+/// The SQL dialects `dberd export --format sql` can write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Dialect {
+    #[default]
+    Postgres,
+    Sqlite,
+    Mysql,
+}
+
+impl Dialect {
+    fn quote(self, name: &str) -> String {
+        match self {
+            Dialect::Postgres | Dialect::Sqlite => format!("\"{}\"", name.replace('"', "\"\"")),
+            Dialect::Mysql => format!("`{}`", name.replace('`', "``")),
+        }
+    }
+
+    fn column_type(self, ty: ColumnType) -> &'static str {
+        match (self, ty) {
+            (_, ColumnType::Int) => "INTEGER",
+            (Dialect::Postgres, ColumnType::Float) => "DOUBLE PRECISION",
+            (Dialect::Sqlite, ColumnType::Float) => "REAL",
+            (Dialect::Mysql, ColumnType::Float) => "DOUBLE",
+            (Dialect::Postgres | Dialect::Sqlite, ColumnType::String) => "TEXT",
+            // MySQL can't index TEXT columns without a prefix length.
+            (Dialect::Mysql, ColumnType::String) => "VARCHAR(255)",
+            (Dialect::Sqlite, ColumnType::Bool) => "INTEGER",
+            (Dialect::Postgres | Dialect::Mysql, ColumnType::Bool) => "BOOLEAN",
+        }
+    }
+
+    fn literal(self, value: &LiteralValue) -> Option<String> {
+        match value {
+            LiteralValue::Number(number @ (Number::Integer(_) | Number::Big(_))) => Some(number.to_string()),
+            LiteralValue::Number(number) => {
+                let float = number.to_float();
+                float.is_finite().then(|| format!("{float:?}"))
+            }
+            LiteralValue::String(string) => {
+                let mut escaped = string.replace('\'', "''");
+                // MySQL treats backslashes in strings as escapes.
+                if self == Dialect::Mysql {
+                    escaped = escaped.replace('\\', "\\\\");
+                }
+                Some(format!("'{escaped}'"))
+            }
+            LiteralValue::Boolean(boolean) => Some(match (self, boolean) {
+                (Dialect::Sqlite, true) => "1".into(),
+                (Dialect::Sqlite, false) => "0".into(),
+                (_, true) => "TRUE".into(),
+                (_, false) => "FALSE".into(),
+            }),
+            LiteralValue::Array(_) | LiteralValue::Record(_) | LiteralValue::Function { .. } => None,
+        }
+    }
+
+    /// Translates a dberd expression to SQL, if SQL has an equivalent for everything in it.
+    /// Identifiers refer to columns, except those bound to the SQL in `bindings`. The expression
+    /// is parenthesized unless it is written at the `top` of a clause.
+    fn expression(self, (expr, _): &Spanned<Expr>, bindings: &HashMap<String, String>, top: bool) -> Option<String> {
+        let nested = |sql: String| if top { sql } else { format!("({sql})") };
+        match expr {
+            Expr::Literal(value) => self.literal(value),
+            Expr::Ident(name) => Some(bindings.get(name).cloned().unwrap_or_else(|| self.quote(name))),
+            Expr::Grouping(inner) => self.expression(inner, bindings, top),
+            Expr::Let { ident, value, body } => {
+                let (Expr::Ident(name), _) = ident.as_ref() else {
+                    return None;
+                };
+                let mut bindings = bindings.clone();
+                bindings.insert(name.clone(), self.expression(value, &bindings, false)?);
+                self.expression(body, &bindings, top)
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                let (lhs, rhs) = (self.expression(lhs, bindings, false)?, self.expression(rhs, bindings, false)?);
+                Some(nested(match op {
+                    BinaryOp::Add => format!("{lhs} + {rhs}"),
+                    // Dividing integers gives a fraction in dberd, but truncates in SQL.
+                    BinaryOp::Divide => format!("CAST({lhs} AS {}) / {rhs}", self.column_type(ColumnType::Float)),
+                    BinaryOp::ShiftLeft => format!("{lhs} << {rhs}"),
+                    BinaryOp::ShiftRight => format!("{lhs} >> {rhs}"),
+                    BinaryOp::BitAnd => format!("{lhs} & {rhs}"),
+                    BinaryOp::BitOr => format!("{lhs} | {rhs}"),
+                    BinaryOp::BitXor => match self {
+                        Dialect::Postgres => format!("{lhs} # {rhs}"),
+                        Dialect::Mysql => format!("{lhs} ^ {rhs}"),
+                        Dialect::Sqlite => format!("({lhs} | {rhs}) - ({lhs} & {rhs})"),
+                    },
+                }))
+            }
+            Expr::Call { fun, args } => {
+                let (Expr::Ident(name), _) = fun.as_ref() else {
+                    return None;
+                };
+                if bindings.contains_key(name) {
+                    return None;
+                }
+                let operand = |i: usize| self.expression(args.get(i)?, bindings, false);
+                let argument = |i: usize| self.expression(args.get(i)?, bindings, true);
+                let binary = |op: &str| Some(nested(format!("{} {op} {}", operand(0)?, operand(1)?)));
+                match (name.as_str(), args.len()) {
+                    ("eq", 2) => binary("="),
+                    ("lt", 2) => binary("<"),
+                    ("le", 2) => binary("<="),
+                    ("gt", 2) => binary(">"),
+                    ("ge", 2) => binary(">="),
+                    ("and", 2) => binary("AND"),
+                    ("or", 2) => binary("OR"),
+                    ("add", 2) => binary("+"),
+                    ("not", 1) => Some(nested(format!("NOT {}", operand(0)?))),
+                    ("len", 1) => Some(match self {
+                        Dialect::Postgres => format!("char_length({})", argument(0)?),
+                        Dialect::Mysql => format!("CHAR_LENGTH({})", argument(0)?),
+                        Dialect::Sqlite => format!("length({})", argument(0)?),
+                    }),
+                    ("float", 1) => Some(format!(
+                        "CAST({} AS {})",
+                        argument(0)?,
+                        self.column_type(ColumnType::Float)
+                    )),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Whether a foreign key can refer to a table that doesn't exist yet.
+    fn allows_forward_references(self) -> bool {
+        self == Dialect::Sqlite
+    }
+}
+
+/// `OrderItem` becomes `order_item`, as in the names of foreign key columns.
+pub fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// What a table or column was made for. Unlike its name, this stays the same when the entities
+/// and columns it was made for are renamed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Origin {
+    /// An entity, or a column declared by one.
+    Declared(String),
+    /// A join table between two entities.
+    Join(String, String),
+    /// A column referring to a key column of another entity.
+    Reference(String, String),
+}
+
+impl Origin {
+    /// The origin in terms of the new schema. Columns are given the origin of their table in the
+    /// old schema.
+    fn renamed(&self, table: Option<&Origin>, renames: &Renames) -> Origin {
+        match (self, table) {
+            (Origin::Declared(name), Some(Origin::Declared(entity))) => {
+                Origin::Declared(renames.column(entity, name).into())
+            }
+            (Origin::Declared(name), _) => Origin::Declared(renames.entity(name).into()),
+            (Origin::Join(from, to), _) => Origin::Join(renames.entity(from).into(), renames.entity(to).into()),
+            (Origin::Reference(entity, key), _) => {
+                Origin::Reference(renames.entity(entity).into(), renames.column(entity, key).into())
+            }
+        }
+    }
+}
+
+pub struct Column {
+    pub name: String,
+    pub ty: ColumnType,
+    pub optional: bool,
+    /// The SQL literal the default of the column evaluates to.
+    pub default: Option<String>,
+    origin: Origin,
+}
+
+#[derive(Clone)]
+struct ForeignKey {
+    columns: Vec<String>,
+    table: String,
+    references: Vec<String>,
+}
+
+/// A table as it is written out, with the columns and keys that relations add to entities.
+pub struct Table {
+    pub name: String,
+    origin: Origin,
+    pub columns: Vec<Column>,
+    primary_key: Vec<String>,
+    unique: Vec<Vec<String>>,
+    foreign_keys: Vec<ForeignKey>,
+    /// The checks of each column, in SQL.
+    checks: Vec<(String, String)>,
+    /// Comments about the defaults and checks that SQL has no equivalent for.
+    notes: Vec<String>,
+}
+
+impl Table {
+    fn new(entity: &Entity, dialect: Dialect) -> Self {
+        let mut notes = Vec::new();
+        let mut checks = Vec::new();
+        let mut columns = Vec::new();
+        for column in &entity.columns {
+            let name = &column.name.0;
+            // Defaults can't refer to anything, so they are written as the value they evaluate to.
+            let default = column.default.as_ref().and_then(|default| {
+                let value = enterpreter::evaluate(default.clone(), &Context::default(), &schema::limits());
+                let literal = value.ok().and_then(|value| dialect.literal(&value));
+                if literal.is_none() {
+                    let default = formatter::format_expr(default, 60);
+                    notes.push(format!("The default {default} of {} has no SQL equivalent.", dialect.quote(name)));
+                }
+                literal
+            });
+            for check in &column.checks {
+                match dialect.expression(check, &HashMap::new(), true) {
+                    Some(sql) => checks.push((name.clone(), sql)),
+                    None => {
+                        let check = formatter::format_expr(check, 60);
+                        notes.push(format!("The check {check} on {} has no SQL equivalent.", dialect.quote(name)));
+                    }
+                }
+            }
+            columns.push(Column {
+                name: name.clone(),
+                ty: column.ty.0,
+                optional: column.has(Constraint::Optional),
+                default,
+                origin: Origin::Declared(name.clone()),
+            });
+        }
+
+        Table {
+            name: entity.name.0.clone(),
+            origin: Origin::Declared(entity.name.0.clone()),
+            columns,
+            primary_key: entity.primary_key().map(|column| column.name.0.clone()).collect(),
+            unique: entity
+                .columns
+                .iter()
+                .filter(|column| column.has(Constraint::Unique))
+                .map(|column| vec![column.name.0.clone()])
+                .collect(),
+            foreign_keys: Vec::new(),
+            checks,
+            notes,
+        }
+    }
+
+    /// Adds a column unless the entity already declares one of that name, e.g. a `user_id` it
+    /// meant as the foreign key.
+    fn add_column(&mut self, name: String, ty: ColumnType, optional: bool, origin: Origin) {
+        if !self.columns.iter().any(|column| column.name == name) {
+            self.columns.push(Column {
+                name,
+                ty,
+                optional,
+                default: None,
+                origin,
+            });
+        }
+    }
+
+    /// The primary, unique and foreign keys and the checks of the table, named the way Postgres
+    /// would name them.
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys = Vec::new();
+        if !self.primary_key.is_empty() {
+            keys.push(Key {
+                name: format!("{}_pkey", self.name),
+                kind: KeyKind::Primary,
+                columns: self.primary_key.clone(),
+            });
+        }
+        for columns in &self.unique {
+            keys.push(Key {
+                name: format!("{}_{}_key", self.name, columns.join("_")),
+                kind: KeyKind::Unique,
+                columns: columns.clone(),
+            });
+        }
+        for key in &self.foreign_keys {
+            keys.push(Key {
+                name: format!("{}_{}_fkey", self.name, key.columns.join("_")),
+                kind: KeyKind::Foreign {
+                    table: key.table.clone(),
+                    references: key.references.clone(),
+                },
+                columns: key.columns.clone(),
+            });
+        }
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (column, sql) in &self.checks {
+            let count = counts.entry(column).or_default();
+            let suffix = if *count == 0 { String::new() } else { count.to_string() };
+            *count += 1;
+            keys.push(Key {
+                name: format!("{}_{column}_check{suffix}", self.name),
+                kind: KeyKind::Check(sql.clone()),
+                columns: vec![column.clone()],
+            });
+        }
+        keys
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyKind {
+    Primary,
+    Unique,
+    Foreign { table: String, references: Vec<String> },
+    /// A check on the column of the key, in SQL.
+    Check(String),
+}
+
+/// A key or check constraint, with the name it is created with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub name: String,
+    pub kind: KeyKind,
+    pub columns: Vec<String>,
+}
+
+impl Key {
+    fn definition(&self, dialect: Dialect) -> String {
+        let constraint = format!("CONSTRAINT {}", dialect.quote(&self.name));
+        match &self.kind {
+            KeyKind::Primary => format!("{constraint} PRIMARY KEY ({})", list(dialect, &self.columns)),
+            KeyKind::Unique => format!("{constraint} UNIQUE ({})", list(dialect, &self.columns)),
+            KeyKind::Foreign { table, references } => format!(
+                "{constraint} FOREIGN KEY ({}) REFERENCES {} ({})",
+                list(dialect, &self.columns),
+                dialect.quote(table),
+                list(dialect, references)
+            ),
+            KeyKind::Check(sql) => format!("{constraint} CHECK ({sql})"),
+        }
+    }
+}
+
+fn is_many(cardinality: Cardinality) -> bool {
+    cardinality.max.is_none_or(|max| max > 1)
+}
+
+/// Adds columns to `child` that refer to the primary key of `parent`, returning their names.
+/// `cardinality` is how many rows of `parent` each row of `child` has, if a relation says so. The
+/// columns are nullable if that can be none.
+fn refer(child: &mut Table, parent: &Entity, cardinality: Option<Cardinality>, unique: bool) -> Vec<String> {
+    let optional = cardinality.is_some_and(|cardinality| cardinality.min == 0);
+    let prefix = snake_case(&parent.name.0);
+    let mut columns = Vec::new();
+    let mut references = Vec::new();
+    for key in parent.primary_key() {
+        let name = format!("{prefix}_{}", key.name.0);
+        let origin = Origin::Reference(parent.name.0.clone(), key.name.0.clone());
+        child.add_column(name.clone(), key.ty.0, optional, origin);
+        columns.push(name);
+        references.push(key.name.0.clone());
+    }
+
+    if unique {
+        child.unique.push(columns.clone());
+    }
+    child.foreign_keys.push(ForeignKey {
+        columns: columns.clone(),
+        table: parent.name.0.clone(),
+        references,
+    });
+    columns
+}
+
+/// Turns entities into tables and relations into foreign keys.
+///
+/// A relation only says how many rows of `to` each row of `from` has, so it is read together
+/// with the relation the other way around, if there is one. Many rows on both sides need a join
+/// table, many rows on one side a foreign key on that side, and otherwise a unique foreign key on
+/// the `to` side.
+pub fn tables(module: &Module, dialect: Dialect) -> Vec<Table> {
+    let entity = |name: &str| module.entities.iter().position(|entity| entity.name.0 == name);
+    let mut tables: Vec<Table> = module.entities.iter().map(|entity| Table::new(entity, dialect)).collect();
+
+    let mut handled = vec![false; module.relations.len()];
+    for (i, relation) in module.relations.iter().enumerate() {
+        if handled[i] {
+            continue;
+        }
+        let (Some(from), Some(to)) = (entity(&relation.from.0), entity(&relation.to.0)) else {
+            continue;
+        };
+        let reverse = module.relations.iter().enumerate().find(|&(j, other)| {
+            j != i && !handled[j] && other.from.0 == relation.to.0 && other.to.0 == relation.from.0
+        });
+        let reverse: Option<&Relation> = reverse.map(|(j, other)| {
+            handled[j] = true;
+            other
+        });
+
+        let reverse_cardinality = reverse.map(|reverse| reverse.cardinality.0);
+        let many = is_many(relation.cardinality.0);
+        let reverse_many = reverse.is_some_and(|reverse| is_many(reverse.cardinality.0));
+        match (many, reverse_many) {
+            (true, true) => {
+                let (from, to) = (&module.entities[from], &module.entities[to]);
+                let mut join = Table {
+                    name: format!("{}_{}", snake_case(&from.name.0), snake_case(&to.name.0)),
+                    origin: Origin::Join(from.name.0.clone(), to.name.0.clone()),
+                    columns: Vec::new(),
+                    primary_key: Vec::new(),
+                    unique: Vec::new(),
+                    foreign_keys: Vec::new(),
+                    checks: Vec::new(),
+                    notes: Vec::new(),
+                };
+                for parent in [from, to] {
+                    let columns = refer(&mut join, parent, None, false);
+                    join.primary_key.extend(columns);
+                }
+                tables.push(join);
+            }
+            (false, true) => {
+                refer(&mut tables[from], &module.entities[to], Some(relation.cardinality.0), false);
+            }
+            (true, false) => {
+                refer(&mut tables[to], &module.entities[from], reverse_cardinality, false);
+            }
+            (false, false) => {
+                refer(&mut tables[to], &module.entities[from], reverse_cardinality, true);
+            }
+        }
+    }
+
+    tables
+}
+
+/// Orders tables so that every table comes after the tables it refers to, keeping the order they
+/// were declared in otherwise. Tables that refer to each other are taken in declaration order.
+fn sort(mut tables: Vec<Table>) -> Vec<Table> {
+    let mut sorted: Vec<Table> = Vec::new();
+    while !tables.is_empty() {
+        let ready = tables.iter().position(|table| {
+            table.foreign_keys.iter().all(|key| {
+                key.table == table.name || sorted.iter().any(|created| created.name == key.table)
+            })
+        });
+        sorted.push(tables.remove(ready.unwrap_or(0)));
+    }
+    sorted
+}
+
+fn list(dialect: Dialect, names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| dialect.quote(name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn column_definition(dialect: Dialect, column: &Column) -> String {
+    let mut definition = format!("{} {}", dialect.quote(&column.name), dialect.column_type(column.ty));
+    if !column.optional {
+        definition.push_str(" NOT NULL");
+    }
+    if let Some(default) = &column.default {
+        write!(definition, " DEFAULT {default}").unwrap();
+    }
+    definition
+}
+
+/// A `CREATE TABLE` statement with every key whose foreign keys `inline` accepts.
+fn create_table(dialect: Dialect, table: &Table, inline: impl Fn(&Key) -> bool) -> String {
+    let lines: Vec<String> = table
+        .columns
+        .iter()
+        .map(|column| column_definition(dialect, column))
+        .chain(
+            table
+                .keys()
+                .into_iter()
+                .filter(|key| !matches!(key.kind, KeyKind::Foreign { .. }) || inline(key))
+                .map(|key| key.definition(dialect)),
+        )
+        .map(|line| format!("    {line}"))
+        .collect();
+    let notes: String = table.notes.iter().map(|note| format!("-- {note}\n")).collect();
+    format!("{notes}CREATE TABLE {} (\n{}\n);\n", dialect.quote(&table.name), lines.join(",\n"))
+}
+
+/// Writes a `CREATE TABLE` statement for every entity, and one for every join table.
+pub fn ddl(module: &Module, dialect: Dialect) -> String {
+    let mut out = String::new();
+    let mut created: Vec<String> = Vec::new();
+    let mut deferred = Vec::new();
+
+    for table in sort(tables(module, dialect)) {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        let inline = |key: &Key| match &key.kind {
+            KeyKind::Foreign { table: parent, .. } => {
+                *parent == table.name || created.contains(parent) || dialect.allows_forward_references()
+            }
+            _ => true,
+        };
+        for key in table.keys() {
+            if !inline(&key) {
+                deferred.push((table.name.clone(), key));
+            }
+        }
+        out.push_str(&create_table(dialect, &table, inline));
+        created.push(table.name);
+    }
+
+    for (table, key) in deferred {
+        write!(out, "\nALTER TABLE {} ADD {};\n", dialect.quote(&table), key.definition(dialect)).unwrap();
+    }
+
+    out
+}
+
+/// A table of the old schema, and what it became in the new one.
+struct Matched<'a> {
+    old: &'a Table,
+    new: &'a Table,
+    /// The columns of the old table, and what they became in the new one.
+    columns: Vec<(&'a Column, &'a Column)>,
+}
+
+impl Matched<'_> {
+    /// The keys of the old table in terms of the new schema, keeping their old names.
+    fn old_keys(&self, matched: &[Matched]) -> Vec<Key> {
+        let column = |table: &Matched, name: &str| {
+            table
+                .columns
+                .iter()
+                .find(|(old, _)| old.name == name)
+                .map_or(name.to_string(), |(_, new)| new.name.clone())
+        };
+        let columns = |names: &[String]| names.iter().map(|name| column(self, name)).collect();
+
+        self.old
+            .keys()
+            .into_iter()
+            .map(|key| Key {
+                kind: match key.kind {
+                    KeyKind::Foreign { table, references } => match matched.iter().find(|m| m.old.name == table) {
+                        Some(parent) => KeyKind::Foreign {
+                            table: parent.new.name.clone(),
+                            references: references.iter().map(|name| column(parent, name)).collect(),
+                        },
+                        None => KeyKind::Foreign { table, references },
+                    },
+                    kind => kind,
+                },
+                columns: columns(&key.columns),
+                name: key.name,
+            })
+            .collect()
+    }
+}
+
+/// Whether a key of the old schema is still there in the new one. MySQL always calls primary
+/// keys `PRIMARY`, and SQLite never refers to keys by name, so only the keys' columns matter there.
+fn same_key(dialect: Dialect, old: &Key, new: &Key) -> bool {
+    let ignore_name = dialect == Dialect::Sqlite || (dialect == Dialect::Mysql && new.kind == KeyKind::Primary);
+    old.kind == new.kind && old.columns == new.columns && (ignore_name || old.name == new.name)
+}
+
+fn drop_key(dialect: Dialect, table: &str, key: &Key) -> String {
+    let table = dialect.quote(table);
+    let name = dialect.quote(&key.name);
+    match (dialect, &key.kind) {
+        (Dialect::Sqlite, _) => format!(
+            "-- SQLite can't drop keys of existing tables, so {table} has to be recreated without {}.",
+            key.definition(dialect)
+        ),
+        (Dialect::Postgres, _) => format!("ALTER TABLE {table} DROP CONSTRAINT {name};"),
+        (Dialect::Mysql, KeyKind::Primary) => format!("ALTER TABLE {table} DROP PRIMARY KEY;"),
+        (Dialect::Mysql, KeyKind::Unique) => format!("ALTER TABLE {table} DROP INDEX {name};"),
+        (Dialect::Mysql, KeyKind::Foreign { .. }) => format!("ALTER TABLE {table} DROP FOREIGN KEY {name};"),
+        (Dialect::Mysql, KeyKind::Check(_)) => format!("ALTER TABLE {table} DROP CHECK {name};"),
+    }
+}
+
+fn add_key(dialect: Dialect, table: &str, key: &Key) -> String {
+    let table = dialect.quote(table);
+    match dialect {
+        Dialect::Sqlite => format!(
+            "-- SQLite can't add keys to existing tables, so {table} has to be recreated with {}.",
+            key.definition(dialect)
+        ),
+        Dialect::Postgres | Dialect::Mysql => format!("ALTER TABLE {table} ADD {};", key.definition(dialect)),
+    }
+}
+
+fn alter_column(dialect: Dialect, table: &str, old: &Column, new: &Column) -> Vec<String> {
+    let quoted = dialect.quote(table);
+    let name = dialect.quote(&new.name);
+    match dialect {
+        Dialect::Sqlite => vec![format!(
+            "-- SQLite can't change columns of existing tables, so {quoted} has to be recreated with {}.",
+            column_definition(dialect, new)
+        )],
+        Dialect::Mysql => vec![format!(
+            "ALTER TABLE {quoted} MODIFY COLUMN {};",
+            column_definition(dialect, new)
+        )],
+        Dialect::Postgres => {
+            let mut statements = Vec::new();
+            if old.ty != new.ty {
+                statements.push(format!(
+                    "ALTER TABLE {quoted} ALTER COLUMN {name} TYPE {};",
+                    dialect.column_type(new.ty)
+                ));
+            }
+            if old.optional != new.optional {
+                let action = if new.optional { "DROP" } else { "SET" };
+                statements.push(format!("ALTER TABLE {quoted} ALTER COLUMN {name} {action} NOT NULL;"));
+            }
+            if old.default != new.default {
+                statements.push(match &new.default {
+                    Some(default) => format!("ALTER TABLE {quoted} ALTER COLUMN {name} SET DEFAULT {default};"),
+                    None => format!("ALTER TABLE {quoted} ALTER COLUMN {name} DROP DEFAULT;"),
+                });
+            }
+            statements
+        }
+    }
+}
+
+/// Writes the statements that turn a database created by [`ddl`] for `old` into one for `new`.
+///
+/// Tables and columns are matched up by the entities, columns and relations they were made for,
+/// so renaming an entity also renames its join tables and the columns referring to it. Keys whose
+/// columns or names change are dropped and added again, along with the foreign keys referring to
+/// them. SQLite can't change the columns or keys of existing tables, so those changes are left
+/// as comments.
+pub fn migration(old: &Module, new: &Module, renames: &Renames, dialect: Dialect) -> String {
+    let old_tables = sort(tables(old, dialect));
+    let new_tables = sort(tables(new, dialect));
+
+    let mut matched: Vec<Matched> = Vec::new();
+    for new_table in &new_tables {
+        let Some(old_table) = old_tables
+            .iter()
+            .find(|table| table.origin.renamed(None, renames) == new_table.origin)
+        else {
+            continue;
+        };
+        let columns = old_table
+            .columns
+            .iter()
+            .filter_map(|old_column| {
+                let origin = old_column.origin.renamed(Some(&old_table.origin), renames);
+                let new_column = new_table.columns.iter().find(|column| column.origin == origin)?;
+                Some((old_column, new_column))
+            })
+            .collect();
+        matched.push(Matched {
+            old: old_table,
+            new: new_table,
+            columns,
+        });
+    }
+
+    let mut renamed = Vec::new();
+    let mut dropped_keys = Vec::new();
+    let mut dropped = Vec::new();
+    let mut created = Vec::new();
+    let mut changed = Vec::new();
+    let mut added_keys = Vec::new();
+
+    // Keys that are dropped, with the table they are dropped from, and keys that are added.
+    let mut drops: Vec<(&str, Key)> = Vec::new();
+    let mut adds: Vec<(&str, Key)> = Vec::new();
+    let mut kept: Vec<(&str, Key, Key)> = Vec::new();
+
+    for table in &matched {
+        let (old, new) = (table.old, table.new);
+        if old.name != new.name {
+            renamed.push(format!(
+                "ALTER TABLE {} RENAME TO {};",
+                dialect.quote(&old.name),
+                dialect.quote(&new.name)
+            ));
+        }
+        for (old_column, new_column) in &table.columns {
+            if old_column.name != new_column.name {
+                renamed.push(format!(
+                    "ALTER TABLE {} RENAME COLUMN {} TO {};",
+                    dialect.quote(&new.name),
+                    dialect.quote(&old_column.name),
+                    dialect.quote(&new_column.name)
+                ));
+            }
+            if old_column.ty != new_column.ty
+                || old_column.optional != new_column.optional
+                || old_column.default != new_column.default
+            {
+                changed.extend(alter_column(dialect, &new.name, old_column, new_column));
+            }
+        }
+
+        let quoted = dialect.quote(&new.name);
+        for old_column in &old.columns {
+            if table.columns.iter().any(|(column, _)| column.name == old_column.name) {
+                continue;
+            }
+            let name = dialect.quote(&old_column.name);
+            let in_key = old.keys().iter().any(|key| key.columns.contains(&old_column.name));
+            dropped.push(if dialect == Dialect::Sqlite && in_key {
+                format!("-- SQLite can't drop columns that are part of a key, so {quoted} has to be recreated without {name}.")
+            } else {
+                format!("ALTER TABLE {quoted} DROP COLUMN {name};")
+            });
+        }
+        for new_column in &new.columns {
+            if table.columns.iter().any(|(_, column)| column.name == new_column.name) {
+                continue;
+            }
+            let definition = column_definition(dialect, new_column);
+            changed.push(if dialect == Dialect::Sqlite && !new_column.optional && new_column.default.is_none() {
+                format!("-- SQLite can't add NOT NULL columns without a default, so {quoted} has to be recreated with {definition}.")
+            } else {
+                format!("ALTER TABLE {quoted} ADD COLUMN {definition};")
+            });
+        }
+
+        let old_keys = table.old_keys(&matched);
+        let new_keys = new.keys();
+        for key in &old_keys {
+            match new_keys.iter().find(|new_key| same_key(dialect, key, new_key)) {
+                Some(new_key) => kept.push((&new.name, key.clone(), new_key.clone())),
+                None => drops.push((&new.name, key.clone())),
+            }
+        }
+        for key in new_keys {
+            if !old_keys.iter().any(|old_key| same_key(dialect, old_key, &key)) {
+                adds.push((&new.name, key));
+            }
+        }
+    }
+
+    // A foreign key has to be dropped before the key it refers to, and added again afterwards.
+    let recreated: Vec<&str> = drops
+        .iter()
+        .filter(|(_, key)| key.kind == KeyKind::Primary)
+        .map(|(table, _)| *table)
+        .collect();
+    for (table, old_key, new_key) in kept {
+        if matches!(&new_key.kind, KeyKind::Foreign { table: parent, .. } if recreated.contains(&parent.as_str())) {
+            drops.push((table, old_key));
+            adds.push((table, new_key));
+        }
+    }
+    // Foreign keys are dropped first and added last, so the keys they refer to exist.
+    let is_foreign = |key: &Key| matches!(key.kind, KeyKind::Foreign { .. });
+    drops.sort_by_key(|(_, key)| !is_foreign(key));
+    adds.sort_by_key(|(_, key)| is_foreign(key));
+    dropped_keys.extend(drops.iter().map(|(table, key)| drop_key(dialect, table, key)));
+
+    for table in old_tables.iter().rev() {
+        if !matched.iter().any(|m| m.old.name == table.name) {
+            dropped.push(format!("DROP TABLE {};", dialect.quote(&table.name)));
+        }
+    }
+
+    let mut new_keys = Vec::new();
+    for table in &new_tables {
+        if matched.iter().any(|m| m.new.name == table.name) {
+            continue;
+        }
+        let inline = |_: &Key| dialect.allows_forward_references();
+        created.push(create_table(dialect, table, inline).trim_end().to_string());
+        if !dialect.allows_forward_references() {
+            new_keys.extend(table.keys().into_iter().filter(is_foreign).map(|key| (&table.name, key)));
+        }
+    }
+    added_keys.extend(adds.iter().map(|(table, key)| add_key(dialect, table, key)));
+    added_keys.extend(new_keys.iter().map(|(table, key)| add_key(dialect, table, key)));
+
+    let mut sections: Vec<String> = [renamed, dropped_keys, dropped]
+        .into_iter()
+        .map(|statements| statements.join("\n"))
+        .collect();
+    sections.extend(created);
+    sections.extend([changed, added_keys].into_iter().map(|statements| statements.join("\n")));
+
+    let mut out = sections
+        .into_iter()
+        .filter(|section| !section.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
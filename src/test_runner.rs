@@ -0,0 +1,119 @@
+use std::{error::Error, fs, io, path::PathBuf};
+
+use crate::{
+    diagnostics::{self, MessageFormat, Reported},
+    enterpreter::{self, Context, EvalError, Limits},
+    parser::{LiteralValue, Module},
+    resolver,
+};
+
+/// Finds the files to test. Files are taken as they are, and directories are searched for
+/// `.dberd` files.
+fn discover(sources: &[String]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending: Vec<PathBuf> = sources.iter().map(PathBuf::from).collect();
+
+    while let Some(path) = pending.pop() {
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+        for entry in fs::read_dir(&path)? {
+            let path = entry?.path();
+            if path.is_dir() || path.extension().is_some_and(|extension| extension == "dberd") {
+                pending.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Lexes, parses and resolves a file, printing its diagnostics if it has any errors.
+fn load(format: MessageFormat, source: &'static str, source_text: &str) -> Result<Module, Box<dyn Error>> {
+    let errors = match diagnostics::lex(source_text)
+        .and_then(|tokens| diagnostics::parse(source_text, tokens))
+    {
+        Ok(module) => {
+            let errors = resolver::resolve(&module).diagnostics;
+            if errors.is_empty() {
+                return Ok(module);
+            }
+            errors
+        }
+        Err(errors) => errors,
+    };
+
+    for error in errors {
+        error.emit(format, source, source_text)?;
+    }
+    Err(format!("{source} could not be compiled").into())
+}
+
+/// Runs every test declared in `sources` whose name contains `filter`, each in a context of its
+/// own, and fails if any of them fail.
+pub fn run(
+    sources: &[String],
+    filter: Option<&str>,
+    limits: &Limits,
+    format: MessageFormat,
+) -> Result<(), Box<dyn Error>> {
+    let (mut passed, mut failed, mut filtered) = (0, 0, 0);
+
+    for path in discover(sources)? {
+        let source: &'static str = Box::leak(path.display().to_string().into_boxed_str());
+        let source_text = fs::read_to_string(&path)?;
+        let module = match load(format, source, &source_text) {
+            Ok(module) => module,
+            Err(error) => {
+                println!("error: {error}");
+                failed += 1;
+                continue;
+            }
+        };
+
+        for test in module.tests {
+            let name = &test.name.0;
+            if filter.is_some_and(|filter| !name.contains(filter)) {
+                filtered += 1;
+                continue;
+            }
+
+            let span = test.body.1.clone();
+            let result = match enterpreter::evaluate(test.body, &Context::default(), limits) {
+                Ok(LiteralValue::Boolean(false)) => Err(EvalError::AssertionFailed {
+                    actual: Box::new((LiteralValue::Boolean(false), span)),
+                    expected: None,
+                }),
+                Ok(_) => Ok(()),
+                Err(error) => Err(error),
+            };
+
+            match result {
+                Ok(()) => {
+                    println!("test {source}: {name:?} ... ok");
+                    passed += 1;
+                }
+                Err(error) => {
+                    println!("test {source}: {name:?} ... FAILED");
+                    match error.diagnostic() {
+                        Some(diagnostic) => diagnostic.emit(format, source, &source_text)?,
+                        None => diagnostics::emit_error(format, Some(source), error.code(), &error.to_string()),
+                    }
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {passed} passed; {failed} failed; {filtered} filtered out",
+        if failed == 0 { "ok" } else { "FAILED" }
+    );
+    if failed > 0 {
+        return Err(Reported(format!("{failed} test(s) failed")).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,239 @@
+//! The types of the expressions in column defaults and checks, which have to fit their column
+//! before any row is ever checked.
+
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    diagnostics::{Diagnostic, Label, Severity},
+    enterpreter::{self, Context},
+    lexer::{Span, Spanned},
+    number::Number,
+    parser::{BinaryOp, Expr, LiteralValue, BUILTINS},
+    schema::{self, ColumnType},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Float,
+    /// An exact number that may not be an integer, like `1 / 3`.
+    Number,
+    String,
+    Bool,
+    Array,
+    Record,
+    Function,
+    /// Anything, like what a function bound with `let` returns. It is only checked once the
+    /// expression is evaluated.
+    Unknown,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "an integer"),
+            Type::Float => write!(f, "a float"),
+            Type::Number => write!(f, "a fraction"),
+            Type::String => write!(f, "a string"),
+            Type::Bool => write!(f, "a boolean"),
+            Type::Array => write!(f, "an array"),
+            Type::Record => write!(f, "a record"),
+            Type::Function => write!(f, "a function"),
+            Type::Unknown => write!(f, "anything"),
+        }
+    }
+}
+
+impl From<ColumnType> for Type {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Int => Type::Int,
+            ColumnType::Float => Type::Float,
+            ColumnType::String => Type::String,
+            ColumnType::Bool => Type::Bool,
+        }
+    }
+}
+
+impl Type {
+    fn is_numeric(self) -> bool {
+        matches!(self, Type::Int | Type::Float | Type::Number | Type::Unknown)
+    }
+
+    /// Whether a value of this type can be stored in a column of type `column`. Float columns
+    /// take any number.
+    pub fn fits(self, column: ColumnType) -> bool {
+        match (self, column) {
+            (Type::Unknown, _) => true,
+            (ty, ColumnType::Float) => ty.is_numeric(),
+            (ty, column) => ty == Type::from(column),
+        }
+    }
+}
+
+/// The type of a value, e.g. one a default evaluated to.
+pub fn of(value: &LiteralValue) -> Type {
+    match value {
+        LiteralValue::Number(Number::Integer(_) | Number::Big(_)) => Type::Int,
+        LiteralValue::Number(Number::Rational(_)) => Type::Number,
+        LiteralValue::Number(Number::Float(_)) => Type::Float,
+        LiteralValue::String(_) => Type::String,
+        LiteralValue::Boolean(_) => Type::Bool,
+        LiteralValue::Array(_) => Type::Array,
+        LiteralValue::Record(_) => Type::Record,
+        LiteralValue::Function { .. } => Type::Function,
+    }
+}
+
+pub fn mismatch(span: Span, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        code: "E0012",
+        message: "Type mismatch found.".into(),
+        span: span.clone(),
+        labels: vec![Label { span, message }],
+        help: None,
+    }
+}
+
+/// Checks that the expression at `span` has a type that `ok` accepts.
+fn expect(ty: Type, span: &Span, expected: &str, ok: impl Fn(Type) -> bool) -> Result<Type, Diagnostic> {
+    if ty == Type::Unknown || ok(ty) {
+        Ok(ty)
+    } else {
+        Err(mismatch(span.clone(), format!("Expected {expected}, but this is {ty}.")))
+    }
+}
+
+/// The type of numbers added or divided: floats win, and exact numbers stay exact.
+fn arithmetic(lhs: Type, rhs: Type, integers_stay_integers: bool) -> Type {
+    match (lhs, rhs) {
+        (Type::Float, _) | (_, Type::Float) => Type::Float,
+        (Type::Unknown, _) | (_, Type::Unknown) => Type::Unknown,
+        (Type::Int, Type::Int) if integers_stay_integers => Type::Int,
+        _ => Type::Number,
+    }
+}
+
+/// The type of a division that only involves constants, like `4 / 2`, which is an integer when
+/// the division is exact. Divisions that fail, like `1 / 0`, are left for their evaluation to
+/// report.
+fn constant_division(division: &Spanned<Expr>, scope: &HashMap<String, Type>) -> Option<Type> {
+    let constant = schema::free_names(division)
+        .iter()
+        .all(|name| BUILTINS.contains(name) && !scope.contains_key(*name));
+    if !constant {
+        return None;
+    }
+    Some(match enterpreter::evaluate(division.clone(), &Context::default(), &schema::limits()) {
+        Ok(value) => of(&value),
+        Err(_) => Type::Unknown,
+    })
+}
+
+/// Infers the type of `expr` with `scope` bound, reporting the first expression that doesn't fit
+/// where it is used. Identifiers that aren't bound are left to the resolver to report.
+pub fn infer(spanned: &Spanned<Expr>, scope: &HashMap<String, Type>) -> Result<Type, Diagnostic> {
+    let (expr, span) = spanned;
+    match expr {
+        Expr::Literal(LiteralValue::Array(items)) => {
+            for item in items {
+                infer(item, scope)?;
+            }
+            Ok(Type::Array)
+        }
+        Expr::Literal(LiteralValue::Function { params, body }) => {
+            let mut scope = scope.clone();
+            for param in params {
+                if let (Expr::Ident(name), _) = param {
+                    scope.insert(name.clone(), Type::Unknown);
+                }
+            }
+            infer(body, &scope)?;
+            Ok(Type::Function)
+        }
+        Expr::Literal(value) => Ok(of(value)),
+        Expr::Ident(name) => Ok(scope.get(name).copied().unwrap_or(Type::Unknown)),
+        Expr::Let { ident, value, body } => {
+            let mut scope = scope.clone();
+            if let (Expr::Ident(name), _) = ident.as_ref() {
+                let ty = infer(value, &scope)?;
+                scope.insert(name.clone(), ty);
+            }
+            infer(body, &scope)
+        }
+        Expr::Grouping(inner) => infer(inner, scope),
+        Expr::Binary { op, lhs, rhs } => {
+            let (lhs_ty, rhs_ty) = (infer(lhs, scope)?, infer(rhs, scope)?);
+            match op {
+                BinaryOp::Add | BinaryOp::Divide => {
+                    expect(lhs_ty, &lhs.1, "a number", Type::is_numeric)?;
+                    expect(rhs_ty, &rhs.1, "a number", Type::is_numeric)?;
+                    match (op, lhs_ty, rhs_ty) {
+                        (BinaryOp::Divide, Type::Int, Type::Int) => {
+                            Ok(constant_division(spanned, scope).unwrap_or(Type::Number))
+                        }
+                        _ => Ok(arithmetic(lhs_ty, rhs_ty, *op == BinaryOp::Add)),
+                    }
+                }
+                _ => {
+                    expect(lhs_ty, &lhs.1, "an integer", |ty| ty == Type::Int)?;
+                    expect(rhs_ty, &rhs.1, "an integer", |ty| ty == Type::Int)?;
+                    Ok(Type::Int)
+                }
+            }
+        }
+        Expr::Call { fun, args } => {
+            let types = args
+                .iter()
+                .map(|arg| infer(arg, scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (Expr::Ident(name), _) = fun.as_ref() else {
+                return Ok(Type::Unknown);
+            };
+            if scope.contains_key(name) {
+                return Ok(Type::Unknown);
+            }
+            builtin(name, span, args, &types)
+        }
+    }
+}
+
+/// The type of a call to a builtin, checking its arguments.
+fn builtin(name: &str, span: &Span, args: &[Spanned<Expr>], types: &[Type]) -> Result<Type, Diagnostic> {
+    let (expected, arity): (&str, usize) = match name {
+        "trunc" | "round" | "float" | "num" | "den" => ("a number", 1),
+        "add" | "lt" | "le" | "gt" | "ge" => ("a number", 2),
+        "not" | "assert" => ("a boolean", 1),
+        "and" | "or" => ("a boolean", 2),
+        "len" | "from_json" => ("a string", 1),
+        "to_json" => ("anything", 1),
+        "eq" | "assert_eq" => ("anything", 2),
+        _ => return Ok(Type::Unknown),
+    };
+    if args.len() != arity {
+        let s = if arity == 1 { "" } else { "s" };
+        return Err(mismatch(
+            span.clone(),
+            format!("'{name}' takes {arity} argument{s}, but is given {}.", args.len()),
+        ));
+    }
+
+    for (arg, ty) in args.iter().zip(types) {
+        match expected {
+            "a number" => expect(*ty, &arg.1, expected, Type::is_numeric)?,
+            "a boolean" => expect(*ty, &arg.1, expected, |ty| ty == Type::Bool)?,
+            "a string" => expect(*ty, &arg.1, expected, |ty| ty == Type::String)?,
+            _ => *ty,
+        };
+    }
+
+    Ok(match name {
+        "trunc" | "round" | "num" | "den" | "len" => Type::Int,
+        "float" => Type::Float,
+        "from_json" => Type::Unknown,
+        "to_json" => Type::String,
+        "add" => arithmetic(types[0], types[1], true),
+        _ => Type::Bool,
+    })
+}
@@ -0,0 +1,388 @@
+//! `dberd validate`: checks sample data against a schema before it is used to seed a database.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use num_bigint::BigInt;
+use serde_json::Value;
+
+use crate::{
+    diagnostics::MessageFormat,
+    enterpreter::{self, Context},
+    formatter,
+    number::Number,
+    parser::{LiteralValue, Module},
+    schema::{self, ColumnType},
+    sql::{self, Dialect, KeyKind, Table},
+    types,
+};
+
+/// Something wrong with a row of sample data.
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub file: String,
+    /// The 1-based number of the row in its file, not counting the header of CSV files.
+    pub row: usize,
+    /// The column the violation is about, if it is about a single one.
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn emit(&self, format: MessageFormat) {
+        match format {
+            MessageFormat::Human => match &self.column {
+                Some(column) => eprintln!("{}: row {}, column '{column}': {}", self.file, self.row, self.message),
+                None => eprintln!("{}: row {}: {}", self.file, self.row, self.message),
+            },
+            MessageFormat::Json => eprintln!(
+                "{}",
+                serde_json::json!({
+                    "file": self.file,
+                    "row": self.row,
+                    "column": self.column,
+                    "message": self.message,
+                })
+            ),
+        }
+    }
+}
+
+/// A row as it was read, with the columns whose values couldn't be read at all.
+struct Row {
+    values: HashMap<String, LiteralValue>,
+    invalid: HashSet<String>,
+}
+
+/// The rows of one table, read from a data file.
+struct Data<'a> {
+    file: String,
+    table: &'a Table,
+    rows: Vec<Row>,
+}
+
+/// Finds the data files to validate. Files are taken as they are, and directories are searched
+/// for `.json` and `.csv` files.
+fn discover(sources: &[String]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending: Vec<PathBuf> = sources.iter().map(PathBuf::from).collect();
+
+    while let Some(path) = pending.pop() {
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+        for entry in fs::read_dir(&path)? {
+            let path = entry?.path();
+            if path.is_dir() || path.extension().is_some_and(|extension| extension == "json" || extension == "csv") {
+                pending.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Finds the table a file holds the rows of, by its name without the extension. The case of the
+/// name doesn't matter unless that makes it ambiguous.
+fn table_for<'a>(tables: &'a [Table], path: &Path) -> Result<&'a Table, String> {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    if let Some(table) = tables.iter().find(|table| table.name == stem) {
+        return Ok(table);
+    }
+    let matching: Vec<_> = tables
+        .iter()
+        .filter(|table| table.name.eq_ignore_ascii_case(stem))
+        .collect();
+    match matching.as_slice() {
+        [table] => Ok(table),
+        _ => Err(format!(
+            "{} doesn't hold the rows of any table; name it after one of {}",
+            path.display(),
+            tables.iter().map(|table| table.name.as_str()).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Reads a JSON value into a column of type `ty`, or says why it can't be.
+fn json_value(value: &Value, ty: ColumnType) -> Result<Option<LiteralValue>, String> {
+    let value = match value {
+        Value::Null => return Ok(None),
+        Value::Bool(boolean) => LiteralValue::Boolean(*boolean),
+        Value::String(string) => LiteralValue::String(string.clone()),
+        Value::Number(number) => LiteralValue::Number(match (number.as_i64(), number.as_f64()) {
+            (Some(integer), _) => Number::Integer(integer),
+            _ if number.is_u64() => Number::Big(BigInt::from(number.as_u64().unwrap())),
+            (_, Some(float)) => Number::Float(float),
+            _ => return Err(format!("{number} is out of range")),
+        }),
+        Value::Array(_) => return Err("Expected a value, but found an array".into()),
+        Value::Object(_) => return Err("Expected a value, but found an object".into()),
+    };
+    fit(value, ty).map(Some)
+}
+
+/// Reads a CSV field into a column of type `ty`, or says why it can't be. Empty fields have no
+/// value, like `NULL`.
+fn csv_value(field: &str, ty: ColumnType) -> Result<Option<LiteralValue>, String> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let value = match ty {
+        ColumnType::String => LiteralValue::String(field.into()),
+        ColumnType::Int => match (field.parse::<i64>(), field.parse::<BigInt>()) {
+            (Ok(integer), _) => LiteralValue::Number(Number::Integer(integer)),
+            (_, Ok(big)) => LiteralValue::Number(Number::Big(big)),
+            _ => return Err(format!("Expected an integer, but found '{field}'")),
+        },
+        ColumnType::Float => match field.parse::<f64>() {
+            Ok(float) if float.is_finite() => LiteralValue::Number(Number::Float(float)),
+            _ => return Err(format!("Expected a float, but found '{field}'")),
+        },
+        ColumnType::Bool => match field.to_ascii_lowercase().as_str() {
+            "true" | "t" | "1" => LiteralValue::Boolean(true),
+            "false" | "f" | "0" => LiteralValue::Boolean(false),
+            _ => return Err(format!("Expected a boolean, but found '{field}'")),
+        },
+    };
+    Ok(Some(value))
+}
+
+/// Checks that a value fits a column of type `ty`. Numbers in float columns become floats, so
+/// that `1` and `1.0` are the same value there.
+fn fit(value: LiteralValue, ty: ColumnType) -> Result<LiteralValue, String> {
+    let found = types::of(&value);
+    if !found.fits(ty) {
+        return Err(format!("Expected {}, but found {found}", types::Type::from(ty)));
+    }
+    Ok(match value {
+        LiteralValue::Number(number) if ty == ColumnType::Float => LiteralValue::Number(number.to_finite_float()?),
+        value => value,
+    })
+}
+
+/// Reads the rows of a data file, reporting values that don't fit their column.
+fn read(path: &Path, table: &Table, violations: &mut Vec<Violation>) -> Result<Vec<Row>, Box<dyn Error>> {
+    let file = path.display().to_string();
+    let mut violate = |row: usize, column: Option<&str>, message: String| {
+        violations.push(Violation {
+            file: file.clone(),
+            row,
+            column: column.map(Into::into),
+            message,
+        })
+    };
+    let ty = |name: &str| table.columns.iter().find(|column| column.name == name).map(|column| column.ty);
+
+    let mut rows = Vec::new();
+    let mut read_row = |number: usize, fields: Vec<(String, Result<Option<LiteralValue>, String>)>| {
+        let mut row = Row {
+            values: HashMap::new(),
+            invalid: HashSet::new(),
+        };
+        for (name, value) in fields {
+            match value {
+                Ok(Some(value)) => {
+                    row.values.insert(name, value);
+                }
+                Ok(None) => {}
+                Err(message) => {
+                    violate(number, Some(&name), format!("{message}."));
+                    row.invalid.insert(name);
+                }
+            }
+        }
+        rows.push(row);
+    };
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => {
+            let json: Value = serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|error| format!("{file} is not valid JSON: {error}"))?;
+            let Value::Array(items) = json else {
+                return Err(format!("{file} has to hold an array of rows").into());
+            };
+            for (i, item) in items.iter().enumerate() {
+                let Value::Object(object) = item else {
+                    return Err(format!("Row {} of {file} has to be an object", i + 1).into());
+                };
+                let fields = object
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = match ty(name) {
+                            Some(ty) => json_value(value, ty),
+                            None => Err(format!("'{}' has no such column", table.name)),
+                        };
+                        (name.clone(), value)
+                    })
+                    .collect();
+                read_row(i + 1, fields);
+            }
+        }
+        Some("csv") => {
+            let mut reader = csv::Reader::from_path(path).map_err(|error| format!("Can't read {file}: {error}"))?;
+            let headers = reader.headers().map_err(|error| format!("Can't read {file}: {error}"))?.clone();
+            for (i, record) in reader.records().enumerate() {
+                let record = record.map_err(|error| format!("Can't read {file}: {error}"))?;
+                let fields = headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(name, field)| {
+                        let value = match ty(name) {
+                            Some(ty) => csv_value(field, ty),
+                            None => Err(format!("'{}' has no such column", table.name)),
+                        };
+                        (name.to_string(), value)
+                    })
+                    .collect();
+                read_row(i + 1, fields);
+            }
+        }
+        _ => return Err(format!("{file} is neither a .json nor a .csv file").into()),
+    }
+    Ok(rows)
+}
+
+/// Shows a value the way it would be written in source.
+fn show(value: &LiteralValue) -> String {
+    formatter::format_value(value, 60, None)
+}
+
+/// Shows the values of some columns of a row, or `None` if any of them has no value.
+fn key_values(row: &Row, columns: &[String]) -> Option<String> {
+    let values: Option<Vec<String>> = columns.iter().map(|column| row.values.get(column).map(show)).collect();
+    values.map(|values| values.join(", "))
+}
+
+/// Checks the rows in the data files `sources` against the tables that `module` would be written
+/// out as, so foreign key and join table columns are included.
+///
+/// Every value has to fit its column, columns that aren't optional and have no default need a
+/// value, keys have to be unique, foreign keys have to refer to rows that are there, and the
+/// checks of each entity have to hold. Foreign keys into tables without a data file aren't
+/// checked, since their rows aren't known.
+pub fn validate(module: &Module, sources: &[String]) -> Result<Vec<Violation>, Box<dyn Error>> {
+    let tables = sql::tables(module, Dialect::Postgres);
+    let mut violations = Vec::new();
+
+    let mut data: Vec<Data> = Vec::new();
+    for path in discover(sources)? {
+        let table = table_for(&tables, &path)?;
+        let file = path.display().to_string();
+        if let Some(other) = data.iter().find(|data| data.table.name == table.name) {
+            return Err(format!("{} and {file} both hold the rows of '{}'", other.file, table.name).into());
+        }
+        let rows = read(&path, table, &mut violations)?;
+        data.push(Data { file, table, rows });
+    }
+
+    for Data { file, table, rows } in &mut data {
+        let mut violate = |row: usize, column: &str, message: String| {
+            violations.push(Violation {
+                file: file.clone(),
+                row,
+                column: Some(column.into()),
+                message,
+            })
+        };
+        let entity = module.entities.iter().find(|entity| entity.name.0 == table.name);
+
+        for (i, row) in rows.iter_mut().enumerate() {
+            for column in &table.columns {
+                if row.values.contains_key(&column.name) || row.invalid.contains(&column.name) {
+                    continue;
+                }
+                let default = entity
+                    .and_then(|entity| entity.columns.iter().find(|c| c.name.0 == column.name))
+                    .and_then(|column| column.default.clone());
+                match default {
+                    Some(default) => match enterpreter::evaluate(default, &Context::default(), &schema::limits()) {
+                        Ok(value) => {
+                            row.values.insert(column.name.clone(), value);
+                        }
+                        Err(error) => violate(i + 1, &column.name, format!("The default failed: {error}.")),
+                    },
+                    None if !column.optional => {
+                        violate(i + 1, &column.name, "A value is required, since the column isn't optional.".into());
+                    }
+                    None => {}
+                }
+            }
+
+            let Some(entity) = entity else {
+                continue;
+            };
+            for (check, error) in schema::failed_checks(entity, &row.values) {
+                let column = entity
+                    .columns
+                    .iter()
+                    .find(|column| column.checks.iter().any(|other| std::ptr::eq(other, check)))
+                    .map_or("", |column| column.name.0.as_str());
+                let check = formatter::format_expr(check, 60);
+                violate(
+                    i + 1,
+                    column,
+                    match error {
+                        Some(error) => format!("The check {check} failed: {error}."),
+                        None => format!("The check {check} doesn't hold."),
+                    },
+                );
+            }
+        }
+
+        for key in table.keys() {
+            if matches!(key.kind, KeyKind::Primary | KeyKind::Unique) {
+                let mut seen: HashMap<String, usize> = HashMap::new();
+                for (i, row) in rows.iter().enumerate() {
+                    let Some(values) = key_values(row, &key.columns) else {
+                        continue;
+                    };
+                    if let Some(first) = seen.get(&values) {
+                        let kind = if key.kind == KeyKind::Primary { "primary key" } else { "unique key" };
+                        violate(
+                            i + 1,
+                            &key.columns.join(", "),
+                            format!("{values} is already used by row {first}, but it is a {kind}."),
+                        );
+                    } else {
+                        seen.insert(values, i + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    for child in &data {
+        for key in child.table.keys() {
+            let KeyKind::Foreign { table, references } = &key.kind else {
+                continue;
+            };
+            let Some(parent) = data.iter().find(|data| data.table.name == *table) else {
+                continue;
+            };
+            let existing: HashSet<String> = parent.rows.iter().filter_map(|row| key_values(row, references)).collect();
+            for (i, row) in child.rows.iter().enumerate() {
+                let Some(values) = key_values(row, &key.columns) else {
+                    continue;
+                };
+                if !existing.contains(&values) {
+                    violations.push(Violation {
+                        file: child.file.clone(),
+                        row: i + 1,
+                        column: Some(key.columns.join(", ")),
+                        message: format!("{values} doesn't refer to any row of '{table}' in {}.", parent.file),
+                    });
+                }
+            }
+        }
+    }
+
+    violations.sort_by(|a, b| (&a.file, a.row).cmp(&(&b.file, b.row)));
+    Ok(violations)
+}
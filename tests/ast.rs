@@ -0,0 +1,47 @@
+//! Modules survive being written as AST JSON and read back.
+mod common;
+
+use common::{dberd, examples, parse};
+use dberd::{ast, formatter};
+
+#[test]
+fn json_round_trips() {
+    for (name, source) in examples() {
+        let (_, module) = parse(&name, &source);
+        let json = ast::to_json(&module);
+        let loaded = ast::from_json(&json).unwrap_or_else(|error| panic!("{name}: {error}"));
+        assert_eq!(ast::to_json(&loaded), json, "{name}");
+        assert_eq!(formatter::format_module(&loaded, 80), formatter::format_module(&module, 80), "{name}");
+    }
+}
+
+#[test]
+fn loaded_modules_run_the_same() {
+    for (name, source) in examples() {
+        let (_, module) = parse(&name, &source);
+        let file = std::env::temp_dir().join(format!("dberd-ast-{}-{name}.json", std::process::id()));
+        std::fs::write(&file, serde_json::to_string(&ast::to_json(&module)).unwrap()).unwrap();
+
+        let from_source = dberd().arg("run").arg(common::path(&format!("examples/{name}"))).output().unwrap();
+        let from_json = dberd().args(["run", "--input", "ast-json"]).arg(&file).output().unwrap();
+        std::fs::remove_file(file).unwrap();
+        assert_eq!(from_json.status.code(), from_source.status.code(), "{name}");
+        assert_eq!(
+            String::from_utf8(from_json.stdout).unwrap(),
+            String::from_utf8(from_source.stdout).unwrap(),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn errors_say_where() {
+    let (_, module) = parse("call", "{} -> :add{1, 2}");
+    let mut json = ast::to_json(&module);
+    json["expr"]["value"]["body"]["args"][1]["kind"] = "nonsense".into();
+    let error = ast::from_json(&json).unwrap_err();
+    assert!(error.contains("$.expr.value.body.args[1]"), "{error}");
+
+    json["version"] = (ast::VERSION + 1).into();
+    assert!(ast::from_json(&json).is_err());
+}
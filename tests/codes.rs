@@ -0,0 +1,70 @@
+//! Every diagnostic code has an explanation whose examples do what they say.
+mod common;
+
+use dberd::codes::{self, Explanation, EXPLANATIONS};
+
+/// The codes that appear in the source of the crate, apart from the explanations themselves.
+fn codes_in_source() -> Vec<String> {
+    let mut codes = Vec::new();
+    let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    for entry in std::fs::read_dir(src).unwrap() {
+        let path = entry.unwrap().path();
+        if path.file_name().unwrap() == "codes.rs" {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+        for (i, _) in source.match_indices('"') {
+            let code = source[i + 1..].split('"').next().unwrap();
+            let is_code = code.len() == 5
+                && (code.starts_with('E') || code.starts_with('W'))
+                && code[1..].bytes().all(|byte| byte.is_ascii_digit());
+            if is_code && !codes.iter().any(|known| known == code) {
+                codes.push(code.to_string());
+            }
+        }
+    }
+    codes.sort();
+    codes
+}
+
+#[test]
+fn emitted_codes_are_explained() {
+    let emitted = codes_in_source();
+    let explained: Vec<&str> = EXPLANATIONS
+        .iter()
+        .filter(|explanation| explanation.emitted)
+        .map(|explanation| explanation.code)
+        .collect();
+    assert_eq!(emitted, explained);
+    for code in emitted {
+        assert!(codes::explanation(&code).is_some(), "{code}");
+    }
+}
+
+/// Runs the command of an explanation on an example, returning the output.
+fn check(command: &str, code: &str, example: &str, name: &str) -> String {
+    let file = std::env::temp_dir().join(format!("dberd-codes-{}-{code}-{name}", std::process::id()));
+    std::fs::write(&file, example).unwrap();
+    let mut args = command.split_whitespace();
+    assert_eq!(args.next(), Some("dberd"));
+    let output = common::dberd().args(args).arg(&file).output().unwrap();
+    std::fs::remove_file(file).unwrap();
+    String::from_utf8(output.stdout).unwrap() + &String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn examples_do_what_they_say() {
+    for explanation in EXPLANATIONS {
+        let Explanation { code, command, failing, passing, .. } = *explanation;
+        let reported = format!("[{code}]");
+        let output = check(command, code, failing, "failing");
+        if explanation.emitted {
+            assert!(output.contains(&reported), "{code}: {failing:?} isn't reported:\n{output}");
+        } else {
+            assert!(!output.contains(&reported), "{code} is retired, but still reported:\n{output}");
+        }
+        let output = check(command, code, passing, "passing");
+        assert!(!output.contains("Error"), "{code}: {passing:?} fails:\n{output}");
+        assert!(!output.contains(&reported), "{code}: {passing:?} is reported:\n{output}");
+    }
+}
@@ -0,0 +1,60 @@
+//! Helpers shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use dberd::{
+    diagnostics,
+    lexer::{Spanned, Token},
+    parser::Module,
+};
+
+/// A path relative to the `tests` directory.
+pub fn path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(relative)
+}
+
+/// The name and source of every example in `tests/examples`, sorted by name.
+pub fn examples() -> Vec<(String, String)> {
+    let mut examples: Vec<_> = std::fs::read_dir(path("examples"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "dberd"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read_to_string(path).unwrap())
+        })
+        .collect();
+    examples.sort();
+    assert!(!examples.is_empty());
+    examples
+}
+
+/// Lexes and parses a source file, failing the test if it has errors.
+pub fn parse(name: &str, source: &str) -> (Vec<Spanned<Token>>, Module) {
+    let tokens = diagnostics::lex(source).unwrap_or_else(|errors| panic!("{name} doesn't lex: {errors:?}"));
+    let module = diagnostics::parse(source, tokens.clone()).unwrap_or_else(|errors| panic!("{name} doesn't parse: {errors:?}"));
+    (tokens, module)
+}
+
+/// The path of the `dberd` binary, for tests that run it like a user would.
+pub fn dberd() -> std::process::Command {
+    std::process::Command::new(env!("CARGO_BIN_EXE_dberd"))
+}
+
+/// Compares `actual` with the golden file at `relative`, byte for byte. Run the tests with
+/// `DBERD_BLESS=1` to write the golden files instead, after checking that the new output is right.
+pub fn assert_golden(relative: &str, actual: &str) {
+    let path = path(relative);
+    if std::env::var_os("DBERD_BLESS").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("can't read {}: {error}; run with DBERD_BLESS=1 to create it", path.display()));
+    assert!(
+        expected == actual,
+        "{} doesn't match, run with DBERD_BLESS=1 to update it:\n--- expected\n{expected}\n--- actual\n{actual}",
+        path.display()
+    );
+}
@@ -0,0 +1,51 @@
+//! Replays recorded sessions of a debugger client against the debug adapter, comparing everything
+//! it answers with the `.expected.json` file next to each session.
+
+mod common;
+
+use std::io::Cursor;
+
+use dberd::{
+    dap,
+    lsp::{read_message, write_message},
+};
+use serde_json::Value;
+
+fn replay(session: &str) {
+    let requests: Vec<Value> =
+        serde_json::from_str(&std::fs::read_to_string(common::path(&format!("dap/{session}.json"))).unwrap()).unwrap();
+    let mut input = Vec::new();
+    for request in &requests {
+        write_message(&mut input, request).unwrap();
+    }
+
+    let mut output = Vec::new();
+    dap::serve(Cursor::new(input), &mut output).unwrap();
+    let mut output = Cursor::new(output);
+    let mut messages = Vec::new();
+    while let Some(message) = read_message(&mut output).unwrap() {
+        messages.push(message);
+    }
+
+    let actual = serde_json::to_string_pretty(&messages).unwrap() + "\n";
+    common::assert_golden(&format!("dap/{session}.expected.json"), &actual);
+}
+
+/// Stops at a breakpoint inside a function, inspects the stack and its variables, then runs to the
+/// end.
+#[test]
+fn breakpoint() {
+    replay("breakpoint");
+}
+
+/// Stops on entry, then steps over and into expressions and disconnects while stopped.
+#[test]
+fn stepping() {
+    replay("stepping");
+}
+
+/// Requests that can't be answered fail without ending the session.
+#[test]
+fn errors() {
+    replay("errors");
+}
@@ -0,0 +1,71 @@
+//! `dberd debug` driven by commands on stdin, and the steps `dberd tokenize --trace` prints.
+mod common;
+
+use std::{io::Write, process::Stdio};
+
+use common::{assert_golden, path};
+
+/// Runs `dberd` from the `tests` directory with `stdin`, so that the paths it prints are the same
+/// everywhere. Returns whether it succeeded, stdout and stderr.
+fn run(args: &[&str], stdin: &str) -> (bool, String, String) {
+    let mut child = common::dberd()
+        .args(args)
+        .current_dir(path(""))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// Debugs `debugger/program.dberd` with `flags`, typing `commands`, and compares what the
+/// debugger printed with `debugger/{name}.stdout`.
+fn session(name: &str, flags: &[&str], commands: &[&str]) {
+    let args = [&["debug"], flags, &["debugger/program.dberd"]].concat();
+    let stdin: String = commands.iter().map(|command| format!("{command}\n")).collect();
+    let (success, stdout, stderr) = run(&args, &stdin);
+    assert!(success, "{name}: {stderr}");
+    assert_eq!(stderr, "", "{name}");
+    assert_golden(&format!("debugger/{name}.stdout"), &stdout);
+}
+
+#[test]
+fn stepping() {
+    session("stepping", &[], &["s", "l", "p double", "p nope", "s", "s", "n", "w", "c"]);
+}
+
+#[test]
+fn breakpoints() {
+    session("breakpoints", &["--break", "4"], &["l", "c", "c"]);
+    session("breakpoint_commands", &[], &["b 5", "b five", "c", "l", "d 5", "d 5", "c"]);
+}
+
+#[test]
+fn unknown_commands_show_help() {
+    session("help", &[], &["frobnicate", "c"]);
+}
+
+#[test]
+fn quitting_is_not_an_error() {
+    session("quit", &[], &["q"]);
+    // Running out of commands quits as well.
+    let (success, stdout, stderr) = run(&["debug", "debugger/program.dberd"], "");
+    assert!(success, "{stderr}");
+    assert_eq!(stderr, "");
+    assert!(stdout.ends_with("(debug) "), "{stdout}");
+}
+
+#[test]
+fn trace() {
+    let (success, stdout, stderr) = run(&["tokenize", "--trace", "debugger/program.dberd"], "");
+    assert!(success, "{stderr}");
+    assert!(stdout.ends_with("\n5\n"), "{stdout}");
+    assert_golden("debugger/trace.stderr", &stderr);
+}
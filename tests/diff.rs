@@ -0,0 +1,62 @@
+//! What `dberd diff` says changed between two schemas.
+mod common;
+
+use dberd::diff;
+
+fn summary(old: &str, new: &str) -> String {
+    let (_, old) = common::parse("old", old);
+    let (_, new) = common::parse("new", new);
+    let renames = diff::renames(&old, &new, true).unwrap();
+    diff::summary(&old, &new, &renames)
+}
+
+const NOTE: &str = "\nGuessed renames pair up what was removed with what was added that is most like it. \
+                    Annotate the new name with `was old_name` to pair it up differently, or pass \
+                    --no-guess if nothing was renamed.\n";
+
+#[test]
+fn similar_entities_are_renames() {
+    let old = "entity Purchase { id: int pk, total: float, note: string }";
+    let new = "entity Order { id: int pk, total: float, note: string, paid: bool }";
+    assert_eq!(
+        summary(old, new),
+        format!("~ entity Purchase renamed to Order (guessed)\n~ entity Order\n    + paid: bool\n{NOTE}")
+    );
+}
+
+#[test]
+fn different_entities_are_not_renames() {
+    let old = "entity Purchase { id: int pk, total: float }";
+    let new = "entity Tag { name: string pk, color: string }";
+    assert_eq!(summary(old, new), "- entity Purchase\n+ entity Tag\n");
+}
+
+#[test]
+fn ambiguous_renames_are_not_guessed() {
+    let old = "entity Purchase { id: int pk }";
+    let new = "entity Order { id: int pk }\nentity Sale { id: int pk }";
+    assert_eq!(summary(old, new), "- entity Purchase\n+ entity Order\n+ entity Sale\n");
+}
+
+#[test]
+fn similar_columns_are_renames() {
+    let old = "entity User { id: int pk, nick: string optional }";
+    let new = "entity User { id: int pk, nickname: string optional unique }";
+    assert_eq!(
+        summary(old, new),
+        format!(
+            "~ entity User\n    ~ nick renamed to nickname (guessed)\n    \
+             ~ nickname: string optional -> string optional unique\n{NOTE}"
+        )
+    );
+}
+
+#[test]
+fn removed_relations_keep_their_names() {
+    let old = "entity User { id: int pk }\nentity Purchase { id: int pk }\nrelation User 0..* Purchase";
+    let new = "entity User { id: int pk }\nentity Order was Purchase { id: int pk }";
+    assert_eq!(
+        summary(old, new),
+        "~ entity Purchase renamed to Order\n- relation User 0..* Purchase\n"
+    );
+}
@@ -0,0 +1,41 @@
+//! Golden files of the diagrams `dberd export` draws.
+mod common;
+
+use common::{assert_golden, parse, path};
+use dberd::{
+    export::{self, Format},
+    sql::Dialect,
+};
+
+fn diagrams(relative: &str, name: &str) {
+    let source = std::fs::read_to_string(path(relative)).unwrap();
+    let (_, module) = parse(relative, &source);
+    for (format, extension) in [(Format::Dot, "dot"), (Format::Mermaid, "mmd")] {
+        let exported = export::export(&module, format, Dialect::default());
+        assert_golden(&format!("export/{name}.{extension}"), &exported);
+    }
+}
+
+#[test]
+fn schema() {
+    diagrams("examples/schema.dberd", "schema");
+}
+
+#[test]
+fn diagram() {
+    diagrams("export/diagram.dberd", "diagram");
+}
+
+#[test]
+fn command_line_output_is_the_same() {
+    for (format, extension) in [("dot", "dot"), ("mermaid", "mmd")] {
+        let output = common::dberd()
+            .args(["export", "--format", format])
+            .arg(path("export/diagram.dberd"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let expected = std::fs::read(path(&format!("export/diagram.{extension}"))).unwrap();
+        assert_eq!(output.stdout, expected, "{format}");
+    }
+}
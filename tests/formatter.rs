@@ -0,0 +1,107 @@
+mod common;
+
+use dberd::{formatter, interpret_with, lexer::Token, parser::LiteralValue, Limits};
+
+fn format(name: &str, source: &str, width: usize) -> String {
+    let (tokens, module) = common::parse(name, source);
+    formatter::format(source, &tokens, &module, width)
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    for (name, source) in common::examples() {
+        for width in [20, 40, 80, 120] {
+            let once = format(&name, &source, width);
+            let twice = format(&name, &once, width);
+            assert_eq!(once, twice, "formatting {name} at width {width} twice changed it");
+        }
+    }
+}
+
+#[test]
+fn comments_are_kept() {
+    for (name, source) in common::examples() {
+        let (tokens, _) = common::parse(&name, &source);
+        let comments: Vec<String> = tokens
+            .iter()
+            .filter(|(token, _)| *token == Token::Comment)
+            .map(|(_, span)| source.chars().skip(span.start).take(span.len()).collect())
+            .collect();
+
+        let formatted = format(&name, &source, 80);
+        let mut rest = formatted.as_str();
+        for comment in comments {
+            let Some(at) = rest.find(comment.trim_end()) else {
+                panic!("formatting {name} lost or reordered the comment {comment:?}:\n{formatted}");
+            };
+            rest = &rest[at + comment.trim_end().len()..];
+        }
+    }
+}
+
+#[test]
+fn formatted_code_means_the_same() {
+    for (name, source) in common::examples() {
+        let (_, module) = common::parse(&name, &source);
+        let (_, formatted) = common::parse(&name, &format(&name, &source, 40));
+        assert_eq!(
+            formatter::format_module(&module, 80),
+            formatter::format_module(&formatted, 80),
+            "formatting {name} changed what it means"
+        );
+    }
+}
+
+/// Evaluates a program, which is `source` as a file.
+fn eval(source: &str) -> LiteralValue {
+    let (_, module) = common::parse("value", source);
+    interpret_with(module.expr.unwrap(), &mut (), &Limits::default()).unwrap()
+}
+
+#[test]
+fn values_break_lines_to_fit() {
+    let value = eval(r#"{} -> :from_json{"[[1, 2, 3], {\"name\": \"Ada\", \"born\": 1815}, []]"}"#);
+    assert_eq!(
+        formatter::format_value(&value, 80, None),
+        r#"[[1, 2, 3], {born: 1815, name: "Ada"}, []]"#
+    );
+    assert_eq!(
+        formatter::format_value(&value, 30, None),
+        "[\n    [1, 2, 3],\n    {born: 1815, name: \"Ada\"},\n    []\n]"
+    );
+    assert_eq!(
+        formatter::format_value(&value, 16, None),
+        "[\n    [1, 2, 3],\n    {\n        born: 1815,\n        name: \"Ada\"\n    },\n    []\n]"
+    );
+}
+
+#[test]
+fn record_fields_are_quoted_unless_they_are_words() {
+    let value = eval(r#"{} -> :from_json{"{\"a b\": 1, \"x_1\": 2}"}"#);
+    assert_eq!(formatter::format_value(&value, 80, None), r#"{"a b": 1, x_1: 2}"#);
+}
+
+#[test]
+fn strings_are_escaped_to_parse_back() {
+    for string in ["plain", "quote \" and backslash \\", "tab\tline\nreturn\r", "nul\0", "bell\u{7}", "é 😀"] {
+        let value = LiteralValue::String(string.into());
+        let printed = formatter::format_value(&value, 80, None);
+        assert_eq!(eval(&format!("{{}} -> {printed}")), value, "{string:?} was printed as {printed}");
+    }
+    assert_eq!(
+        formatter::format_value(&LiteralValue::String("say \"hi\"\n".into()), 80, None),
+        r#""say \"hi\"\n""#
+    );
+}
+
+#[test]
+fn functions_are_summarized() {
+    let source = "{} ->\n  let pair = {a, b} ->\n    :add{a, b} in\n  pair";
+    let value = eval(source);
+    assert_eq!(formatter::format_value(&value, 80, None), "<fn arity=2>");
+    assert_eq!(
+        formatter::format_value(&value, 80, Some(("pair.dberd", source))),
+        "<fn arity=2 defined at pair.dberd:2>"
+    );
+    assert_eq!(formatter::format_value(&eval("{} -> {} -> 1"), 80, None), "<fn arity=0>");
+}
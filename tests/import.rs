@@ -0,0 +1,39 @@
+//! Importing SQL, and exporting what was imported.
+mod common;
+
+use common::{assert_golden, path};
+use dberd::{
+    formatter, import,
+    sql::{self, Dialect},
+};
+
+fn import(source: &str) -> (String, Vec<&'static str>) {
+    let (module, diagnostics) = import::sql(source);
+    let codes = diagnostics.iter().map(|diagnostic| diagnostic.code).collect();
+    (formatter::format_module(&module, 80), codes)
+}
+
+#[test]
+fn shop() {
+    let source = std::fs::read_to_string(path("import/shop.sql")).unwrap();
+    let (imported, codes) = import(&source);
+    assert_eq!(codes, ["W0004"], "only user_id should be renamed");
+    assert_golden("import/shop.dberd", &imported);
+}
+
+#[test]
+fn exported_schemas_import_the_same() {
+    let source = std::fs::read_to_string(path("import/shop.sql")).unwrap();
+    let (module, _) = import::sql(&source);
+    for dialect in [Dialect::Postgres, Dialect::Sqlite, Dialect::Mysql] {
+        let exported = sql::ddl(&module, dialect);
+        let (again, diagnostics) = import::sql(&exported);
+        assert!(diagnostics.is_empty(), "{dialect:?}: {diagnostics:?}\n{exported}");
+        assert_eq!(
+            formatter::format_module(&again, 80),
+            formatter::format_module(&module, 80),
+            "{dialect:?}:\n{exported}"
+        );
+        assert_eq!(sql::ddl(&again, dialect), exported, "{dialect:?}");
+    }
+}
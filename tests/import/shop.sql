@@ -0,0 +1,18 @@
+-- A dump with a foreign key declared with its column and one declared on its own.
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE orders (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id),
+    total REAL NOT NULL
+);
+
+CREATE TABLE profiles (
+    id INTEGER PRIMARY KEY,
+    users_id INTEGER NOT NULL UNIQUE,
+    bio TEXT,
+    FOREIGN KEY (users_id) REFERENCES users (id)
+);
@@ -0,0 +1,157 @@
+//! Converting values to and from JSON, with the builtins and with `dberd run --output json`.
+mod common;
+
+use dberd::{
+    json,
+    number::Number,
+    parser::LiteralValue,
+};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use serde_json::json;
+
+fn number(number: Number) -> LiteralValue {
+    LiteralValue::Number(number)
+}
+
+#[test]
+fn integers() {
+    assert_eq!(json::to_json(&number(Number::Integer(i64::MIN))).unwrap(), json!(i64::MIN));
+    let max = number(Number::from(BigInt::from(u64::MAX)));
+    assert_eq!(json::to_json(&max).unwrap(), json!(u64::MAX));
+    assert_eq!(json::parse(&u64::MAX.to_string()).unwrap(), max);
+
+    let too_big = number(Number::from(BigInt::from(u64::MAX) + 1));
+    assert_eq!(json::to_json(&too_big).unwrap_err(), "18446744073709551616 is too big for JSON");
+    assert_eq!(json::parse("18446744073709551616").unwrap(), number(Number::Float(2f64.powi(64))));
+}
+
+#[test]
+fn rationals_are_rejected() {
+    let third = number(Number::from(BigRational::new(1.into(), 3.into())));
+    assert_eq!(json::to_json(&third).unwrap_err(), "1/3 has no exact JSON form; convert it with float first");
+}
+
+#[test]
+fn floats() {
+    assert_eq!(json::to_json(&number(Number::Float(0.5))).unwrap(), json!(0.5));
+    assert_eq!(json::parse("0.5").unwrap(), number(Number::Float(0.5)));
+    for float in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+        assert!(json::to_json(&number(Number::Float(float))).is_err(), "{float}");
+    }
+    assert!(json::parse("1e400").is_err());
+}
+
+#[test]
+fn null_is_rejected() {
+    assert_eq!(json::parse("[1, null]").unwrap_err(), "null has no dberd value");
+}
+
+/// Runs a program, returning whether it succeeded, stdout and stderr.
+fn run(program: &str, flags: &[&str]) -> (bool, String, String) {
+    let file = std::env::temp_dir().join(format!("dberd-json-{}-{:x}.dberd", std::process::id(), hash(program, flags)));
+    std::fs::write(&file, program).unwrap();
+    let output = common::dberd().arg("run").args(flags).arg(&file).output().unwrap();
+    std::fs::remove_file(file).unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// Tells the files of tests running in parallel apart.
+fn hash(program: &str, flags: &[&str]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (program, flags).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn builtins() {
+    let (success, stdout, stderr) = run("{} -> :to_json{:from_json{\"[18446744073709551615, 0.5]\"}}", &[]);
+    assert!(success, "{stderr}");
+    assert_eq!(stdout, "\"[18446744073709551615,0.5]\"\n");
+
+    let (success, stdout, stderr) = run("{} -> :to_json{1 / 3}", &[]);
+    assert!(!success && stdout.is_empty());
+    assert!(stderr.starts_with("[E0014] Error: 1/3 has no exact JSON form"), "{stderr}");
+    assert!(stderr.ends_with("error: Failed to evaluate\n"), "{stderr}");
+}
+
+#[test]
+fn output_json() {
+    let (success, stdout, stderr) = run("{} -> 18446744073709551615", &["--output", "json"]);
+    assert!(success, "{stderr}");
+    assert_eq!(stdout, "18446744073709551615\n");
+
+    for (program, message) in [
+        ("{} -> 18446744073709551616", "18446744073709551616 is too big for JSON"),
+        ("{} -> 1 / 3", "1/3 has no exact JSON form; convert it with float first"),
+    ] {
+        let (success, stdout, stderr) = run(program, &["--output", "json"]);
+        assert!(!success && stdout.is_empty(), "{program}");
+        assert!(stderr.starts_with(&format!("[E0014] Error: {message}.\n")), "{program}: {stderr}");
+
+        let (success, _, stderr) = run(program, &["--output", "json", "--message-format", "json"]);
+        assert!(!success, "{program}");
+        let errors = json_lines(&stderr);
+        assert_eq!(errors.len(), 1, "{program}: {stderr}");
+        assert_eq!(errors[0]["message"], format!("{message}."), "{program}");
+        assert_eq!(errors[0]["code"], "E0014", "{program}");
+        assert_eq!(errors[0]["span"]["byte_start"], 0, "the whole program has no JSON form: {program}");
+    }
+}
+
+#[test]
+fn runtime_errors_are_reported() {
+    let (success, _, stderr) = run("{} -> 1 / 0", &[]);
+    assert!(!success);
+    assert!(stderr.starts_with("[E0014] Error: Can't divide 1 by zero.\n"), "{stderr}");
+    assert!(!stderr.contains("Runtime"), "{stderr}");
+
+    let (success, _, stderr) = run("{} -> 1 + (1 / 0)", &["--message-format", "json"]);
+    assert!(!success);
+    let errors = json_lines(&stderr);
+    assert_eq!(errors.len(), 1, "{stderr}");
+    assert_eq!(errors[0]["code"], "E0014");
+    assert_eq!(errors[0]["span"]["byte_start"], 11, "the span is the failing division");
+    assert_eq!(errors[0]["span"]["byte_end"], 16);
+}
+
+/// Parses stderr written with `--message-format json`, failing if any line isn't JSON.
+fn json_lines(stderr: &str) -> Vec<serde_json::Value> {
+    stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|error| panic!("{error}: {line:?}")))
+        .collect()
+}
+
+#[test]
+fn message_format_json_is_only_json() {
+    let (success, stdout, stderr) = run("{} -> let x = 1 x", &["--message-format", "json"]);
+    assert!(!success && stdout.is_empty());
+    let errors = json_lines(&stderr);
+    assert_eq!(errors.len(), 1, "{stderr}");
+    assert_eq!(errors[0]["code"], "E0002");
+
+    let file = std::env::temp_dir().join(format!("dberd-json-fmt-{}.dberd", std::process::id()));
+    std::fs::write(&file, "{}   ->  1\n").unwrap();
+    let output = common::dberd().args(["--message-format", "json", "fmt", "--check"]).arg(&file).output().unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let errors = json_lines(&stderr);
+    assert_eq!(errors.len(), 1, "{stderr}");
+    assert_eq!(errors[0]["message"], format!("{} is not formatted", file.display()));
+    assert_eq!(errors[0]["file"], file.display().to_string());
+
+    let output = common::dberd().args(["--message-format", "json", "run", "missing.dberd"]).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let errors = json_lines(&stderr);
+    assert_eq!(errors.len(), 1, "{stderr}");
+    assert_eq!(errors[0]["file"], serde_json::Value::Null);
+    assert_eq!(errors[0]["code"], serde_json::Value::Null);
+}
@@ -0,0 +1,74 @@
+//! The hand-written lexer reports the same errors as the chumsky lexer it replaced.
+mod common;
+
+#[path = "lexer/chumsky.rs"]
+mod chumsky;
+
+use ::chumsky::{error::Simple, Parser};
+use dberd::{diagnostics, lexer};
+
+/// Sources that don't lex, each with a different mistake.
+const INVALID: &[&str] = &[
+    // A character that can't start a token.
+    "{} -> 1 < 2",
+    "{} -> 1 <",
+    "{} -> $",
+    // Strings that are never closed.
+    "{} -> \"abc",
+    "{} -> \"abc\n",
+    // Escapes that don't exist, or aren't finished.
+    "{} -> \"\\q\"",
+    "{} -> \"\\u{}\"",
+    "{} -> \"\\u{110000}\"",
+    "{} -> \"\\u{D800}\"",
+    "{} -> \"\\u{1234567}\"",
+    "{} -> \"\\u{zz}\"",
+    "{} -> \"\\u{41\"",
+    "{} -> \"\\u41\"",
+    "{} -> \"abc\\",
+    // Non-ASCII characters outside of strings and comments.
+    "{} -> é",
+    "{} -> x\u{a0}",
+    "{} -> 😀 # 😀",
+];
+
+/// What an error says, sorted so that the order the alternatives were tried in doesn't matter.
+fn describe(error: &Simple<char>) -> String {
+    let mut expected: Vec<_> = error.expected().collect();
+    expected.sort();
+    format!(
+        "{:?} {:?} expected {expected:?} found {:?} while lexing {:?}",
+        error.span(),
+        error.reason(),
+        error.found(),
+        error.label()
+    )
+}
+
+#[test]
+fn errors_match_the_chumsky_lexer() {
+    let old = chumsky::lexer();
+    for source in INVALID {
+        let errors = old.parse(*source).expect_err(source);
+        let expected: Vec<_> = errors.iter().map(describe).collect();
+        let error = lexer::lex(source).expect_err(source);
+        assert_eq!(vec![describe(&error)], expected, "{source:?}");
+    }
+}
+
+#[test]
+fn errors_are_invalid_characters() {
+    for source in INVALID {
+        let diagnostics = diagnostics::lex(source).expect_err(source);
+        let codes: Vec<_> = diagnostics.iter().map(|diagnostic| diagnostic.code).collect();
+        assert_eq!(codes, ["E0001"], "{source:?}");
+    }
+}
+
+#[test]
+fn examples_lex_the_same() {
+    let old = chumsky::lexer();
+    for (name, source) in common::examples() {
+        assert_eq!(lexer::lex(&source).ok(), old.parse(source.as_str()).ok(), "{name}");
+    }
+}
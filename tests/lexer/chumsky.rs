@@ -0,0 +1,141 @@
+//! The lexer as it was before it was written by hand, kept to check that the hand-written one
+//! reports the same errors, and as a baseline for the lexer benchmark.
+
+use chumsky::prelude::*;
+
+use dberd::lexer::{Spanned, Token};
+
+// Token starts are matched with `one_of` rather than `filter`, so that lex errors can say what
+// they expected.
+const DIGITS: &str = "0123456789";
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+pub fn token() -> impl Parser<char, Token, Error = Simple<char>> {
+    let equals = just("=").to(Token::Equals);
+    let plus = just("+").to(Token::Plus);
+    let minus = just("-").to(Token::Minus);
+    let star = just("*").to(Token::Star);
+    let slash = just("/").to(Token::Slash);
+
+    let colon = just(":").to(Token::Colon);
+    let left_brace = just("{").to(Token::LeftBrace);
+    let right_brace = just("}").to(Token::RightBrace);
+    let left_paren = just("(").to(Token::LeftParen);
+    let right_paren = just(")").to(Token::RightParen);
+
+    let comma = just(",").to(Token::Comma);
+    let dot = just(".").to(Token::Dot);
+
+    let arrow = just("->").to(Token::Arrow);
+
+    let comment = just("#")
+        .then(filter(|c: &char| *c != '\n').repeated())
+        .to(Token::Comment);
+
+    let ampersand = just("&").to(Token::Ampersand);
+    let pipe = just("|").to(Token::Pipe);
+    let caret = just("^").to(Token::Caret);
+    let shift_left = just("<<").to(Token::ShiftLeft);
+    let shift_right = just(">>").to(Token::ShiftRight);
+
+    // Everything that could belong to a number is taken, even if it isn't a valid digit, so that
+    // `0b12` is one malformed literal rather than `0b1` followed by `2`. A sign is only part of
+    // the literal right after an exponent.
+    let number = one_of(DIGITS)
+        .chain(
+            one_of("eE")
+                .chain(one_of("+-"))
+                .chain(filter(|c: &char| c.is_ascii_digit()))
+                .or(just('.').chain(filter(|c: &char| c.is_ascii_digit())))
+                .or(filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_').map(|c| vec![c]))
+                .repeated()
+                .flatten(),
+        )
+        .collect()
+        .map(Token::Number);
+
+    let escape = just('\\').ignore_then(choice((
+        just('\\'),
+        just('"'),
+        just('n').to('\n'),
+        just('r').to('\r'),
+        just('t').to('\t'),
+        just('0').to('\0'),
+        filter(|c: &char| c.is_ascii_hexdigit())
+            .repeated()
+            .at_least(1)
+            .at_most(6)
+            .collect::<String>()
+            .delimited_by(just("u{"), just('}'))
+            .try_map(|hex, span| {
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| Simple::custom(span, "Invalid unicode escape"))
+            }),
+    )));
+    let string = just('"')
+        .ignore_then(
+            filter(|c: &char| *c != '\\' && *c != '"')
+                .or(escape)
+                .repeated()
+                .then_ignore(just('"'))
+                .labelled("a string literal"),
+        )
+        .collect()
+        .map(Token::Str);
+
+    // Keywords are only keywords as whole words, so `int` is an identifier rather than `in`
+    // followed by `t`.
+    let word = one_of(LETTERS)
+        .chain(filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_').repeated())
+        .collect::<String>()
+        .map(|word| match word.as_str() {
+            "let" => Token::Let,
+            "in" => Token::In,
+            "true" => Token::True,
+            "false" => Token::False,
+            _ => Token::Ident(word),
+        });
+
+    let symbol = choice((
+        // Arrow must take priority over minus
+        arrow,
+        equals,
+        plus,
+        minus,
+        star,
+        slash,
+        colon,
+        left_brace,
+        right_brace,
+        left_paren,
+        right_paren,
+        comma,
+        dot,
+        comment,
+        ampersand,
+        pipe,
+        caret,
+        shift_left,
+        shift_right,
+    ));
+
+    choice((symbol, number, string, word))
+}
+
+/// Lexes a whole source file. Whitespace between tokens is skipped, but comments are kept as
+/// [`Token::Comment`] so that the formatter can put them back; the parser filters them out.
+pub fn lexer() -> impl Parser<char, Vec<Spanned<Token>>, Error = Simple<char>> {
+    let whitespace = one_of(" \t\r\n").repeated();
+
+    whitespace
+        .clone()
+        .ignore_then(
+            token()
+                .map_with_span(|token, span| (token, span))
+                .then_ignore(whitespace)
+                .repeated(),
+        )
+        .then_ignore(end())
+}
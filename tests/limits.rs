@@ -0,0 +1,107 @@
+//! Evaluating code with limits through the library, like a service running code it doesn't trust.
+mod common;
+
+use std::thread;
+
+use dberd::{
+    diagnostics,
+    enterpreter::{on_stack, MAX_DEPTH},
+    interpret_with,
+    lexer::Spanned,
+    number::Number,
+    parser::{Expr, LiteralValue},
+    resolver, EvalError, Limit, Limits,
+};
+
+/// Calls itself forever, without ever building a value.
+const RECURSION: &str = "{} -> let f = {h} -> :h{h} in :f{f}";
+
+fn parse(source: &str) -> Spanned<Expr> {
+    let tokens = diagnostics::lex(source).expect("the source should lex");
+    let module = diagnostics::parse(source, tokens).expect("the source should parse");
+    assert!(resolver::resolve(&module).diagnostics.is_empty());
+    module.expr.expect("the source should have an expression")
+}
+
+fn run(source: &str, limits: &Limits) -> Result<LiteralValue, EvalError> {
+    interpret_with(parse(source), &mut (), limits)
+}
+
+/// The limit that stopped the evaluation, and what it was set to.
+fn exceeded(result: Result<LiteralValue, EvalError>) -> (Limit, u64) {
+    match result {
+        Err(EvalError::LimitExceeded { limit, max, .. }) => (limit, max),
+        other => panic!("expected a limit to be exceeded, got {other:?}"),
+    }
+}
+
+#[test]
+fn steps() {
+    let limits = Limits { max_steps: Some(1000), ..Limits::default() };
+    assert_eq!(exceeded(run(RECURSION, &limits)), (Limit::Steps, 1000));
+}
+
+#[test]
+fn depth() {
+    let limits = Limits { max_depth: Some(100), ..Limits::default() };
+    assert_eq!(exceeded(run(RECURSION, &limits)), (Limit::Depth, 100));
+}
+
+#[test]
+fn depth_is_capped() {
+    let max = MAX_DEPTH as u64;
+    assert_eq!(exceeded(run(RECURSION, &Limits::default())), (Limit::Depth, max));
+
+    let limits = Limits { max_depth: Some(usize::MAX), ..Limits::default() };
+    assert_eq!(exceeded(run(RECURSION, &limits)), (Limit::Depth, max));
+}
+
+#[test]
+fn depth_does_not_depend_on_the_callers_stack() {
+    let expr = parse(RECURSION);
+    let thread = std::thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(|| exceeded(interpret_with(expr, &mut (), &Limits::default())))
+        .unwrap();
+    assert_eq!(thread.join().unwrap(), (Limit::Depth, MAX_DEPTH as u64));
+}
+
+#[test]
+fn evaluations_share_the_thread_they_run_on() {
+    let (outer, inner, value) = on_stack(|| {
+        let outer = thread::current().id();
+        let inner = on_stack(|| thread::current().id());
+        (outer, inner, run(RECURSION, &Limits::default()))
+    });
+    assert_ne!(outer, thread::current().id());
+    assert_eq!(inner, outer);
+    assert_eq!(exceeded(value), (Limit::Depth, MAX_DEPTH as u64));
+}
+
+#[test]
+fn max_depth_flag_cant_go_over_the_cap() {
+    let output = common::dberd()
+        .args(["run", "--max-depth", "10001", "missing.dberd"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("10001 is not in 0..=10000"), "{stderr}");
+}
+
+#[test]
+fn memory() {
+    let limits = Limits { max_memory: Some(10_000), ..Limits::default() };
+    assert_eq!(exceeded(run(RECURSION, &limits)), (Limit::Memory, 10_000));
+}
+
+#[test]
+fn within_limits() {
+    let limits = Limits {
+        max_steps: Some(100),
+        max_depth: Some(10),
+        max_memory: Some(10_000),
+    };
+    let value = run("{} -> let x = 1 in :add{x, 2}", &limits);
+    assert_eq!(value, Ok(LiteralValue::Number(Number::Integer(3))));
+}
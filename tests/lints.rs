@@ -0,0 +1,115 @@
+mod common;
+
+use dberd::{
+    diagnostics::Severity,
+    lints::{self, Lint, LintOptions},
+    resolver,
+};
+
+/// Has one of every lint, in source order.
+const SOURCE: &str = "entity User { id: int pk, age: int check :ge{18, 0} }
+{} ->
+    let unused = 1 in
+    let f = {} -> 2 in
+    let g = f in
+    let x = 3 in
+    :add{(let x = 4 in x), :add{x, :g{}}}
+";
+
+fn lint(source: &str, options: &LintOptions) -> Vec<(&'static str, Severity)> {
+    let (_, module) = common::parse("source", source);
+    let resolution = resolver::resolve(&module);
+    assert!(resolution.diagnostics.is_empty(), "{:?}", resolution.diagnostics);
+    lints::lint(&module, &resolution, options)
+        .into_iter()
+        .map(|diagnostic| (diagnostic.code, diagnostic.severity))
+        .collect()
+}
+
+#[test]
+fn every_lint_is_reported() {
+    assert_eq!(
+        lint(SOURCE, &LintOptions::default()),
+        [
+            ("W0005", Severity::Warning),
+            ("W0001", Severity::Warning),
+            ("W0003", Severity::Warning),
+            ("W0002", Severity::Warning),
+        ]
+    );
+}
+
+#[test]
+fn clean_code_has_no_lints() {
+    let source = "entity User { id: int pk, age: int check :ge{age, 0} }
+{} -> let f = {x} -> x in :f{1}";
+    assert_eq!(lint(source, &LintOptions::default()), []);
+}
+
+#[test]
+fn allowed_lints_are_not_reported() {
+    let options = LintOptions {
+        allow: vec![Lint::UnusedBinding, Lint::ConstantCondition],
+        ..LintOptions::default()
+    };
+    assert_eq!(lint(SOURCE, &options), [("W0003", Severity::Warning), ("W0002", Severity::Warning)]);
+}
+
+#[test]
+fn denied_lints_are_errors() {
+    let options = LintOptions {
+        deny: vec![Lint::Shadowing],
+        ..LintOptions::default()
+    };
+    assert_eq!(
+        lint(SOURCE, &options),
+        [
+            ("W0005", Severity::Warning),
+            ("W0001", Severity::Warning),
+            ("W0003", Severity::Warning),
+            ("W0002", Severity::Error),
+        ]
+    );
+}
+
+#[test]
+fn deny_warnings_makes_every_lint_an_error() {
+    let options = LintOptions {
+        allow: vec![Lint::UncalledFunction],
+        deny_warnings: true,
+        ..LintOptions::default()
+    };
+    assert_eq!(
+        lint(SOURCE, &options),
+        [("W0005", Severity::Error), ("W0001", Severity::Error), ("W0002", Severity::Error)]
+    );
+}
+
+#[test]
+fn command_line_flags() {
+    let file = std::env::temp_dir().join(format!("dberd-lints-{}.dberd", std::process::id()));
+    std::fs::write(&file, SOURCE).unwrap();
+    let lint = |flags: &[&str]| {
+        let output = common::dberd().arg("lint").args(flags).arg(&file).output().unwrap();
+        (output.status.success(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (success, stderr) = lint(&[]);
+    assert!(success, "{stderr}");
+    assert!(stderr.contains("W0001") && stderr.contains("W0005"), "{stderr}");
+
+    let (success, stderr) = lint(&["--allow", "unused-binding"]);
+    assert!(success && !stderr.contains("W0001"), "{stderr}");
+
+    let (success, stderr) = lint(&["--deny", "shadowing"]);
+    assert!(!success && stderr.contains("1 error(s) found"), "{stderr}");
+
+    let (success, stderr) = lint(&["--deny-warnings"]);
+    assert!(!success && stderr.contains("4 error(s) found"), "{stderr}");
+
+    let allow_all = ["unused-binding", "shadowing", "uncalled-function", "constant-condition"].map(|lint| ["--allow", lint]).concat();
+    let (success, stderr) = lint(&[allow_all.as_slice(), &["--deny-warnings"]].concat());
+    assert!(success && stderr.is_empty(), "{stderr}");
+
+    std::fs::remove_file(file).unwrap();
+}
@@ -0,0 +1,129 @@
+//! Drives the language server like an editor would, with a scripted session.
+
+use std::io::Cursor;
+
+use dberd::lsp::{self, read_message};
+use serde_json::{json, Value};
+
+const BROKEN: &str = "{} -> :add{1, nope}";
+const DOCUMENT: &str = "# doc
+entity User { id: int pk }
+{} ->
+    let double = {x} -> x + x in
+    :double{21}
+";
+
+fn frame(content: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{content}", content.len())
+}
+
+fn request(id: i64, method: &str, params: Value) -> String {
+    frame(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string())
+}
+
+fn notification(method: &str, params: Value) -> String {
+    frame(&json!({ "jsonrpc": "2.0", "method": method, "params": params }).to_string())
+}
+
+fn open(uri: &str, text: &str) -> String {
+    notification(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": uri, "languageId": "dberd", "version": 1, "text": text } }),
+    )
+}
+
+fn at(uri: &str, line: u64, character: u64) -> Value {
+    json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+}
+
+/// Runs the server on `messages` and returns everything it wrote.
+fn session(messages: &[String]) -> Vec<Value> {
+    let mut output = Vec::new();
+    lsp::serve(Cursor::new(messages.concat()), &mut output).unwrap();
+
+    let mut output = Cursor::new(output);
+    let mut responses = Vec::new();
+    while let Some(response) = read_message(&mut output).unwrap() {
+        responses.push(response);
+    }
+    responses
+}
+
+fn range(start: (u64, u64), end: (u64, u64)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 },
+    })
+}
+
+#[test]
+fn scripted_session() {
+    let responses = session(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        notification("initialized", json!({})),
+        open("file:///broken.dberd", BROKEN),
+        open("file:///doc.dberd", DOCUMENT),
+        request(2, "textDocument/hover", at("file:///doc.dberd", 4, 5)),
+        request(3, "textDocument/definition", at("file:///doc.dberd", 4, 5)),
+        request(4, "textDocument/documentSymbol", json!({ "textDocument": { "uri": "file:///doc.dberd" } })),
+        request(5, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+        request(6, "textDocument/hover", at("file:///doc.dberd", 4, 5)),
+    ]);
+    let [initialize, broken, document, hover, definition, symbols, shutdown] = responses.as_slice() else {
+        panic!("unexpected responses: {responses:#?}");
+    };
+
+    assert_eq!(initialize["id"], 1);
+    let capabilities = &initialize["result"]["capabilities"];
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+
+    assert_eq!(broken["method"], "textDocument/publishDiagnostics");
+    assert_eq!(broken["params"]["uri"], "file:///broken.dberd");
+    let diagnostics = broken["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0005");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"], range((0, 14), (0, 18)));
+
+    assert_eq!(document["params"]["uri"], "file:///doc.dberd");
+    assert_eq!(document["params"]["diagnostics"], json!([]));
+
+    assert_eq!(hover["id"], 2);
+    let contents = hover["result"]["contents"]["value"].as_str().unwrap();
+    assert!(contents.contains("double: function"), "{contents}");
+    assert_eq!(hover["result"]["range"], range((4, 5), (4, 11)));
+
+    assert_eq!(definition["id"], 3);
+    assert_eq!(definition["result"]["uri"], "file:///doc.dberd");
+    assert_eq!(definition["result"]["range"], range((3, 8), (3, 14)));
+
+    assert_eq!(symbols["id"], 4);
+    let symbols = symbols["result"].as_array().unwrap();
+    let names: Vec<_> = symbols.iter().map(|symbol| symbol["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["User", "double"]);
+    assert_eq!(symbols[0]["children"][0]["name"], "id");
+
+    assert_eq!(shutdown["id"], 5);
+    assert_eq!(shutdown["result"], Value::Null);
+}
+
+#[test]
+fn malformed_messages_get_an_error() {
+    let responses = session(&[
+        frame("{not json"),
+        request(1, "textDocument/unknown", json!({})),
+        request(2, "shutdown", Value::Null),
+    ]);
+    let [malformed, unknown, shutdown] = responses.as_slice() else {
+        panic!("unexpected responses: {responses:#?}");
+    };
+
+    assert_eq!(malformed["id"], Value::Null);
+    assert_eq!(malformed["error"]["code"], -32700);
+    assert_eq!(unknown["id"], 1);
+    assert_eq!(unknown["error"]["code"], -32601);
+    assert_eq!(shutdown["id"], 2);
+}
@@ -0,0 +1,248 @@
+//! Number literals, and how the kinds of numbers combine when evaluated.
+mod common;
+
+use std::ops::Range;
+
+use dberd::{
+    diagnostics, formatter, interpret_with,
+    lexer::Spanned,
+    number::Number,
+    parser::{Expr, LiteralValue},
+    EvalError, Limits,
+};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+
+/// Evaluates `expr` as the body of a program.
+fn eval(expr: &str) -> Result<LiteralValue, EvalError> {
+    let (_, module) = common::parse(expr, &format!("{{}} -> {expr}"));
+    interpret_with(module.expr.unwrap(), &mut (), &Limits::default())
+}
+
+fn number(expr: &str) -> Number {
+    match eval(expr) {
+        Ok(LiteralValue::Number(number)) => number,
+        other => panic!("{expr} evaluated to {other:?}"),
+    }
+}
+
+/// The code, message and span of every error found parsing `expr`, with spans counted from the
+/// start of `expr`.
+fn errors(expr: &str) -> Vec<(&'static str, String, Range<usize>)> {
+    let source = format!("{{}} -> {expr}");
+    let offset = source.len() - expr.len();
+    let tokens = diagnostics::lex(&source).unwrap();
+    let errors = diagnostics::parse(&source, tokens).unwrap_err();
+    errors
+        .into_iter()
+        .map(|error| (error.code, error.message, error.span.start - offset..error.span.end - offset))
+        .collect()
+}
+
+#[test]
+fn integers() {
+    assert_eq!(number("42"), Number::Integer(42));
+    assert_eq!(number("1_000"), Number::Integer(1000));
+    assert_eq!(number("9223372036854775807"), Number::Integer(i64::MAX));
+    assert_eq!(number("9223372036854775808"), Number::Big(BigInt::from(i64::MAX) + 1));
+}
+
+#[test]
+fn negative_literals() {
+    assert_eq!(number("-42"), Number::Integer(-42));
+    assert_eq!(number("-9223372036854775808"), Number::Integer(i64::MIN));
+    assert_eq!(number("-0x10"), Number::Integer(-16));
+    assert_eq!(number("-1.5"), Number::Float(-1.5));
+    assert_eq!(number("-2e3"), Number::Float(-2000.0));
+    assert_eq!(number("1 + -1"), Number::Integer(0));
+}
+
+#[test]
+fn floats() {
+    assert_eq!(number("0.5"), Number::Float(0.5));
+    assert_eq!(number("1_0.2_5"), Number::Float(10.25));
+    assert_eq!(number("1e3"), Number::Float(1000.0));
+    assert_eq!(number("1E3"), Number::Float(1000.0));
+    assert_eq!(number("2.5e-1"), Number::Float(0.25));
+    assert_eq!(number("2.5e+1"), Number::Float(25.0));
+    assert_eq!(number("1e-400"), Number::Float(0.0));
+}
+
+#[test]
+fn integers_and_floats() {
+    assert_eq!(number("1 + 0.5"), Number::Float(1.5));
+    assert_eq!(number("0.5 + 1"), Number::Float(1.5));
+    assert_eq!(number("1 / 2.0"), Number::Float(0.5));
+    assert_eq!(number("1.0 + 1"), Number::Float(2.0));
+    assert_eq!(number(":float{1}"), Number::Float(1.0));
+    assert_eq!(number(":trunc{2.7}"), Number::Integer(2));
+    assert_eq!(number(":trunc{-2.7}"), Number::Integer(-2));
+    assert_eq!(eval(":eq{1, 1.0}").unwrap(), LiteralValue::Boolean(true));
+    assert_eq!(eval(":lt{1, 1.5}").unwrap(), LiteralValue::Boolean(true));
+
+    let error = eval("1.0 & 1").unwrap_err();
+    assert_eq!(error.to_string(), "'&' only works on integers, but got 1.0");
+}
+
+#[test]
+fn over_long_literals_are_invalid() {
+    let digits = "1".to_string() + &"0".repeat(400);
+    assert_eq!(
+        errors(&format!("{digits}.0")),
+        [("E0004", "Float literal is out of range.".to_string(), 0..digits.len() + 2)]
+    );
+    assert_eq!(errors("1e400"), [("E0004", "Float literal is out of range.".to_string(), 0..5)]);
+    assert_eq!(errors("-1e400"), [("E0004", "Float literal is out of range.".to_string(), 1..6)]);
+
+    // Integers have no limit.
+    assert_eq!(number(&digits), Number::Big(BigInt::from(10).pow(400)));
+}
+
+#[test]
+fn exponents_need_digits() {
+    assert_eq!(errors("1e"), [("E0004", "This exponent has no digits.".to_string(), 0..2)]);
+    assert_eq!(errors("1.5E"), [("E0004", "This exponent has no digits.".to_string(), 0..4)]);
+}
+
+fn rational(numerator: i64, denominator: i64) -> Number {
+    Number::Rational(BigRational::new(numerator.into(), denominator.into()))
+}
+
+#[test]
+fn overflowing_integers_become_big() {
+    assert_eq!(number("9223372036854775807 + 1"), Number::Big(BigInt::from(i64::MAX) + 1));
+    assert_eq!(number("-9223372036854775808 + -1"), Number::Big(BigInt::from(i64::MIN) - 1));
+    assert_eq!(number("1 << 64"), Number::Big(BigInt::from(1) << 64));
+    // Results that fit again shrink back.
+    assert_eq!(number("(9223372036854775807 + 1) + -1"), Number::Integer(i64::MAX));
+    assert_eq!(number("(1 << 64) >> 64"), Number::Integer(1));
+}
+
+#[test]
+fn divisions_are_exact() {
+    assert_eq!(number("1 / 3 + 1 / 6"), rational(1, 2));
+    assert_eq!(number("4 / 2"), Number::Integer(2));
+    assert_eq!(number("1 / 3 + 2 / 3"), Number::Integer(1));
+    assert_eq!(number("-1 / 3"), rational(-1, 3));
+    assert_eq!(number("1 / -3"), rational(-1, 3));
+    assert_eq!(number("1 / 3 + 0.5"), Number::Float(1.0 / 3.0 + 0.5));
+    assert_eq!(eval("1 / 0").unwrap_err().to_string(), "Can't divide 1 by zero");
+    assert_eq!(eval("1 / 0.0").unwrap_err().to_string(), "Can't divide 1 by zero");
+}
+
+#[test]
+fn numerators_denominators_and_rounding() {
+    assert_eq!(number(":num{6 / 4}"), Number::Integer(3));
+    assert_eq!(number(":den{6 / 4}"), Number::Integer(2));
+    assert_eq!(number(":num{-6 / 4}"), Number::Integer(-3));
+    assert_eq!(number(":den{-6 / 4}"), Number::Integer(2));
+    assert_eq!(number(":den{5}"), Number::Integer(1));
+    assert_eq!(eval(":num{0.5}").unwrap_err().to_string(), "The float 0.5 has no numerator");
+
+    assert_eq!(number(":round{5 / 2}"), Number::Integer(3));
+    assert_eq!(number(":round{-5 / 2}"), Number::Integer(-3));
+    assert_eq!(number(":round{7 / 3}"), Number::Integer(2));
+    assert_eq!(number(":round{2.5}"), Number::Integer(3));
+    assert_eq!(number(":trunc{-5 / 2}"), Number::Integer(-2));
+    assert_eq!(number(":round{1e20}"), Number::Big(BigInt::from(10).pow(20)));
+}
+
+#[test]
+fn printed_numbers_parse_back_to_the_same_value() {
+    for expr in [
+        "0",
+        "-7",
+        "9223372036854775807 + 1",
+        "-9223372036854775808 + -1",
+        "1 / 3",
+        "-1 / 3",
+        "(1 << 70) / 3",
+        "0.1",
+        "-0.0",
+        "1e300",
+        "1e-300",
+        "1 / 3 + 0.0",
+        ":float{1 << 1000}",
+    ] {
+        let value = eval(expr).unwrap();
+        let printed = formatter::format_value(&value, 80, None);
+        assert_eq!(eval(&printed).unwrap(), value, "{expr} was printed as {printed}");
+    }
+}
+
+#[test]
+fn floats_stay_finite() {
+    for (expr, message) in [
+        ("1e308 + 1e308", "1e308 + 1e308 is too big for a float"),
+        ("1e308 / 0.5", "1e308 / 0.5 is too big for a float"),
+        (":float{1 << 1024}", "A number with 309 digits is too big for a float"),
+        (":float{(-1 << 1030) / 3}", "A number with 310 digits is too big for a float"),
+    ] {
+        assert_eq!(eval(expr).unwrap_err().to_string(), message, "{expr}");
+    }
+}
+
+#[test]
+fn radix_literals() {
+    assert_eq!(number("0x1F"), Number::Integer(31));
+    assert_eq!(number("0xff"), Number::Integer(255));
+    assert_eq!(number("0o17"), Number::Integer(15));
+    assert_eq!(number("0b1010"), Number::Integer(10));
+    assert_eq!(number("0b1010_1010"), Number::Integer(170));
+    assert_eq!(number("1_000_000"), Number::Integer(1_000_000));
+    assert_eq!(number("0xffff_ffff_ffff_ffff"), Number::Big(BigInt::from(u64::MAX)));
+}
+
+#[test]
+fn malformed_literals() {
+    let error = |message: &str, span: Range<usize>| ("E0004", message.to_string(), span);
+    assert_eq!(errors("0x"), [error("This hexadecimal literal has no digits.", 0..2)]);
+    assert_eq!(errors("0b"), [error("This binary literal has no digits.", 0..2)]);
+    assert_eq!(errors("0b2"), [error("'2' is not a binary digit.", 2..3)]);
+    assert_eq!(errors("0b1021"), [error("'2' is not a binary digit.", 4..5)]);
+    assert_eq!(errors("0o8"), [error("'8' is not an octal digit.", 2..3)]);
+    assert_eq!(errors("0x1g"), [error("'g' is not a hexadecimal digit.", 3..4)]);
+    assert_eq!(errors("1__0"), [error("Only one '_' can separate digits.", 1..3)]);
+    assert_eq!(errors("1_"), [error("'_' can only separate digits.", 1..2)]);
+    assert_eq!(errors("0x_1"), [error("'_' can only separate digits.", 2..3)]);
+    assert_eq!(errors("1_.5"), [error("'_' can only separate digits.", 1..2)]);
+    assert_eq!(errors("-0b2"), [error("'2' is not a binary digit.", 3..4)]);
+}
+
+/// Writes out an expression with every binary operation in parentheses.
+fn grouped((expr, _): &Spanned<Expr>) -> String {
+    match expr {
+        Expr::Binary { op, lhs, rhs } => format!("({} {op} {})", grouped(lhs), grouped(rhs)),
+        Expr::Grouping(inner) => grouped(inner),
+        Expr::Literal(LiteralValue::Number(number)) => number.to_string(),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn operator_precedence() {
+    for (expr, expected) in [
+        ("1 | 2 ^ 3 & 4", "(1 | (2 ^ (3 & 4)))"),
+        ("1 & 2 ^ 3 | 4", "(((1 & 2) ^ 3) | 4)"),
+        ("1 << 2 & 3", "((1 << 2) & 3)"),
+        ("1 & 2 << 3", "(1 & (2 << 3))"),
+        ("1 | 2 >> 1", "(1 | (2 >> 1))"),
+        ("1 << 2 >> 3", "((1 << 2) >> 3)"),
+        ("1 + 2 << 3", "((1 + 2) << 3)"),
+        ("1 << 2 + 3", "(1 << (2 + 3))"),
+        ("8 / 2 + 1", "((8 / 2) + 1)"),
+        ("(1 | 2) & 3", "((1 | 2) & 3)"),
+    ] {
+        let (_, module) = common::parse(expr, &format!("{{}} -> {expr}"));
+        let Some((Expr::Literal(LiteralValue::Function { body, .. }), _)) = &module.expr else {
+            panic!("{expr} isn't a program");
+        };
+        assert_eq!(grouped(body), expected, "{expr}");
+    }
+
+    assert_eq!(number("1 | 2 ^ 3 & 6"), Number::Integer(1));
+    assert_eq!(number("6 & 3 ^ 1 | 8"), Number::Integer(11));
+    assert_eq!(number("1 + 1 << 2"), Number::Integer(8));
+    assert_eq!(number("1 << 4 >> 2"), Number::Integer(4));
+    assert_eq!(number("3 & 1 << 1"), Number::Integer(2));
+}
@@ -0,0 +1,28 @@
+//! What `dberd parse` says about source files that don't parse: what it expected, grouped into
+//! kinds of tokens, and what it was parsing at the time.
+mod common;
+
+use common::{assert_golden, path};
+
+#[test]
+fn errors() {
+    let mut sources: Vec<_> = std::fs::read_dir(path("parse"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter_map(|name| name.strip_suffix(".dberd").map(str::to_string))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    for name in sources {
+        // Run from the `tests` directory, so that the paths in the reports are the same everywhere.
+        let output = common::dberd()
+            .args(["parse", "--emit", "ast-json", &format!("parse/{name}.dberd")])
+            .current_dir(path(""))
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{name} parsed");
+        assert!(output.stdout.is_empty(), "{name}");
+        assert_golden(&format!("parse/{name}.stderr"), &String::from_utf8(output.stderr).unwrap());
+    }
+}
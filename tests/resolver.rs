@@ -0,0 +1,129 @@
+//! Which binding every identifier refers to, and what is said about those that refer to none.
+mod common;
+
+use dberd::{
+    interpret_with,
+    number::Number,
+    parser::LiteralValue,
+    resolver::{self, suggestion, BindingKind},
+    Limits,
+};
+
+/// Each identifier that refers to a binding, with the offset of that binding.
+type References = Vec<(String, usize)>;
+
+/// The label and help of each unknown identifier.
+type Unknown = Vec<(String, Option<String>)>;
+
+/// Resolves `source`, returning its references in source order and its unknown identifiers.
+fn resolve(source: &str) -> (References, Unknown) {
+    let (_, module) = common::parse("source", source);
+    let resolution = resolver::resolve(&module);
+    let mut references: Vec<_> = resolution
+        .references
+        .iter()
+        .map(|(span, &binding)| (span.start, &source[span.clone()], resolution.bindings[binding].span.start))
+        .collect();
+    references.sort();
+    let diagnostics = resolution
+        .diagnostics
+        .into_iter()
+        .inspect(|diagnostic| assert_eq!(diagnostic.code, "E0005"))
+        .map(|diagnostic| (diagnostic.labels[0].message.clone(), diagnostic.help))
+        .collect();
+    (
+        references.into_iter().map(|(_, name, binding)| (name.to_string(), binding)).collect(),
+        diagnostics,
+    )
+}
+
+fn unknown(name: &str, help: Option<&str>) -> (String, Option<String>) {
+    (format!("'{name}' is not defined in this scope."), help.map(str::to_string))
+}
+
+#[test]
+fn suggestions_allow_one_typo_per_three_characters() {
+    let names = ["len", "length", "assert_eq", "x"];
+    assert_eq!(suggestion("y", names.into_iter()), Some("x"));
+    assert_eq!(suggestion("lem", names.into_iter()), Some("len"));
+    assert_eq!(suggestion("lne", names.into_iter()), None, "a transposition is two edits");
+    assert_eq!(suggestion("lenght", names.into_iter()), Some("length"));
+    assert_eq!(suggestion("asert_qe", names.into_iter()), None);
+    assert_eq!(suggestion("asert_eq", names.into_iter()), Some("assert_eq"));
+    assert_eq!(suggestion("lengthy", names.into_iter()), Some("length"));
+    assert_eq!(suggestion("foo", names.into_iter()), None);
+    assert_eq!(suggestion("le", ["len", "lt"].into_iter()), Some("len"), "ties go to the first");
+    assert_eq!(suggestion("x", std::iter::empty()), None);
+}
+
+#[test]
+fn unknown_identifiers_suggest_names_in_scope() {
+    let (_, diagnostics) = resolve("{} -> let total = 1 in totl");
+    assert_eq!(diagnostics, [unknown("totl", Some("Did you mean 'total'?"))]);
+
+    let (_, diagnostics) = resolve("{} -> :ad{1, 2}");
+    assert_eq!(diagnostics, [unknown("ad", Some("Did you mean 'add'?"))]);
+
+    // Names that went out of scope aren't suggested.
+    let (_, diagnostics) = resolve("{} -> :add{let total = 1 in total, totl}");
+    assert_eq!(diagnostics, [unknown("totl", None)]);
+}
+
+#[test]
+fn lets_are_only_visible_in_their_body() {
+    let (references, diagnostics) = resolve("{} -> let x = x in :add{x, (let y = 1 in y), y}");
+    assert_eq!(references, [("x".into(), 10), ("y".into(), 32)]);
+    assert_eq!(diagnostics, [unknown("x", None), unknown("y", Some("Did you mean 'x'?"))]);
+}
+
+#[test]
+fn parameters_are_only_visible_in_their_function() {
+    let (references, diagnostics) = resolve("{} -> let f = {a, b} -> :add{a, b} in :f{a, 1}");
+    assert_eq!(references, [("a".into(), 15), ("b".into(), 18), ("f".into(), 10)]);
+    assert_eq!(diagnostics, [unknown("a", Some("Did you mean 'f'?"))]);
+}
+
+#[test]
+fn inner_bindings_shadow_outer_ones() {
+    let (references, diagnostics) = resolve("{} -> let x = 1 in :add{let x = x in x, x}");
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    // The value of the inner `let` still sees the outer `x`.
+    assert_eq!(references, [("x".into(), 10), ("x".into(), 28), ("x".into(), 10)]);
+
+    let (references, _) = resolve("{} -> let x = 1 in {x} -> x");
+    assert_eq!(references, [("x".into(), 20)]);
+}
+
+#[test]
+fn bindings_know_what_they_are() {
+    let source = "entity T { id: int pk, n: int check :ge{n, 0} }\n{} -> let f = {a} -> a in :f{1}";
+    let (_, module) = common::parse("source", source);
+    let resolution = resolver::resolve(&module);
+    let kinds: Vec<_> = resolution
+        .bindings
+        .iter()
+        .map(|binding| {
+            let kind = match binding.kind {
+                BindingKind::Let { .. } => "let",
+                BindingKind::Parameter => "parameter",
+                BindingKind::Column { .. } => "column",
+            };
+            (binding.name, kind)
+        })
+        .collect();
+    // The value of a `let` is resolved before its name is bound.
+    assert_eq!(kinds, [("a", "parameter"), ("f", "let"), ("id", "column"), ("n", "column")]);
+}
+
+/// Resolution is lexical, but the interpreter looks names up in the call that is running, so a
+/// name that is only bound where a function is called is unknown to the resolver.
+#[test]
+fn names_only_bound_where_a_function_is_called_are_unknown() {
+    let source = "{} -> let f = {} -> x in let x = 1 in :f{}";
+    let (_, diagnostics) = resolve(source);
+    assert_eq!(diagnostics, [unknown("x", None)]);
+
+    let (_, module) = common::parse("source", source);
+    let value = interpret_with(module.expr.unwrap(), &mut (), &Limits::default()).unwrap();
+    assert_eq!(value, LiteralValue::Number(Number::Integer(1)));
+}
@@ -0,0 +1,64 @@
+//! Resolving and type-checking the defaults and checks of columns.
+mod common;
+
+use dberd::resolver;
+
+/// Resolves an entity, returning the code and the label of each diagnostic, with the help if any.
+fn diagnostics(columns: &str) -> Vec<(&'static str, String, Option<String>)> {
+    let source = format!("entity T {{ id: int pk, {columns} }}");
+    let (_, module) = common::parse("source", &source);
+    resolver::resolve(&module)
+        .diagnostics
+        .into_iter()
+        .map(|diagnostic| (diagnostic.code, diagnostic.labels[0].message.clone(), diagnostic.help))
+        .collect()
+}
+
+#[test]
+fn checks_see_the_columns() {
+    assert_eq!(diagnostics("age: int check :ge{age, 0}, name: string check :gt{:len{name}, age}"), []);
+}
+
+#[test]
+fn unknown_names_in_checks_are_reported() {
+    assert_eq!(
+        diagnostics("age: int check :foo{age}"),
+        [("E0005", "'foo' is not defined in this scope.".into(), None)]
+    );
+    assert_eq!(
+        diagnostics("age: int check :ge{agee, 0}"),
+        [("E0005", "'agee' is not defined in this scope.".into(), Some("Did you mean 'age'?".into()))]
+    );
+}
+
+#[test]
+fn defaults_dont_see_the_columns() {
+    assert_eq!(
+        diagnostics("age: int, older: int default age"),
+        [("E0005", "'age' is not defined in this scope.".into(), Some("Did you mean 'ge'?".into()))]
+    );
+}
+
+#[test]
+fn exact_integer_divisions_are_integers() {
+    assert_eq!(diagnostics("half: int default 4 / 2"), []);
+    assert_eq!(diagnostics("half: int default :trunc{7 / 2} / 3"), []);
+    assert_eq!(
+        diagnostics("third: int default 1 / 3"),
+        [("E0012", "'third' is an integer, but its default is a fraction.".into(), None)]
+    );
+}
+
+#[test]
+fn divisions_by_zero_are_invalid_defaults() {
+    assert_eq!(diagnostics("bad: int default 1 / 0"), [("E0013", "Can't divide 1 by zero.".into(), None)]);
+}
+
+#[test]
+fn function_bodies_are_checked() {
+    assert_eq!(
+        diagnostics("f: int check let g = {x} -> x & 1.5 in :eq{:g{f}, 1}"),
+        [("E0012", "Expected an integer, but this is a float.".into(), None)]
+    );
+    assert_eq!(diagnostics("f: int check let g = {x} -> x & 1 in :eq{:g{f}, 1}"), []);
+}
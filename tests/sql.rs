@@ -0,0 +1,61 @@
+//! Snapshots of the SQL written for each dialect, and how the foreign keys of relations are
+//! validated.
+mod common;
+
+use common::{assert_golden, parse, path};
+use dberd::{
+    sql::{self, Dialect},
+    validate,
+};
+
+const DIALECTS: [(Dialect, &str); 3] = [
+    (Dialect::Postgres, "postgres"),
+    (Dialect::Sqlite, "sqlite"),
+    (Dialect::Mysql, "mysql"),
+];
+
+fn snapshot(relative: &str, name: &str) {
+    let source = std::fs::read_to_string(path(relative)).unwrap();
+    let (_, module) = parse(relative, &source);
+    for (dialect, dialect_name) in DIALECTS {
+        assert_golden(&format!("sql/{name}.{dialect_name}.sql"), &sql::ddl(&module, dialect));
+    }
+}
+
+#[test]
+fn schema() {
+    snapshot("examples/schema.dberd", "schema");
+}
+
+#[test]
+fn relations() {
+    snapshot("sql/relations.dberd", "relations");
+}
+
+#[test]
+fn runaway_defaults_are_left_out() {
+    let source = std::fs::read_to_string(path("sql/runaway.dberd")).unwrap();
+    let (_, module) = parse("runaway.dberd", &source);
+    let ddl = sql::ddl(&module, Dialect::Postgres);
+    assert!(ddl.contains("of \"count\" has no SQL equivalent."), "{ddl}");
+    assert!(ddl.contains("    \"count\" INTEGER NOT NULL,\n"), "{ddl}");
+}
+
+#[test]
+fn optional_foreign_keys_can_be_empty() {
+    let source = std::fs::read_to_string(path("sql/relations.dberd")).unwrap();
+    let (_, module) = parse("relations.dberd", &source);
+    let data = path("sql/data").display().to_string();
+    let violations = validate::validate(&module, &[data]).unwrap();
+    assert!(violations.is_empty(), "{violations:?}");
+}
+
+#[test]
+fn runaway_defaults_are_violations() {
+    let source = std::fs::read_to_string(path("sql/runaway.dberd")).unwrap();
+    let (_, module) = parse("runaway.dberd", &source);
+    let data = path("sql/runaway").display().to_string();
+    let violations = validate::validate(&module, &[data]).unwrap();
+    let messages: Vec<_> = violations.iter().map(|violation| violation.message.as_str()).collect();
+    assert_eq!(messages, ["The default failed: [E0006] Evaluation went over its step limit of 100000."]);
+}
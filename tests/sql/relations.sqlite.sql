@@ -0,0 +1,34 @@
+CREATE TABLE "User" (
+    "id" INTEGER NOT NULL,
+    "name" TEXT NOT NULL,
+    CONSTRAINT "User_pkey" PRIMARY KEY ("id")
+);
+
+CREATE TABLE "Order" (
+    "id" INTEGER NOT NULL,
+    "user_id" INTEGER,
+    CONSTRAINT "Order_pkey" PRIMARY KEY ("id"),
+    CONSTRAINT "Order_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "User" ("id")
+);
+
+CREATE TABLE "Profile" (
+    "id" INTEGER NOT NULL,
+    "bio" TEXT,
+    "user_id" INTEGER NOT NULL,
+    CONSTRAINT "Profile_pkey" PRIMARY KEY ("id"),
+    CONSTRAINT "Profile_user_id_key" UNIQUE ("user_id"),
+    CONSTRAINT "Profile_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "User" ("id")
+);
+
+CREATE TABLE "Tag" (
+    "name" TEXT NOT NULL,
+    CONSTRAINT "Tag_pkey" PRIMARY KEY ("name")
+);
+
+CREATE TABLE "order_tag" (
+    "order_id" INTEGER NOT NULL,
+    "tag_name" TEXT NOT NULL,
+    CONSTRAINT "order_tag_pkey" PRIMARY KEY ("order_id", "tag_name"),
+    CONSTRAINT "order_tag_order_id_fkey" FOREIGN KEY ("order_id") REFERENCES "Order" ("id"),
+    CONSTRAINT "order_tag_tag_name_fkey" FOREIGN KEY ("tag_name") REFERENCES "Tag" ("name")
+);
@@ -0,0 +1,20 @@
+CREATE TABLE `User` (
+    `id` INTEGER NOT NULL,
+    `email` VARCHAR(255) NOT NULL,
+    `age` INTEGER NOT NULL DEFAULT 18,
+    `nick` VARCHAR(255),
+    CONSTRAINT `User_pkey` PRIMARY KEY (`id`),
+    CONSTRAINT `User_email_key` UNIQUE (`email`),
+    CONSTRAINT `User_email_check` CHECK (CHAR_LENGTH(`email`) > 3),
+    CONSTRAINT `User_age_check` CHECK (`age` >= 18)
+);
+
+CREATE TABLE `Order` (
+    `id` INTEGER NOT NULL,
+    `total` DOUBLE NOT NULL DEFAULT 0,
+    `note` VARCHAR(255) NOT NULL DEFAULT 'none',
+    `user_id` INTEGER NOT NULL,
+    CONSTRAINT `Order_pkey` PRIMARY KEY (`id`),
+    CONSTRAINT `Order_user_id_fkey` FOREIGN KEY (`user_id`) REFERENCES `User` (`id`),
+    CONSTRAINT `Order_total_check` CHECK (`total` >= 0)
+);
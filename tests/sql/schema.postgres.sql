@@ -0,0 +1,20 @@
+CREATE TABLE "User" (
+    "id" INTEGER NOT NULL,
+    "email" TEXT NOT NULL,
+    "age" INTEGER NOT NULL DEFAULT 18,
+    "nick" TEXT,
+    CONSTRAINT "User_pkey" PRIMARY KEY ("id"),
+    CONSTRAINT "User_email_key" UNIQUE ("email"),
+    CONSTRAINT "User_email_check" CHECK (char_length("email") > 3),
+    CONSTRAINT "User_age_check" CHECK ("age" >= 18)
+);
+
+CREATE TABLE "Order" (
+    "id" INTEGER NOT NULL,
+    "total" DOUBLE PRECISION NOT NULL DEFAULT 0,
+    "note" TEXT NOT NULL DEFAULT 'none',
+    "user_id" INTEGER NOT NULL,
+    CONSTRAINT "Order_pkey" PRIMARY KEY ("id"),
+    CONSTRAINT "Order_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "User" ("id"),
+    CONSTRAINT "Order_total_check" CHECK ("total" >= 0)
+);
@@ -0,0 +1,48 @@
+//! `dberd test` counts passing and failing tests and fails if any of them do.
+mod common;
+
+use common::{assert_golden, path};
+
+/// Runs `dberd test` from the `tests` directory, so that the paths it prints are the same
+/// everywhere. Returns whether it succeeded and its stdout.
+fn test(args: &[&str]) -> (bool, String) {
+    let output = common::dberd().arg("test").args(args).current_dir(path("")).output().unwrap();
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn passing() {
+    let (success, stdout) = test(&["test_runner/passing.dberd"]);
+    assert!(success, "{stdout}");
+    assert!(stdout.ends_with("\ntest result: ok. 3 passed; 0 failed; 0 filtered out\n"), "{stdout}");
+}
+
+#[test]
+fn failing() {
+    let (success, stdout) = test(&["test_runner"]);
+    assert!(!success);
+    assert_golden("test_runner/all.stdout", &stdout);
+}
+
+#[test]
+fn filter() {
+    let files = ["test_runner/failing/mixed.dberd", "test_runner/passing.dberd"];
+    let (success, stdout) = test(&[&["--filter", "sum"], files.as_slice()].concat());
+    assert!(!success);
+    assert!(stdout.ends_with("\ntest result: FAILED. 0 passed; 1 failed; 6 filtered out\n"), "{stdout}");
+
+    let (success, stdout) = test(&[&["--filter", "addition"], files.as_slice()].concat());
+    assert!(success, "{stdout}");
+    assert!(stdout.ends_with("\ntest result: ok. 1 passed; 0 failed; 6 filtered out\n"), "{stdout}");
+}
+
+#[test]
+fn files_that_do_not_compile_fail() {
+    let (success, stdout) = test(&["test_runner/failing/broken.dberd"]);
+    assert!(!success);
+    assert_eq!(
+        stdout,
+        "error: test_runner/failing/broken.dberd could not be compiled\n\
+         \ntest result: FAILED. 0 passed; 1 failed; 0 filtered out\n"
+    );
+}